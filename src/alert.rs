@@ -0,0 +1,199 @@
+use std::{collections::HashMap, sync::Arc};
+
+use chrono::{DateTime, Local, NaiveTime, Timelike};
+use lazy_static::lazy_static;
+use reqwest::{Client, ClientBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use strum::Display;
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+
+use crate::config::{self, CONFIG};
+
+lazy_static! {
+    pub static ref ALERTER: Arc<Mutex<Option<Alerter>>> = Arc::new(Mutex::new(None));
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, Display,
+)]
+pub enum Severity {
+    INFO,
+    WARNING,
+    CRITICAL,
+}
+
+/// webhook的消息格式
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Display)]
+pub enum WebhookKind {
+    /// 直接POST告警的json
+    JSON,
+    /// url为https://api.telegram.org/bot<token>/sendMessage，需要配置chat_id
+    TELEGRAM,
+    /// 钉钉群机器人
+    DINGTALK,
+}
+
+/// 一条告警，相同key的告警在去重时间内只发送一次
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Alert {
+    pub key: String,
+    pub severity: Severity,
+    pub title: String,
+    pub message: String,
+    pub time: DateTime<Local>,
+}
+
+impl Alert {
+    pub fn new(
+        key: impl Into<String>,
+        severity: Severity,
+        title: impl Into<String>,
+        message: impl Into<String>,
+    ) -> Alert {
+        Alert {
+            key: key.into(),
+            severity,
+            title: title.into(),
+            message: message.into(),
+            time: Local::now(),
+        }
+    }
+
+    pub fn text(&self) -> String {
+        format!(
+            "[{}] {}\n{}\n{}",
+            self.severity,
+            self.title,
+            self.message,
+            self.time.format("%Y-%m-%d %H:%M:%S")
+        )
+    }
+
+    /// 按webhook格式生成请求body
+    pub fn payload(&self, webhook: &config::Webhook) -> Value {
+        match webhook.kind {
+            WebhookKind::JSON => json!(self),
+            WebhookKind::TELEGRAM => json!({
+                "chat_id": webhook.chat_id.clone().unwrap_or_default(),
+                "text": self.text(),
+            }),
+            WebhookKind::DINGTALK => json!({
+                "msgtype": "text",
+                "text": { "content": self.text() },
+            }),
+        }
+    }
+}
+
+/// 静默时段，格式HH:MM-HH:MM，可以跨零点
+fn in_quiet_hours(quiet_hours: &[String], time: &DateTime<Local>) -> bool {
+    let now = NaiveTime::from_hms_opt(time.hour(), time.minute(), 0).unwrap_or_default();
+    quiet_hours.iter().any(|period| {
+        let Some((start, end)) = period.split_once('-') else {
+            warn!("静默时段格式错误:{}", period);
+            return false;
+        };
+        let parse = |text: &str| NaiveTime::parse_from_str(text.trim(), "%H:%M").ok();
+        match (parse(start), parse(end)) {
+            (Some(start), Some(end)) if start <= end => start <= now && now < end,
+            (Some(start), Some(end)) => now >= start || now < end,
+            _ => {
+                warn!("静默时段格式错误:{}", period);
+                false
+            }
+        }
+    })
+}
+
+pub struct Alerter {
+    config: config::Alert,
+    sent: HashMap<String, DateTime<Local>>,
+    client: Client,
+}
+
+impl Alerter {
+    pub fn new(config: config::Alert) -> Alerter {
+        Alerter {
+            config,
+            sent: HashMap::new(),
+            client: ClientBuilder::new()
+                .timeout(std::time::Duration::from_secs(10))
+                .build()
+                .unwrap(),
+        }
+    }
+
+    /// 低于最低级别、静默时段内非CRITICAL、去重时间内重复的告警不发送
+    pub fn should_send(&self, alert: &Alert) -> bool {
+        if alert.severity < self.config.min_severity {
+            return false;
+        }
+        if alert.severity < Severity::CRITICAL
+            && in_quiet_hours(&self.config.quiet_hours, &alert.time)
+        {
+            info!("静默时段,不发送告警:{}", alert.key);
+            return false;
+        }
+        let dedup = chrono::Duration::seconds(self.config.dedup_seconds as i64);
+        if let Some(last) = self.sent.get(&alert.key) {
+            if alert.time - *last < dedup {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// 至少一个webhook发送成功后才记录，全部失败时下次同样的告警还会发送
+    pub fn record_sent(&mut self, alert: &Alert) {
+        self.sent.insert(alert.key.clone(), alert.time);
+    }
+
+    /// 发送到所有满足级别的webhook，返回发送成功的数量
+    pub async fn send(&mut self, alert: &Alert) -> usize {
+        if !self.should_send(alert) {
+            return 0;
+        }
+        let mut delivered = 0;
+        for webhook in self.config.webhooks.iter() {
+            if alert.severity < webhook.min_severity {
+                continue;
+            }
+            let result = self
+                .client
+                .post(&webhook.url)
+                .json(&alert.payload(webhook))
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            match result {
+                Ok(_) => delivered += 1,
+                Err(e) => error!("发送告警失败:{},{},{:?}", webhook.kind, alert.key, e),
+            }
+        }
+        if delivered > 0 {
+            self.record_sent(alert);
+        }
+        delivered
+    }
+}
+
+/// 记录日志并在后台发送告警，不阻塞调用方
+pub fn notify(alert: Alert) {
+    match alert.severity {
+        Severity::INFO => info!("告警:{}", alert.text()),
+        Severity::WARNING => warn!("告警:{}", alert.text()),
+        Severity::CRITICAL => error!("告警:{}", alert.text()),
+    }
+    tokio::spawn(async move {
+        let alerter = Arc::clone(&ALERTER);
+        let mut locked = alerter.lock().await;
+        if locked.is_none() {
+            let config = Arc::clone(&CONFIG);
+            let alert = config.lock().await.alert.clone();
+            *locked = Some(Alerter::new(alert));
+        }
+        locked.as_mut().unwrap().send(&alert).await;
+    });
+}
@@ -0,0 +1,240 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use chrono::Local;
+use hmac::{Hmac, Mac};
+use reqwest::RequestBuilder;
+use sha2::{Digest, Sha256};
+use tracing::error;
+
+use crate::config;
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub const HEADER_SERVER_ID: &str = "X-Server-Id";
+pub const HEADER_TIMESTAMP: &str = "X-Timestamp";
+pub const HEADER_NONCE: &str = "X-Nonce";
+pub const HEADER_SIGNATURE: &str = "X-Signature";
+pub const HEADER_TOKEN_ISSUED: &str = "X-Token-Issued";
+
+static NONCE: AtomicU64 = AtomicU64::new(0);
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn hmac(key: &[u8], message: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC可以使用任意长度的key");
+    mac.update(message);
+    mac
+}
+
+/// 签发给服务器的token，绑定签发时间(毫秒)，每次下单都不同
+/// 下单时通过环境变量MONITOR_TOKEN和MONITOR_TOKEN_ISSUED传给监控程序
+pub fn issue_token(secret: &str, server_id: u32, issued: i64) -> String {
+    to_hex(
+        &hmac(
+            secret.as_bytes(),
+            format!("{}:{}", server_id, issued).as_bytes(),
+        )
+        .finalize()
+        .into_bytes(),
+    )
+}
+
+/// 待签名内容:请求方法、路径、时间戳、随机数和body的sha256
+pub fn signing_string(
+    method: &str,
+    path: &str,
+    timestamp: i64,
+    nonce: &str,
+    body: &[u8],
+) -> String {
+    format!(
+        "{}\n{}\n{}\n{}\n{}",
+        method.to_uppercase(),
+        path,
+        timestamp,
+        nonce,
+        to_hex(&Sha256::digest(body))
+    )
+}
+
+/// 监控程序对上报请求签名
+#[derive(Debug, Clone)]
+pub struct Signer {
+    pub server_id: u32,
+    issued: i64,
+    token: String,
+}
+
+impl Signer {
+    pub fn new(server_id: u32, issued: i64, token: impl Into<String>) -> Signer {
+        Signer {
+            server_id,
+            issued,
+            token: token.into(),
+        }
+    }
+
+    /// 从环境变量MONITOR_TOKEN和MONITOR_TOKEN_ISSUED读取token，没有token时请求会被服务端拒绝
+    pub fn from_env(server_id: u32) -> Signer {
+        let token = std::env::var("MONITOR_TOKEN").unwrap_or_else(|_| {
+            error!("无法从环境变量中获取:MONITOR_TOKEN");
+            String::new()
+        });
+        let issued = std::env::var("MONITOR_TOKEN_ISSUED")
+            .ok()
+            .and_then(|issued| issued.parse::<i64>().ok())
+            .unwrap_or_else(|| {
+                error!("无法从环境变量中获取:MONITOR_TOKEN_ISSUED");
+                0
+            });
+        Signer::new(server_id, issued, token)
+    }
+
+    pub fn sign(
+        &self,
+        method: &str,
+        path: &str,
+        body: &[u8],
+        timestamp: i64,
+        nonce: &str,
+    ) -> Vec<(&'static str, String)> {
+        let message = signing_string(method, path, timestamp, nonce, body);
+        let signature = hmac(self.token.as_bytes(), message.as_bytes())
+            .finalize()
+            .into_bytes();
+        vec![
+            (HEADER_SERVER_ID, self.server_id.to_string()),
+            (HEADER_TOKEN_ISSUED, self.issued.to_string()),
+            (HEADER_TIMESTAMP, timestamp.to_string()),
+            (HEADER_NONCE, nonce.to_string()),
+            (HEADER_SIGNATURE, to_hex(&signature)),
+        ]
+    }
+
+    /// 给POST请求加上签名头，body需要和发送的内容一致
+    pub fn post(&self, client: &reqwest::Client, url: &str, body: Vec<u8>) -> RequestBuilder {
+        let path = reqwest::Url::parse(url)
+            .map(|url| url.path().to_string())
+            .unwrap_or_default();
+        let now = Local::now();
+        let nonce = format!(
+            "{:x}{:x}",
+            now.timestamp_nanos_opt().unwrap_or_default(),
+            NONCE.fetch_add(1, Ordering::SeqCst)
+        );
+        let mut builder = client.post(url);
+        for (name, value) in self.sign("POST", &path, &body, now.timestamp(), &nonce) {
+            builder = builder.header(name, value);
+        }
+        builder.body(body)
+    }
+}
+
+/// 管理接口使用Authorization: Bearer <admin_token>，未配置admin_token时拒绝所有请求
+pub fn verify_admin(admin_token: Option<&str>, authorization: Option<&str>) -> Result<(), String> {
+    let admin_token = admin_token
+        .filter(|token| !token.is_empty())
+        .ok_or("服务端未配置auth.admin_token")?;
+    let token = authorization
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or("缺少请求头:Authorization")?;
+    // 比较摘要，避免按字节比较泄露token长度和前缀
+    if Sha256::digest(token.trim().as_bytes()) == Sha256::digest(admin_token.as_bytes()) {
+        Ok(())
+    } else {
+        Err("管理token错误".to_string())
+    }
+}
+
+/// 服务端校验签名，拒绝未配置密钥、已吊销、过期和重放的请求
+#[derive(Debug)]
+pub struct Verifier {
+    secret: Option<String>,
+    revoked: HashSet<u32>,
+    revoked_before: HashMap<u32, i64>,
+    max_skew: i64,
+    nonces: HashMap<String, i64>,
+}
+
+impl Verifier {
+    pub fn new(auth: &config::Auth) -> Verifier {
+        Verifier {
+            secret: auth.secret.clone().filter(|secret| !secret.is_empty()),
+            revoked: auth.revoked.iter().copied().collect(),
+            revoked_before: HashMap::new(),
+            max_skew: auth.max_skew,
+            nonces: HashMap::new(),
+        }
+    }
+
+    /// 订单取消或释放时调用，该服务器在now(毫秒)及之前签发的token全部失效，重新下单后签发的不受影响
+    pub fn revoke(&mut self, server_id: u32, now: i64) {
+        self.revoked_before.insert(server_id, now);
+    }
+
+    /// 校验通过返回请求头中的server_id，header按名称取值
+    pub fn verify(
+        &mut self,
+        header: impl Fn(&str) -> Option<String>,
+        method: &str,
+        path: &str,
+        body: &[u8],
+        now: i64,
+    ) -> Result<u32, String> {
+        let secret = self.secret.as_ref().ok_or("服务端未配置auth.secret")?;
+        let get = |name: &str| header(name).ok_or(format!("缺少请求头:{}", name));
+        let server_id = get(HEADER_SERVER_ID)?
+            .parse::<u32>()
+            .map_err(|e| format!("server_id错误:{}", e))?;
+        let issued = get(HEADER_TOKEN_ISSUED)?
+            .parse::<i64>()
+            .map_err(|e| format!("token签发时间错误:{}", e))?;
+        let timestamp = get(HEADER_TIMESTAMP)?
+            .parse::<i64>()
+            .map_err(|e| format!("时间戳错误:{}", e))?;
+        let nonce = get(HEADER_NONCE)?;
+        let signature = from_hex(&get(HEADER_SIGNATURE)?).ok_or("签名格式错误".to_string())?;
+
+        if self.revoked.contains(&server_id) {
+            return Err(format!("服务器已吊销:{}", server_id));
+        }
+        if let Some(revoked) = self.revoked_before.get(&server_id) {
+            if issued <= *revoked {
+                return Err(format!("token已吊销:{},签发于:{}", server_id, issued));
+            }
+        }
+        if (now - timestamp).abs() > self.max_skew {
+            return Err(format!("请求已过期:{},当前:{}", timestamp, now));
+        }
+        let token = issue_token(secret, server_id, issued);
+        let message = signing_string(method, path, timestamp, &nonce, body);
+        hmac(token.as_bytes(), message.as_bytes())
+            .verify_slice(&signature)
+            .map_err(|_| format!("签名错误:{}", server_id))?;
+
+        // 签名正确后再记录随机数，过期的随机数不需要保留
+        let max_skew = self.max_skew;
+        self.nonces
+            .retain(|_, timestamp| (now - *timestamp).abs() <= max_skew);
+        let key = format!("{}:{}", server_id, nonce);
+        if self.nonces.insert(key, timestamp).is_some() {
+            return Err(format!("重复的请求:{},{}", server_id, nonce));
+        }
+        Ok(server_id)
+    }
+}
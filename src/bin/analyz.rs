@@ -1,6 +1,48 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use monitor::log::analyzer::{format_summaries, Analyzer};
+use monitor::log::rule::Rules;
 use monitor::log::Logs;
+
+#[derive(Parser)]
+#[command(about = "离线分析历史挖矿日志和my_logs.json")]
+struct Cli {
+    /// 日志目录，包括子目录
+    #[arg(default_value = "logs")]
+    dir: PathBuf,
+    /// 输出json
+    #[arg(long)]
+    json: bool,
+}
+
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt::init();
-    Logs::monitor().await;
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .init();
+    let cli = Cli::parse();
+    // 有配置文件时使用配置的日志规则和文件名规则
+    let (rules, include) = if std::path::Path::new(".conf.toml").exists() {
+        (
+            Rules::load().await,
+            Logs::get_include(&Logs::get_config().await),
+        )
+    } else {
+        (
+            Rules::default(),
+            Logs::get_include(&monitor::config::Log::default()),
+        )
+    };
+    let mut analyzer = Analyzer::new(rules);
+    if let Err(e) = analyzer.feed_dir(&cli.dir, &include) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+    let summaries = analyzer.summaries();
+    if cli.json {
+        println!("{}", serde_json::to_string_pretty(&summaries).unwrap());
+    } else {
+        print!("{}", format_summaries(&summaries));
+    }
 }
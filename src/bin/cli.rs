@@ -0,0 +1,370 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use clap::{Parser, Subcommand};
+use monitor::config::CONFIG;
+use monitor::server::address::{Address, Wallet};
+use monitor::server::admin;
+use monitor::server::clore::model::CardType;
+use monitor::server::clore::Clore;
+use monitor::server::fleet::{self, FleetFilter};
+use monitor::server::operator::{find_order, format_candidates};
+use monitor::server::ssh::Ssh;
+use monitor::server::stream::{self, StreamQuery};
+use monitor::server::verify;
+use reqwest::Method;
+
+#[derive(Parser)]
+#[command(about = "矿机运维命令行")]
+struct Cli {
+    /// 服务端地址
+    #[arg(long, default_value = "http://127.0.0.1:8888")]
+    server: String,
+    /// 管理接口token，未指定时读取环境变量ADMIN_TOKEN
+    #[arg(long)]
+    token: Option<String>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// 实时查看监控上报的日志和心跳
+    Follow {
+        #[arg(long)]
+        server_id: Option<u32>,
+        #[arg(long)]
+        address: Option<String>,
+        #[arg(long)]
+        kind: Option<String>,
+        /// 连接时回放最近的条数
+        #[arg(long, default_value_t = 20)]
+        replay: usize,
+    },
+    /// 市场上可以租用的服务器和价格
+    Market {
+        /// 同时显示不能租用的服务器和原因
+        #[arg(long)]
+        all: bool,
+    },
+    /// 当前订单
+    Orders,
+    /// 服务端的钱包和部署状态
+    Wallets,
+    /// clore余额和主钱包nimble余额
+    Balance,
+    /// 通过服务端租用服务器，每张卡一个未分配的子钱包地址
+    Rent {
+        server_id: u32,
+        #[arg(long, value_delimiter = ',', required = true)]
+        addresses: Vec<String>,
+        /// 忽略不能租用的原因和显卡数量检查
+        #[arg(long)]
+        force: bool,
+    },
+    /// 取消订单，并重置绑定在该订单上的钱包
+    Cancel { order_id: u32 },
+    /// 通过sftp拉取订单服务器上的日志和任务记录
+    PullLogs { order_id: u32 },
+    /// 检查订单服务器的硬件是否和标注一致，只输出结果，不取消订单
+    Verify { order_id: u32 },
+    /// 拉黑服务器，之后不再租用
+    Block { server_id: u32 },
+    /// 移出黑名单
+    Unblock { server_id: u32 },
+    /// 强制将钱包重置为未分配
+    Reset { address: String },
+    /// 暂停租用新的服务器
+    Pause,
+    /// 恢复租用
+    Resume,
+    /// 查看租用是否暂停
+    Status,
+    /// 运行时添加子钱包，重启后以.conf.toml为准
+    AddSub { address: String },
+    /// 运行时移除未使用的子钱包，重启后以.conf.toml为准
+    RemoveSub { address: String },
+    /// 登录订单对应的服务器，目标为订单号或钱包地址
+    Ssh { target: String },
+    /// 在服务器上并发执行命令，输出相同的服务器合并显示
+    Exec {
+        command: String,
+        /// 所有订单
+        #[arg(long, conflicts_with_all = ["order", "server_id", "card", "wallet"])]
+        all: bool,
+        /// 指定订单号，可以多次指定
+        #[arg(long)]
+        order: Vec<u32>,
+        /// 指定服务器，可以多次指定
+        #[arg(long)]
+        server_id: Vec<u32>,
+        /// 显卡型号，如NVIDIA4090，可以多次指定
+        #[arg(long)]
+        card: Vec<String>,
+        /// 钱包地址，按部署的服务器匹配，可以多次指定
+        #[arg(long)]
+        wallet: Vec<String>,
+        /// 同时执行的服务器数量，默认为ssh.concurrency
+        #[arg(long)]
+        concurrency: Option<usize>,
+    },
+}
+
+async fn fetch_wallets(server: &str, token: &str) -> Result<Vec<Wallet>, String> {
+    let url = format!("{}/api/wallets", server.trim_end_matches('/'));
+    reqwest::Client::new()
+        .get(url)
+        .bearer_auth(token)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.to_string())?
+        .json::<Vec<Wallet>>()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+async fn ssh(server: &str, token: &str, target: &str) -> Result<(), String> {
+    let orders = Clore::default().my_orders().await?;
+    let wallets = if target.parse::<u32>().is_ok() {
+        Vec::new()
+    } else {
+        fetch_wallets(server, token).await?
+    };
+    let order = find_order(target, &orders, &wallets)?;
+    let (Some(sshaddr), Some(sshport)) = (order.get_ssh_host(), order.get_map_ssh_port()) else {
+        return Err(format!("server_id:{}无法进行远程链接", order.server_id));
+    };
+    let private_key = Ssh::get_config()
+        .await
+        .key_dir
+        .map(|key_dir| Ssh::key_path(&key_dir, order.server_id))
+        .filter(|path| path.exists());
+    let mut command = std::process::Command::new("ssh");
+    if let Some(private_key) = &private_key {
+        eprintln!(
+            "serverid:{},orderid:{},使用私钥:{:?}",
+            order.server_id, order.order_id, private_key
+        );
+        command.arg("-i").arg(private_key);
+    } else {
+        eprintln!(
+            "serverid:{},orderid:{},密码见.conf.toml中的clore.ssh_passwd",
+            order.server_id, order.order_id
+        );
+    }
+    let status = command
+        .arg("-p")
+        .arg(sshport.to_string())
+        .arg(format!("root@{}", sshaddr))
+        .status()
+        .map_err(|e| e.to_string())?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("ssh退出:{}", status))
+    }
+}
+
+async fn exec(
+    server: &str,
+    token: &str,
+    command: &str,
+    all: bool,
+    filter: FleetFilter,
+    concurrency: Option<usize>,
+) -> Result<(), String> {
+    if !all && filter.is_empty() {
+        return Err("需要指定--all或筛选条件".to_string());
+    }
+    let orders = Clore::default().my_orders().await?;
+    let wallets = if filter.wallets.is_empty() {
+        Vec::new()
+    } else {
+        fetch_wallets(server, token).await?
+    };
+    let orders = filter.select(&orders, &wallets);
+    if orders.is_empty() {
+        return Err("没有匹配的订单".to_string());
+    }
+    let results = fleet::exec(&orders, command, concurrency).await;
+    print!("{}", fleet::format_groups(&fleet::group(&results)));
+    let failed = results.iter().filter(|host| !host.is_success()).count();
+    if failed > 0 {
+        Err(format!("{}/{}台服务器执行失败", failed, results.len()))
+    } else {
+        Ok(())
+    }
+}
+
+async fn verify(order_id: u32) -> Result<(), String> {
+    let orders = Clore::default().my_orders().await?;
+    let order = orders
+        .iter()
+        .find(|order| order.order_id == order_id)
+        .ok_or(format!("订单不存在:{}", order_id))?;
+    let hardware = verify::inspect(order).await.map_err(|e| e.to_string())?;
+    println!("标注:{}", order.specs.gpu);
+    println!("实际:{}", hardware);
+    let config = Arc::clone(&CONFIG);
+    let config = config.lock().await.verify.clone();
+    let mismatches = verify::check(&order.specs, &hardware, &config)?;
+    if mismatches.is_empty() {
+        println!("硬件和标注一致");
+        return Ok(());
+    }
+    for mismatch in mismatches.iter() {
+        println!("{}", mismatch);
+    }
+    Err(format!("{}项和标注不一致", mismatches.len()))
+}
+
+async fn run(cli: Cli) -> Result<(), String> {
+    let token = cli
+        .token
+        .or_else(|| std::env::var("ADMIN_TOKEN").ok())
+        .unwrap_or_default();
+    let server = cli.server.as_str();
+    let admin = |method: Method, path: String| {
+        let token = token.clone();
+        async move {
+            let message = admin::request(server, &token, method, &path).await?;
+            println!("{}", message);
+            Ok(())
+        }
+    };
+    match cli.command {
+        Command::Follow {
+            server_id,
+            address,
+            kind,
+            replay,
+        } => {
+            let query = StreamQuery {
+                server_id,
+                address,
+                kind,
+                replay: Some(replay),
+            };
+            stream::follow(server, &token, &query).await
+        }
+        Command::Market { all } => {
+            let mut candidates = Clore::default()
+                .market()
+                .await?
+                .candidates(&Clore::import_block_server_ids());
+            if !all {
+                candidates.retain(|candidate| candidate.is_available());
+            }
+            print!("{}", format_candidates(&candidates));
+            Ok(())
+        }
+        Command::Orders => {
+            let orders = Clore::default().my_orders().await?;
+            print!("{}", orders);
+            Ok(())
+        }
+        Command::Wallets => {
+            let wallets = fetch_wallets(server, &token).await?;
+            let address = Address(
+                wallets
+                    .into_iter()
+                    .map(|wallet| (wallet.address.clone(), wallet))
+                    .collect::<HashMap<String, Wallet>>(),
+            );
+            println!("{}", address);
+            Ok(())
+        }
+        Command::Balance => {
+            println!("clore:{}", Clore::default().wallet().await?);
+            let config = Address::default().load_address_file().await;
+            for address in config.mst_address.iter() {
+                match Address::get_balance(address).await {
+                    Some(balance) => println!("{}:{}", address, balance),
+                    None => println!("{}:查询失败", address),
+                }
+            }
+            Ok(())
+        }
+        Command::Rent {
+            server_id,
+            addresses,
+            force,
+        } => {
+            admin(
+                Method::POST,
+                format!(
+                    "/admin/rent/{}?addresses={}&force={}",
+                    server_id,
+                    addresses.join(","),
+                    force
+                ),
+            )
+            .await
+        }
+        Command::Cancel { order_id } => {
+            admin(Method::POST, format!("/admin/orders/{}/cancel", order_id)).await
+        }
+        Command::PullLogs { order_id } => {
+            admin(
+                Method::POST,
+                format!("/admin/orders/{}/pull_logs", order_id),
+            )
+            .await
+        }
+        Command::Verify { order_id } => verify(order_id).await,
+        Command::Block { server_id } => {
+            admin(Method::POST, format!("/admin/blocklist/{}", server_id)).await
+        }
+        Command::Unblock { server_id } => {
+            admin(Method::DELETE, format!("/admin/blocklist/{}", server_id)).await
+        }
+        Command::Reset { address } => {
+            admin(Method::POST, format!("/admin/wallets/{}/reset", address)).await
+        }
+        Command::Pause => admin(Method::POST, "/admin/pool/pause".to_string()).await,
+        Command::Resume => admin(Method::POST, "/admin/pool/resume".to_string()).await,
+        Command::Status => admin(Method::GET, "/admin/pool".to_string()).await,
+        Command::AddSub { address } => {
+            admin(Method::POST, format!("/admin/sub_address/{}", address)).await
+        }
+        Command::RemoveSub { address } => {
+            admin(Method::DELETE, format!("/admin/sub_address/{}", address)).await
+        }
+        Command::Ssh { target } => ssh(server, &token, &target).await,
+        Command::Exec {
+            command,
+            all,
+            order,
+            server_id,
+            card,
+            wallet,
+            concurrency,
+        } => {
+            let card_types = card
+                .iter()
+                .map(|card| {
+                    CardType::from_str(&card.to_uppercase())
+                        .map_err(|_| format!("未知显卡型号:{}", card))
+                })
+                .collect::<Result<Vec<CardType>, String>>()?;
+            let filter = FleetFilter {
+                order_ids: order,
+                server_ids: server_id,
+                card_types,
+                wallets: wallet,
+            };
+            exec(&cli.server, &token, &command, all, filter, concurrency).await
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    if let Err(e) = run(cli).await {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
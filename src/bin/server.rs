@@ -1,9 +1,23 @@
+use std::sync::Arc;
+
 use actix_web::{App, HttpServer};
+use monitor::config::CONFIG;
 use monitor::server::address::pool;
+use monitor::server::admin;
+use monitor::server::api;
 use monitor::server::distribute_address;
+use monitor::server::earnings;
+use monitor::server::logs;
+use monitor::server::logs_stream;
 use monitor::server::printlnlog;
+use monitor::server::profitability;
+use monitor::server::pull;
+use monitor::server::report_hashrate;
+use monitor::server::report_task;
+use monitor::server::store::retention;
 use time::macros::format_description;
 use time::UtcOffset;
+use tracing::info;
 use tracing_subscriber::fmt::time::OffsetTime;
 
 #[tokio::main]
@@ -14,18 +28,55 @@ async fn main() -> std::io::Result<()> {
     );
     tracing_subscriber::fmt().with_timer(local_time).init();
 
-    pool().await;
-    // let mut tasks: Vec<_> = Vec::new();
-    // tasks.push(tokio::spawn(pool()));
+    let config = Arc::clone(&CONFIG);
+    let bind = config
+        .lock()
+        .await
+        .server
+        .bind_addr()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    info!("服务端监听:{}:{}", bind.0, bind.1);
+
+    let tasks = vec![
+        tokio::spawn(pool()),
+        tokio::spawn(retention()),
+        tokio::spawn(pull::schedule()),
+    ];
 
-    // let _ = HttpServer::new(|| App::new().service(distribute_address).service(printlnlog))
-    //     .bind(("0.0.0.0", 8888))?
-    //     .run()
-    //     .await;
+    let _ = HttpServer::new(|| {
+        App::new()
+            .service(distribute_address)
+            .service(printlnlog)
+            .service(report_hashrate)
+            .service(report_task)
+            .service(earnings)
+            .service(profitability)
+            .service(logs_stream)
+            .service(logs)
+            .service(api::wallets)
+            .service(api::orders)
+            .service(api::market)
+            .service(api::blocklist)
+            .service(api::metrics)
+            .service(admin::cancel_order)
+            .service(admin::pull_logs)
+            .service(admin::rent_server)
+            .service(admin::block_server)
+            .service(admin::unblock_server)
+            .service(admin::reset_wallet)
+            .service(admin::add_sub_address)
+            .service(admin::remove_sub_address)
+            .service(admin::pool_status)
+            .service(admin::pause_pool)
+            .service(admin::resume_pool)
+    })
+    .bind(bind)?
+    .run()
+    .await;
 
-    // for task in tasks.into_iter() {
-    //     task.abort();
-    // }
+    for task in tasks.into_iter() {
+        task.abort();
+    }
 
     Ok(())
 }
@@ -2,7 +2,7 @@ use std::{
     any::{self, Any},
     fs::OpenOptions,
     io::{BufReader, BufWriter, Read, Write},
-    net::IpAddr,
+    net::{IpAddr, Ipv4Addr},
     sync::Arc,
 };
 
@@ -11,6 +11,8 @@ use serde::{Deserialize, Serialize};
 use tokio::sync::Mutex;
 use tracing::{error, info};
 
+use crate::alert::{Severity, WebhookKind};
+
 lazy_static! {
     pub static ref CONFIG: Arc<Mutex<Config>> = Arc::new(Mutex::new(Config::new()));
 }
@@ -21,9 +23,467 @@ pub struct Address {
     pub sub_address: Vec<String>,
 }
 
+fn monitor_metrics_port() -> u16 {
+    9101
+}
+
+fn monitor_metrics_ip() -> IpAddr {
+    IpAddr::V4(Ipv4Addr::LOCALHOST)
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Monitor {
     pub api_report_log: String,
+    pub api_report_hashrate: Option<String>,
+    /// 监控程序/metrics的监听地址，没有鉴权，默认只监听本机
+    #[serde(default = "monitor_metrics_ip")]
+    pub metrics_ip: IpAddr,
+    /// 监控程序/metrics的端口
+    #[serde(default = "monitor_metrics_port")]
+    pub metrics_port: u16,
+}
+
+impl Monitor {
+    /// 与api_report_log同一服务器的其他接口
+    pub fn get_api(&self, name: &str) -> String {
+        let api = self.api_report_log.trim_end_matches('/');
+        let host = api.rsplit_once('/').map(|(host, _)| host).unwrap_or(api);
+        format!("{}/{}", host, name)
+    }
+
+    /// 未配置时使用api_report_log所在服务器的/report_hashrate
+    pub fn get_api_report_hashrate(&self) -> String {
+        self.api_report_hashrate
+            .clone()
+            .unwrap_or_else(|| self.get_api("report_hashrate"))
+    }
+}
+
+fn log_include() -> String {
+    r"^nimble\w+\.txt$".to_string()
+}
+
+fn log_poll_interval() -> u64 {
+    10
+}
+
+fn spool_max_records() -> usize {
+    10000
+}
+
+fn spool_batch_size() -> usize {
+    100
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Log {
+    #[serde(default)]
+    pub rules: Vec<crate::log::rule::Rule>,
+    /// logs目录下哪些文件是挖矿日志(正则)
+    #[serde(default = "log_include")]
+    pub include: String,
+    /// inotify不可用时轮询日志目录的间隔(秒)
+    #[serde(default = "log_poll_interval")]
+    pub poll_interval: u64,
+    /// 每个地址上报队列最多保留的记录数
+    #[serde(default = "spool_max_records")]
+    pub spool_max_records: usize,
+    /// 每次上报的记录数
+    #[serde(default = "spool_batch_size")]
+    pub spool_batch_size: usize,
+}
+
+impl Default for Log {
+    fn default() -> Self {
+        Log {
+            rules: Vec::new(),
+            include: log_include(),
+            poll_interval: log_poll_interval(),
+            spool_max_records: spool_max_records(),
+            spool_batch_size: spool_batch_size(),
+        }
+    }
+}
+
+fn reward_per_task() -> f64 {
+    2f64
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Accounting {
+    /// 每个成功任务的奖励($NIM)
+    #[serde(default = "reward_per_task")]
+    pub reward_per_task: f64,
+    /// 1 $NIM 折合多少CLORE，用于计算回本
+    pub nim_price: Option<f64>,
+}
+
+impl Default for Accounting {
+    fn default() -> Self {
+        Accounting {
+            reward_per_task: reward_per_task(),
+            nim_price: None,
+        }
+    }
+}
+
+fn retention_days() -> u32 {
+    7
+}
+
+/// 服务端日志存储
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Store {
+    /// 日志保留天数
+    #[serde(default = "retention_days")]
+    pub retention_days: u32,
+}
+
+impl Default for Store {
+    fn default() -> Self {
+        Store {
+            retention_days: retention_days(),
+        }
+    }
+}
+
+fn auth_max_skew() -> i64 {
+    300
+}
+
+/// 监控程序上报接口的鉴权
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Auth {
+    /// 签发服务器token的密钥，未配置时拒绝所有上报
+    pub secret: Option<String>,
+    /// 已吊销的服务器
+    #[serde(default)]
+    pub revoked: Vec<u32>,
+    /// 允许的时间误差(秒)，超过的请求视为过期
+    #[serde(default = "auth_max_skew")]
+    pub max_skew: i64,
+    /// 管理接口和查询接口的token，未配置时拒绝所有请求
+    pub admin_token: Option<String>,
+}
+
+impl Default for Auth {
+    fn default() -> Self {
+        Auth {
+            secret: None,
+            revoked: Vec::new(),
+            max_skew: auth_max_skew(),
+            admin_token: None,
+        }
+    }
+}
+
+fn alert_min_severity() -> Severity {
+    Severity::WARNING
+}
+
+fn alert_dedup_seconds() -> u64 {
+    1800
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Webhook {
+    pub kind: WebhookKind,
+    pub url: String,
+    /// telegram的chat_id
+    pub chat_id: Option<String>,
+    /// 低于该级别的告警不发送到这个webhook
+    #[serde(default = "alert_min_severity")]
+    pub min_severity: Severity,
+}
+
+/// 告警
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Alert {
+    #[serde(default)]
+    pub webhooks: Vec<Webhook>,
+    /// 低于该级别的告警只记录日志
+    #[serde(default = "alert_min_severity")]
+    pub min_severity: Severity,
+    /// 相同告警的去重时间(秒)
+    #[serde(default = "alert_dedup_seconds")]
+    pub dedup_seconds: u64,
+    /// 静默时段，如"23:00-07:00"，期间只发送CRITICAL
+    #[serde(default)]
+    pub quiet_hours: Vec<String>,
+}
+
+impl Default for Alert {
+    fn default() -> Self {
+        Alert {
+            webhooks: Vec::new(),
+            min_severity: alert_min_severity(),
+            dedup_seconds: alert_dedup_seconds(),
+            quiet_hours: Vec::new(),
+        }
+    }
+}
+
+fn ssh_concurrency() -> usize {
+    8
+}
+
+fn ssh_dns_timeout() -> u64 {
+    10
+}
+
+fn ssh_connect_timeout() -> u64 {
+    10
+}
+
+fn ssh_handshake_timeout() -> u64 {
+    15
+}
+
+fn ssh_auth_timeout() -> u64 {
+    15
+}
+
+fn ssh_exec_timeout() -> u64 {
+    30
+}
+
+fn ssh_known_hosts() -> String {
+    "ssh_known_hosts.txt".to_string()
+}
+
+/// 远程ssh检测，超时时间为秒
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Ssh {
+    /// 同时检测的服务器数量
+    #[serde(default = "ssh_concurrency")]
+    pub concurrency: usize,
+    #[serde(default = "ssh_dns_timeout")]
+    pub dns_timeout: u64,
+    #[serde(default = "ssh_connect_timeout")]
+    pub connect_timeout: u64,
+    #[serde(default = "ssh_handshake_timeout")]
+    pub handshake_timeout: u64,
+    #[serde(default = "ssh_auth_timeout")]
+    pub auth_timeout: u64,
+    /// 执行命令并读取输出
+    #[serde(default = "ssh_exec_timeout")]
+    pub exec_timeout: u64,
+    /// 每个服务器的密钥对目录，下单时生成并通过env注入公钥，为空时只使用密码
+    #[serde(default)]
+    pub key_dir: Option<String>,
+    /// 按订单记录主机公钥指纹的文件，为空时不校验
+    #[serde(default = "ssh_known_hosts")]
+    pub known_hosts: String,
+}
+
+impl Default for Ssh {
+    fn default() -> Self {
+        Ssh {
+            concurrency: ssh_concurrency(),
+            dns_timeout: ssh_dns_timeout(),
+            connect_timeout: ssh_connect_timeout(),
+            handshake_timeout: ssh_handshake_timeout(),
+            auth_timeout: ssh_auth_timeout(),
+            exec_timeout: ssh_exec_timeout(),
+            key_dir: None,
+            known_hosts: ssh_known_hosts(),
+        }
+    }
+}
+
+/// 上传到服务器的文件，mode为文件权限
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProvisionFile {
+    pub local: String,
+    pub remote: String,
+    #[serde(default = "provision_file_mode")]
+    pub mode: i32,
+}
+
+/// 部署步骤，命令需要可以重复执行
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProvisionStep {
+    pub name: String,
+    /// per_address时替换{address}和{card_number}，每个地址执行一次
+    pub command: String,
+    #[serde(default)]
+    pub per_address: bool,
+    /// 秒，步骤开始后部署超时重新计时，需要小于25分钟
+    #[serde(default = "provision_step_timeout")]
+    pub timeout: u64,
+}
+
+fn provision_file_mode() -> i32 {
+    0o755
+}
+
+fn provision_step_timeout() -> u64 {
+    60
+}
+
+fn provision_retry_interval() -> u64 {
+    300
+}
+
+fn provision_upload_timeout() -> u64 {
+    300
+}
+
+fn provision_files() -> Vec<ProvisionFile> {
+    ["env.sh", "erust.sh", "execute.sh"]
+        .iter()
+        .map(|name| ProvisionFile {
+            local: name.to_string(),
+            remote: format!("/root/clore/{}", name),
+            mode: provision_file_mode(),
+        })
+        .collect()
+}
+
+fn provision_steps() -> Vec<ProvisionStep> {
+    vec![
+        ProvisionStep {
+            name: "logs".to_string(),
+            command: "mkdir -p /root/clore/logs".to_string(),
+            per_address: false,
+            timeout: provision_step_timeout(),
+        },
+        ProvisionStep {
+            name: "env".to_string(),
+            command: "test -f /root/clore/.env_done || (cd /root/clore && bash env.sh > /root/clore/env.txt 2>&1 && touch /root/clore/.env_done)".to_string(),
+            per_address: false,
+            timeout: 1200,
+        },
+        ProvisionStep {
+            name: "miner".to_string(),
+            command: "pm2 describe nimble{card_number} > /dev/null 2>&1 || (cd /root/clore && bash execute.sh start {card_number} {address})".to_string(),
+            per_address: true,
+            timeout: provision_step_timeout(),
+        },
+    ]
+}
+
+/// 服务器ssh可用后上传脚本并执行部署步骤，不依赖下单时的command
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Provision {
+    #[serde(default)]
+    pub enabled: bool,
+    /// 失败后重试的间隔(秒)，ssh还未启动时下一轮直接重试
+    #[serde(default = "provision_retry_interval")]
+    pub retry_interval: u64,
+    /// 上传文件的超时(秒)，不使用ssh.exec_timeout
+    #[serde(default = "provision_upload_timeout")]
+    pub upload_timeout: u64,
+    #[serde(default = "provision_files")]
+    pub files: Vec<ProvisionFile>,
+    #[serde(default = "provision_steps")]
+    pub steps: Vec<ProvisionStep>,
+}
+
+impl Default for Provision {
+    fn default() -> Self {
+        Provision {
+            enabled: false,
+            retry_interval: provision_retry_interval(),
+            upload_timeout: provision_upload_timeout(),
+            files: provision_files(),
+            steps: provision_steps(),
+        }
+    }
+}
+
+fn pull_interval() -> u64 {
+    600
+}
+
+fn pull_logs_dir() -> String {
+    "/root/clore/logs".to_string()
+}
+
+fn pull_run_logs() -> String {
+    "/root/clore/nimble-miner-public/my_logs.json".to_string()
+}
+
+fn pull_max_bytes() -> u64 {
+    1024 * 1024
+}
+
+/// 通过sftp从服务器拉取挖矿日志和任务记录，监控上报失败时也能看到
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Pull {
+    /// 定时拉取所有订单，关闭时只能通过管理接口手动拉取
+    #[serde(default)]
+    pub enabled: bool,
+    /// 秒
+    #[serde(default = "pull_interval")]
+    pub interval: u64,
+    /// 目录下的<address>.txt
+    #[serde(default = "pull_logs_dir")]
+    pub logs_dir: String,
+    #[serde(default = "pull_run_logs")]
+    pub run_logs: String,
+    /// 每个文件每次最多读取的字节数，剩下的下次读取
+    #[serde(default = "pull_max_bytes")]
+    pub max_bytes: u64,
+}
+
+impl Default for Pull {
+    fn default() -> Self {
+        Pull {
+            enabled: false,
+            interval: pull_interval(),
+            logs_dir: pull_logs_dir(),
+            run_logs: pull_run_logs(),
+            max_bytes: pull_max_bytes(),
+        }
+    }
+}
+
+fn verify_retry_interval() -> u64 {
+    60
+}
+
+fn verify_rating() -> u8 {
+    1
+}
+
+fn verify_tolerance() -> f64 {
+    0.1
+}
+
+fn verify_disk_tolerance() -> f64 {
+    0.3
+}
+
+/// 租用后通过ssh检查显卡、cpu、内存和硬盘是否和标注一致，不一致时取消订单并拉黑
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Verify {
+    #[serde(default)]
+    pub enabled: bool,
+    /// ssh不可用或命令失败后重试的间隔(秒)
+    #[serde(default = "verify_retry_interval")]
+    pub retry_interval: u64,
+    /// 取消订单时的评分
+    #[serde(default = "verify_rating")]
+    pub rating: u8,
+    /// 显存、cpu线程和内存允许比标注少的比例
+    #[serde(default = "verify_tolerance")]
+    pub tolerance: f64,
+    /// 容器内看到的硬盘和标注差别较大，单独设置
+    #[serde(default = "verify_disk_tolerance")]
+    pub disk_tolerance: f64,
+}
+
+impl Default for Verify {
+    fn default() -> Self {
+        Verify {
+            enabled: false,
+            retry_interval: verify_retry_interval(),
+            rating: verify_rating(),
+            tolerance: verify_tolerance(),
+            disk_tolerance: verify_disk_tolerance(),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -32,6 +492,16 @@ pub struct Server {
     pub port: Option<u32>,
 }
 
+impl Server {
+    /// 服务端监听的地址，未配置时监听0.0.0.0:8888
+    pub fn bind_addr(&self) -> Result<(IpAddr, u16), String> {
+        let ip = self.ip.unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+        let port = u16::try_from(self.port.unwrap_or(8888))
+            .map_err(|_| format!("server.port错误:{:?}", self.port))?;
+        Ok((ip, port))
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Clore {
     pub web_api_host: String,
@@ -48,6 +518,24 @@ pub struct Config {
     pub monitor: Monitor,
     pub server: Server,
     pub clore: Clore,
+    #[serde(default)]
+    pub log: Log,
+    #[serde(default)]
+    pub accounting: Accounting,
+    #[serde(default)]
+    pub auth: Auth,
+    #[serde(default)]
+    pub store: Store,
+    #[serde(default)]
+    pub alert: Alert,
+    #[serde(default)]
+    pub ssh: Ssh,
+    #[serde(default)]
+    pub provision: Provision,
+    #[serde(default)]
+    pub pull: Pull,
+    #[serde(default)]
+    pub verify: Verify,
 }
 
 impl Config {
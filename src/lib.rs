@@ -1,4 +1,7 @@
+pub mod alert;
+pub mod auth;
 pub mod config;
 pub mod log;
+pub mod metrics;
 pub mod monitor;
 pub mod server;
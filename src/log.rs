@@ -1,28 +1,44 @@
-use std::fs;
-use std::io::SeekFrom;
+use std::collections::HashSet;
 use std::ops::{Deref, DerefMut};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
+use chrono::{DateTime, Local, NaiveDateTime};
 use indexmap::IndexMap;
 use lazy_static::lazy_static;
 use regex::Regex;
 use reqwest::ClientBuilder;
 use serde::{Deserialize, Serialize};
 use strum::Display;
-use tokio::io::{AsyncBufReadExt, AsyncReadExt};
-use tokio::io::{AsyncSeekExt, BufReader};
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
-use tokio::sync::Mutex;
-use tokio::time::Instant;
+use tokio::sync::{Mutex, Notify};
+use tokio::time::{Duration, Instant};
 use tracing::{error, info, warn};
 
+use crate::auth::Signer;
+use crate::config::CONFIG;
+use crate::metrics::METRICS;
 use crate::monitor::Monitor;
 
+use self::follower::{FileState, Follower, OFFSETS};
+use self::rule::{LogEvent, RuleKind, Rules, Threshold};
+use self::spool::{Spool, SPOOL, TASK_SPOOL};
+use self::watcher::{WatchEvent, Watcher};
+
+pub mod analyzer;
+pub mod follower;
+pub mod rule;
+pub mod spool;
+pub mod watcher;
+
 lazy_static! {
     pub static ref LOG_CHANNEL: Arc<Mutex<(UnboundedSender<Massage>, UnboundedReceiver<Massage>)>> =
         Arc::new(Mutex::new(unbounded_channel::<Massage>()));
     pub static ref LOG_FILES: Arc<Mutex<Logs>> = Arc::new(Mutex::new(Logs::new()));
+    /// 需要重新扫描日志目录
+    pub static ref LOG_SYNC: Arc<Notify> = Arc::new(Notify::new());
 }
 
 #[derive(Debug, Clone)]
@@ -37,12 +53,19 @@ pub struct Massage {
     pub address: String,
     pub msg_type: MsgType,
     pub body: String,
+    pub event: Option<LogEvent>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Log {
     pub filename: PathBuf,
     pub spawn: bool,
+    /// 文件有新内容时唤醒读取
+    #[serde(skip)]
+    changed: Arc<Notify>,
+    /// 文件被删除时通知停止读取
+    #[serde(skip)]
+    stopped: Arc<AtomicBool>,
 }
 
 impl PartialEq for Log {
@@ -51,34 +74,126 @@ impl PartialEq for Log {
     }
 }
 
+impl Log {
+    pub fn new(filename: PathBuf) -> Log {
+        Log {
+            filename,
+            spawn: false,
+            changed: Arc::new(Notify::new()),
+            stopped: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        self.changed.notify_one();
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::SeqCst)
+    }
+
+    /// 等待文件变化，最多等待timeout
+    async fn wait_changed(&self, timeout: Duration) {
+        tokio::select! {
+            _ = self.changed.notified() => {}
+            _ = tokio::time::sleep(timeout) => {}
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Logs(Vec<Log>);
 
 impl Logs {
     pub fn new() -> Logs {
         let mut logs = Logs(Vec::new());
-        let log_path = std::env::current_dir()
-            .unwrap()
-            .join("nimble-miner-public/my_logs.json");
-        logs.add_log_file(log_path);
+        logs.add_log_file(Logs::get_run_logs_path());
         logs
     }
 
+    pub fn get_log_dir() -> PathBuf {
+        std::env::current_dir().unwrap().join("logs")
+    }
+
+    pub fn get_run_logs_path() -> PathBuf {
+        std::env::current_dir()
+            .unwrap()
+            .join("nimble-miner-public/my_logs.json")
+    }
+
+    pub async fn get_config() -> crate::config::Log {
+        let mutex_conf = Arc::clone(&CONFIG);
+        let config = &mutex_conf.lock().await;
+        config.log.clone()
+    }
+
+    /// 配置的日志文件名规则，配置错误时使用默认规则
+    pub fn get_include(config: &crate::config::Log) -> Regex {
+        Regex::new(&config.include).unwrap_or_else(|e| {
+            warn!("日志文件规则错误:{},{}", config.include, e);
+            Regex::new(&crate::config::Log::default().include).unwrap()
+        })
+    }
+
+    /// 加入上报队列，由spool按顺序批量上报
     pub async fn upload(mesage: Massage) {
         if mesage.body.is_empty() {
             return;
         }
-        let api = format!(
-            "{}/{}/{}",
-            Monitor::get_config().await.api_report_log,
-            Monitor::get_server_id().unwrap_or_default(),
-            mesage.address
-        );
-        info!("上报数据:\n{}\n\n{}", api, mesage.body);
-        let client = ClientBuilder::new().build().unwrap();
-        let result = client.post(api).body(mesage.body).send().await;
-        if result.is_err() {
-            error!("上报数据失败:{:?}", result);
+        let spool = Arc::clone(&SPOOL);
+        let mut locked = spool.lock().await;
+        if let Err(e) = (*locked).push(&mesage.address, &mesage.body) {
+            error!("加入上报队列失败:{},{}", mesage.address, e);
+        }
+    }
+
+    /// 定时上报队列中的日志，没有SERVER_ID时不上报
+    pub async fn upload_spool(signer: Option<Signer>) {
+        let Some(signer) = signer else {
+            warn!("没有SERVER_ID,不上报日志");
+            return;
+        };
+        let config = Logs::get_config().await;
+        let spool = Arc::clone(&SPOOL);
+        spool
+            .lock()
+            .await
+            .set_limit(config.spool_max_records, config.spool_batch_size);
+        let task_spool = Arc::clone(&TASK_SPOOL);
+        task_spool
+            .lock()
+            .await
+            .set_limit(config.spool_max_records, 1);
+        let client = ClientBuilder::new()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .unwrap();
+        let mut report_time = Instant::now();
+        loop {
+            let monitor_config = Monitor::get_config().await;
+            Spool::flush(&spool, &client, &monitor_config.api_report_log, &signer).await;
+            let api = monitor_config.get_api("report_task");
+            Spool::flush(&task_spool, &client, &api, &signer).await;
+            let depths = spool.lock().await.depths();
+            let metrics = Arc::clone(&METRICS);
+            let mut metrics_locked = metrics.lock().await;
+            metrics_locked.clear("monitor_spool_depth");
+            for (address, depth) in depths.iter() {
+                metrics_locked.set(
+                    "monitor_spool_depth",
+                    &[("address", address)],
+                    *depth as f64,
+                );
+            }
+            drop(metrics_locked);
+            if report_time.elapsed() >= Duration::from_secs(60) {
+                if !depths.is_empty() {
+                    warn!("上报队列积压:{:?}", depths);
+                }
+                report_time = Instant::now();
+            }
+            tokio::time::sleep(Duration::from_secs(5)).await;
         }
     }
 }
@@ -98,37 +213,42 @@ impl DerefMut for Logs {
 }
 
 impl Logs {
-    pub async fn iter_log_files(&mut self) {
-        let path = std::env::current_dir().unwrap().join("logs");
+    /// 扫描日志目录，只添加符合规则的文件，已删除的文件停止读取
+    pub fn iter_log_files(&mut self, path: &Path, include: &Regex) {
         if !path.exists() {
-            let _ = std::fs::create_dir_all(&path);
+            let _ = std::fs::create_dir_all(path);
         }
-        let result = path.read_dir();
-        for entry in result.unwrap() {
-            if entry.is_ok() {
-                let entry = entry.unwrap();
-                let path: PathBuf = entry.path();
-                if let Ok(metadata) = fs::metadata(&path) {
-                    if metadata.is_file() {
-                        let value = Log {
-                            filename: path,
-                            spawn: false,
-                        };
+        match path.read_dir() {
+            Ok(entries) => {
+                for entry in entries.flatten() {
+                    let path = entry.path();
+                    if Logs::is_log_file(&path, include) && path.is_file() {
+                        let value = Log::new(path);
                         if !(*self).contains(&value) {
                             (*self).push(value);
                         }
                     }
                 }
             }
+            Err(e) => {
+                warn!("读取日志目录失败:{:?},{}", path, e);
+            }
         }
+        for log in (*self).iter() {
+            if log.spawn && !log.is_stopped() && !log.filename.exists() {
+                log.stop();
+            }
+        }
+    }
+
+    pub fn is_log_file(path: &Path, include: &Regex) -> bool {
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| include.is_match(name))
     }
 
     pub fn add_log_file(&mut self, log_path: PathBuf) {
-        let log: Log = Log {
-            filename: log_path,
-            spawn: false,
-        };
-        self.push(log)
+        self.push(Log::new(log_path))
     }
 
     pub async fn read_log_file(log: Log) {
@@ -142,190 +262,171 @@ impl Logs {
             .to_string()
             .replace(".txt", "")
             .replace(".json", "");
-        let result = tokio::fs::OpenOptions::new()
-            .read(true)
-            .open(log.filename.clone())
-            .await
-            .map_err(|e| e.to_string());
-        if let Err(e) = result {
-            error!("{:?}", e);
+        if address == "my_logs" {
+            Logs::read_run_logs(&log).await;
+            Logs::finish(&log).await;
             return;
         }
-        let file = result.unwrap();
-        let mut reader = BufReader::new(file);
-
-        if address == "my_logs" {
-            loop {
-                let cards = Monitor::get_card_number().await.unwrap_or(1);
-                let mut total = 0;
-                let mut total_task = 0;
-                let mut total_task_succss = 0;
-                let mut buff = String::new();
-                let _ = reader.read_to_string(&mut buff).await;
-
-                let s: String = buff
-                    .replace("  ", "")
-                    .replace("\n", "")
-                    .replace("{", "")
-                    .replace("},", "\n")
-                    .replace("}", "")
-                    .replace("[", "")
-                    .replace("]", "")
-                    .replace("\"", "")
-                    .replace(",", " ")
-                    .split("\n")
-                    .map(|row| {
-                        let row = row.replace("WalletAddr:", "").trim().to_string();
-                        total += 2;
-                        total_task += 1;
-                        if row.find("Success").is_some() {
-                            total_task_succss += 1;
-                        }
-                        // row.split(",").map(|s|{
-                        //     s.trim().to_string().split(":").map(|item|{
-                        //         let [key,value] = item.trim();
-                        //     })
-                        // });
-                        row
-                    })
-                    .collect::<Vec<String>>()
-                    .join("\n");
-                println!(
-                    "{} \n{} \n总任务数:{},成功任务数:{},失败任务数:{},合计奖励:{} $NIM,单地址合集奖励:{:.3} $NIM", 
-                    address, s.trim(),total_task,total_task_succss,total_task-total_task_succss,total,total_task_succss/cards);
-                let _ = reader.seek(SeekFrom::Start(0)).await;
-                tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+        let resume = OFFSETS.lock().await.get(&log.filename);
+        let mut follower = match Follower::open(&log.filename, resume).await {
+            Ok(follower) => follower,
+            Err(e) => {
+                error!("打开日志文件失败:{:?},{}", log.filename, e);
+                Logs::finish(&log).await;
+                return;
             }
-        }
-        let mut hashstring = IndexMap::<String, String>::new();
-
-        //拉取任务失败
-        let request_task = Regex::new(r"Failed to init particle").unwrap();
-
-        //第一次启动需要下载相关任务
-        let complex_regex = Regex::new(
-            r"(Generating|Downloading|Map)([\w ]*:)[\t ]+([\d\.]+\%)\|[\S ]+\|[ ]+([\d]+)\/([\d]+)[ +][\[\S]+[ ]+([\d\.]+)[\w<? \w\/\]]+",
-        )
-        .unwrap();
-
-        //获取显卡算力
-        let bit_reg = Regex::new(
-            r"([\d]+%)\|[\S ]+\|[ ]+([\d]+)\/([\d]+)[ +][\[\S]+[ ]+([\d\.]+)[ \w\/\]<?]+",
-        )
-        .unwrap();
-
-        let verify = Regex::new(r"\{'(loss|eval_loss).*}").unwrap();
-
-        // 任务完成时输出
-        let _complated = Regex::new(r"completed the task.*").unwrap();
-
-        let mut instant = tokio::time::Instant::now();
-        let mut lines = reader.lines();
-        while let Ok(some_line) = lines.next_line().await {
-            if let Some(line) = some_line {
-                let line = line.trim().to_string();
-                if line.is_empty() {
-                    continue;
+        };
+
+        let rules = Rules::load().await;
+        let mut events = IndexMap::<String, LogEvent>::new();
+        let mut instant = Instant::now();
+        let mut removed_at: Option<Instant> = None;
+        loop {
+            let (state, lines) = match follower.read_lines().await {
+                Ok(result) => result,
+                Err(e) => {
+                    error!("读取日志文件失败:{:?},{}", log.filename, e);
+                    break;
                 }
-                if verify.captures(&line).is_some() {
-                    continue;
+            };
+            let removed = if state == FileState::REMOVED {
+                // 文件被删除后等待一段时间，期间重新创建会当作轮转处理
+                removed_at.get_or_insert_with(Instant::now).elapsed() > Duration::from_secs(60)
+            } else {
+                removed_at = None;
+                false
+            };
+
+            let idle = lines.is_empty();
+            for line in lines {
+                if let Some(event) = rules.parse(&address, &line) {
+                    if event.kind != RuleKind::IGNORE {
+                        events.insert(event.key(), event);
+                    }
                 }
-                let complex = complex_regex.captures(&line);
-                if complex.is_some() {
-                    let captures = complex.unwrap();
-                    let (_, [operate, extra, percent, prce, total, downspeed]) =
-                        captures.extract::<6>();
-                    let string = format!(
-                        "{} 当前操作:{}{},完成百分比:{},完成进度:{}/{} 下载速度:{}",
-                        address, operate, extra, percent, prce, total, downspeed
-                    );
-                    hashstring.insert(format!("{}{}", operate, extra), string);
-                    continue;
+            }
+
+            if removed || log.is_stopped() {
+                warn!("日志文件已删除,停止监听:{:?}", log.filename);
+                Logs::send_events(&address, &mut events).await;
+                OFFSETS.lock().await.remove(&log.filename);
+                break;
+            }
+            if instant.elapsed() > Duration::from_secs(5) {
+                Logs::send_events(&address, &mut events).await;
+                let mut offsets = OFFSETS.lock().await;
+                offsets.set(&log.filename, follower.offset());
+                if let Err(e) = offsets.save() {
+                    warn!("保存日志读取位置失败:{}", e);
                 }
+                drop(offsets);
+                instant = Instant::now();
+            }
 
-                let bittest = bit_reg.captures(&line);
-                if bittest.is_some() {
-                    let captures = bittest.unwrap();
-                    let (_, [percent, prce, total, it]) = captures.extract::<4>();
-                    let it = it.parse::<f32>().unwrap_or_default();
-                    #[allow(unused_assignments)]
-                    let mut string = String::new();
-                    match it {
-                        it if it > 20f32 => {
-                            // 验算时，这个算力的值非常大，不应该算进到日志里面去
-                            string = format!(
-                                "{} 算力核算 完成百分比:{:<3} 完成进度:{:<5}/{:<5} 当前算力:{}it",
-                                address, percent, prce, total, it
-                            );
-                            hashstring.insert("verify_it".to_string(), string);
-                        }
-                        it if it < 11f32 => {
-                            string = format!(
-                                "{} 异常算力 完成百分比:{:<3} 完成进度:{:<5}/{:<5} 当前算力:{}it",
-                                address, percent, prce, total, it
-                            );
-                            hashstring.insert("need_restart".to_string(), string);
+            if idle {
+                log.wait_changed(Duration::from_secs(1)).await;
+            }
+        }
+        let _ = OFFSETS.lock().await.save();
+        Logs::finish(&log).await;
+    }
+
+    async fn send_events(address: &str, events: &mut IndexMap<String, LogEvent>) {
+        if events.is_empty() {
+            return;
+        }
+        let log_channel = Arc::clone(&LOG_CHANNEL);
+        let locked = log_channel.lock().await;
+        for (_, event) in events.drain(..) {
+            println!("{}", event);
+            let msg_type = if event.need_restart() {
+                warn!("需要重启:{}", event);
+                MsgType::RESTART
+            } else {
+                MsgType::REPORT
+            };
+            let _ = (*locked).0.send(Massage {
+                address: address.to_string(),
+                msg_type,
+                body: serde_json::to_string(&event).unwrap_or_default(),
+                event: Some(event),
+            });
+        }
+    }
+
+    /// 监听结束后重置状态，文件再次出现时重新监听
+    async fn finish(log: &Log) {
+        let log_files = Arc::clone(&LOG_FILES);
+        let mut locked = log_files.lock().await;
+        if let Some(log) = (*locked).iter_mut().find(|l| *l == log) {
+            log.spawn = false;
+            log.stopped.store(false, Ordering::SeqCst);
+        }
+        drop(locked);
+        LOG_SYNC.notify_one();
+    }
+
+    // nimble任务记录，文件变化或每分钟读取一次，新增的任务发送完成事件
+    async fn read_run_logs(log: &Log) {
+        let mut seen =
+            SeenTasks::load(std::env::current_dir().unwrap().join(".run_logs_seen.json"));
+        while !log.is_stopped() {
+            let result = tokio::fs::read_to_string(&log.filename)
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|text| text.parse::<RunLogs>());
+            match result {
+                Ok(run_logs) => {
+                    let tasks = run_logs.take_new(&mut seen);
+                    if !tasks.is_empty() {
+                        let log_channel = Arc::clone(&LOG_CHANNEL);
+                        let locked = log_channel.lock().await;
+                        for task in tasks.iter() {
+                            let event = task.to_event();
+                            info!("任务完成:{}", event);
+                            let _ = (*locked).0.send(Massage {
+                                address: task.wallet_addr.clone(),
+                                msg_type: MsgType::REPORT,
+                                body: serde_json::to_string(&event).unwrap_or_default(),
+                                event: Some(event),
+                            });
                         }
-                        _ => {
-                            // 正常范围算力
-                            string = format!(
-                                "{} 正在任务 完成百分比:{:<3} 完成进度:{:<5}/{:<5} 当前算力:{}it",
-                                address, percent, prce, total, it
-                            );
-
-                            hashstring.insert("work_it".to_string(), string);
+                        drop(locked);
+                        println!("{}", run_logs);
+                        seen.retain(&run_logs);
+                        if let Err(e) = seen.save() {
+                            warn!("保存已发送任务失败:{}", e);
                         }
                     }
-
-                    continue;
-                }
-                if request_task.captures(&line).is_some() {
-                    hashstring.insert("need_restart".to_string(), line.clone());
-                    continue;
                 }
-
-                let string = format!("{} {}", address, line);
-                hashstring.insert(line.to_string(), string);
-            }
-
-            if !hashstring.is_empty() && instant.elapsed() > tokio::time::Duration::from_secs(5) {
-                let body = hashstring
-                    .iter()
-                    .map(|(_, value)| value.clone())
-                    .collect::<Vec<String>>()
-                    .join("\n");
-                let digest = format!("{:?}", md5::compute(body.as_bytes()));
-                let split = "-".repeat(100);
-                hashstring.insert(digest, split);
-
-                println!("{}", body);
-                instant = Instant::now();
-                if hashstring.contains_key("need_restart") {
-                    let restart = hashstring
-                        .get("need_restart")
-                        .unwrap_or(&"".to_string())
-                        .to_string();
-                    let log_channel = Arc::clone(&LOG_CHANNEL);
-                    let locked = log_channel.lock().await;
-                    let _ = (*locked).0.send(Massage {
-                        address: address.clone(),
-                        msg_type: MsgType::RESTART,
-                        body: restart.clone(),
-                    });
-                    drop(locked);
-                    warn!("需要重启:{:?}", restart);
+                Err(e) => {
+                    warn!("读取任务记录失败:{:?},{}", log.filename, e);
                 }
-                hashstring.clear();
             }
+            log.wait_changed(Duration::from_secs(60)).await;
+            // 等待写入完成
+            tokio::time::sleep(Duration::from_millis(200)).await;
         }
     }
 
+    /// 有新文件或文件删除时立即处理，inotify不可用时按poll_interval轮询
     pub async fn monitor() {
+        let log_dir = Logs::get_log_dir();
+        let _ = std::fs::create_dir_all(&log_dir);
+        let config = Logs::get_config().await;
+        let include = Logs::get_include(&config);
+        let poll_interval = config.poll_interval.max(1);
+        let mut dirs = vec![log_dir.clone()];
+        if let Some(dir) = Logs::get_run_logs_path().parent() {
+            if dir.exists() {
+                dirs.push(dir.to_path_buf());
+            }
+        }
+        tokio::spawn(Logs::watch(dirs, include.clone()));
         loop {
             let log_files = Arc::clone(&LOG_FILES);
             let mut log_files_locked = log_files.lock().await;
-            // 日志分析
-            (*log_files_locked).iter_log_files().await;
+            (*log_files_locked).iter_log_files(&log_dir, &include);
             for log in (*log_files_locked).iter_mut() {
                 if !log.spawn && log.filename.exists() {
                     log.spawn = true;
@@ -333,7 +434,49 @@ impl Logs {
                 }
             }
             drop(log_files_locked);
-            tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+            tokio::select! {
+                _ = LOG_SYNC.notified() => {}
+                _ = tokio::time::sleep(Duration::from_secs(poll_interval)) => {}
+            }
+        }
+    }
+
+    async fn watch(dirs: Vec<PathBuf>, include: Regex) {
+        let mut watcher = match Watcher::new(&dirs) {
+            Ok(watcher) => watcher,
+            Err(e) => {
+                warn!("inotify不可用,使用轮询:{}", e);
+                return;
+            }
+        };
+        info!("inotify监听日志目录:{:?}", dirs);
+        loop {
+            let event = match watcher.next().await {
+                Ok(event) => event,
+                Err(e) => {
+                    warn!("inotify出错,使用轮询:{}", e);
+                    return;
+                }
+            };
+            let log_files = Arc::clone(&LOG_FILES);
+            let locked = log_files.lock().await;
+            match event {
+                WatchEvent::CHANGED(path) => match (*locked).iter().find(|l| l.filename == path) {
+                    Some(log) if log.spawn => log.changed.notify_one(),
+                    Some(_) => LOG_SYNC.notify_one(),
+                    None if Logs::is_log_file(&path, &include) => LOG_SYNC.notify_one(),
+                    None => {}
+                },
+                // 只唤醒读取，删除后的等待和停止由读取日志时处理，期间重新创建当作轮转
+                WatchEvent::REMOVED(path) => {
+                    if let Some(log) = (*locked).iter().find(|l| l.filename == path) {
+                        if log.spawn {
+                            log.changed.notify_one();
+                        }
+                    }
+                }
+                WatchEvent::OVERFLOW => LOG_SYNC.notify_one(),
+            }
         }
     }
 }
@@ -346,18 +489,60 @@ pub enum Status {
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct RunLog {
-    #[serde(rename(deserialize = "WalletAddr"))]
+    #[serde(rename = "WalletAddr")]
     pub wallet_addr: String,
-    #[serde(rename(deserialize = "CompletedTime"))]
+    #[serde(rename = "CompletedTime")]
     pub completed_time: String,
-    #[serde(rename(deserialize = "TrainRuntime"))]
+    #[serde(rename = "TrainRuntime")]
     pub trainrun_time: f64,
-    #[serde(rename(deserialize = "Status"))]
+    #[serde(rename = "Status")]
     pub status: Status,
 }
 
+impl RunLog {
+    pub fn key(&self) -> String {
+        format!("{}_{}", self.wallet_addr, self.completed_time)
+    }
+
+    pub fn get_completed_time(&self) -> Option<DateTime<Local>> {
+        NaiveDateTime::parse_from_str(&self.completed_time, "%Y-%m-%d %H:%M:%S")
+            .ok()
+            .and_then(|time| time.and_local_timezone(Local).single())
+    }
+
+    pub fn to_event(&self) -> LogEvent {
+        let mut fields = IndexMap::new();
+        fields.insert("wallet_addr".to_string(), self.wallet_addr.clone());
+        fields.insert("completed_time".to_string(), self.completed_time.clone());
+        fields.insert("runtime".to_string(), format!("{:.3}", self.trainrun_time));
+        fields.insert("status".to_string(), self.status.to_string());
+        LogEvent {
+            address: self.wallet_addr.clone(),
+            rule: "my_logs".to_string(),
+            kind: RuleKind::COMPLETION,
+            fields,
+            value: Some(self.trainrun_time),
+            threshold: Threshold::NORMAL,
+            time: self.get_completed_time().unwrap_or_else(Local::now),
+            line: serde_json::to_string(self).unwrap_or_default(),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
-struct RunLogs(Vec<RunLog>);
+pub struct RunLogs(Vec<RunLog>);
+
+impl FromStr for RunLogs {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<RunLogs, Self::Err> {
+        // 还没有完成任何任务时文件为空
+        if s.trim().is_empty() {
+            return Ok(RunLogs(Vec::new()));
+        }
+        serde_json::from_str::<RunLogs>(s).map_err(|e| e.to_string())
+    }
+}
 
 impl Deref for RunLogs {
     type Target = Vec<RunLog>;
@@ -373,6 +558,65 @@ impl DerefMut for RunLogs {
     }
 }
 
+impl RunLogs {
+    /// 返回没有出现过的任务，并记录到seen中
+    pub fn take_new(&self, seen: &mut HashSet<String>) -> Vec<&RunLog> {
+        (*self)
+            .iter()
+            .filter(|log| seen.insert(log.key()))
+            .collect()
+    }
+}
+
+/// 已经加入上报队列的任务，保存在.run_logs_seen.json，监控重启后不重复上报
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SeenTasks {
+    #[serde(skip)]
+    path: PathBuf,
+    keys: HashSet<String>,
+}
+
+impl SeenTasks {
+    pub fn load(path: PathBuf) -> SeenTasks {
+        let mut seen = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| serde_json::from_str::<SeenTasks>(&text).ok())
+            .unwrap_or_default();
+        seen.path = path;
+        seen
+    }
+
+    /// 只保留任务记录中还存在的任务，避免文件一直变大
+    pub fn retain(&mut self, run_logs: &RunLogs) {
+        let keys = run_logs
+            .iter()
+            .map(|log| log.key())
+            .collect::<HashSet<String>>();
+        self.keys.retain(|key| keys.contains(key));
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let text = serde_json::to_string(self).map_err(|e| e.to_string())?;
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, text).map_err(|e| e.to_string())?;
+        std::fs::rename(&tmp, &self.path).map_err(|e| e.to_string())
+    }
+}
+
+impl Deref for SeenTasks {
+    type Target = HashSet<String>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.keys
+    }
+}
+
+impl DerefMut for SeenTasks {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.keys
+    }
+}
+
 impl std::fmt::Display for RunLogs {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for log in (*self).iter() {
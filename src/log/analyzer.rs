@@ -0,0 +1,325 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Duration, Local};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use super::{
+    rule::{RuleKind, Rules, Threshold},
+    RunLogs, Status,
+};
+
+/// 算力分布(it/s)，验算阶段高于上限的样本单独计数
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HashrateDistribution {
+    pub samples: usize,
+    pub mean: Option<f64>,
+    pub min: Option<f64>,
+    pub p50: Option<f64>,
+    pub p90: Option<f64>,
+    pub max: Option<f64>,
+    /// 低于下限的样本占比(0-1)
+    pub below: f64,
+    pub verifying: usize,
+}
+
+impl HashrateDistribution {
+    fn new(mut values: Vec<f64>, below: usize, verifying: usize) -> HashrateDistribution {
+        values.sort_by(f64::total_cmp);
+        let percentile = |p: f64| {
+            if values.is_empty() {
+                None
+            } else {
+                let index = ((values.len() - 1) as f64 * p).round() as usize;
+                values.get(index).copied()
+            }
+        };
+        HashrateDistribution {
+            samples: values.len(),
+            mean: if values.is_empty() {
+                None
+            } else {
+                Some(values.iter().sum::<f64>() / values.len() as f64)
+            },
+            min: values.first().copied(),
+            p50: percentile(0.5),
+            p90: percentile(0.9),
+            max: values.last().copied(),
+            below: if values.is_empty() {
+                0f64
+            } else {
+                below as f64 / values.len() as f64
+            },
+            verifying,
+        }
+    }
+}
+
+/// 下载阶段最后一次的进度
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DownloadPhase {
+    pub percent: String,
+    pub progress: Option<u64>,
+    pub total: Option<u64>,
+    pub lines: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TaskSummary {
+    pub success: usize,
+    pub failed: usize,
+    pub success_rate: Option<f64>,
+    /// 所有任务的训练时长(秒)
+    pub runtime: f64,
+    /// 日志中completed the task的次数
+    pub completions: usize,
+}
+
+/// 单个地址的汇总
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Summary {
+    pub address: String,
+    pub lines: usize,
+    /// 第一个任务开始训练到最后一次活动(任务完成或日志修改)
+    pub first_seen: Option<DateTime<Local>>,
+    pub last_seen: Option<DateTime<Local>>,
+    /// 秒
+    pub uptime: Option<f64>,
+    pub hashrate: HashrateDistribution,
+    pub downloads: IndexMap<String, DownloadPhase>,
+    /// 按规则统计的错误和需要重启的次数
+    pub failures: BTreeMap<String, usize>,
+    pub tasks: TaskSummary,
+}
+
+#[derive(Debug, Default)]
+struct Collector {
+    summary: Summary,
+    values: Vec<f64>,
+    below: usize,
+    verifying: usize,
+}
+
+impl Collector {
+    fn seen(&mut self, time: DateTime<Local>) {
+        let summary = &mut self.summary;
+        summary.first_seen = Some(summary.first_seen.map_or(time, |first| first.min(time)));
+        summary.last_seen = Some(summary.last_seen.map_or(time, |last| last.max(time)));
+    }
+}
+
+/// 离线分析历史挖矿日志，使用和监控相同的日志规则
+pub struct Analyzer {
+    rules: Rules,
+    collectors: BTreeMap<String, Collector>,
+}
+
+impl Analyzer {
+    pub fn new(rules: Rules) -> Analyzer {
+        Analyzer {
+            rules,
+            collectors: BTreeMap::new(),
+        }
+    }
+
+    fn collector(&mut self, address: &str) -> &mut Collector {
+        self.collectors
+            .entry(address.to_string())
+            .or_insert_with(|| Collector {
+                summary: Summary {
+                    address: address.to_string(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+    }
+
+    /// 挖矿日志的一行，进度条按\r刷新，按\r和\n拆分
+    pub fn feed_line(&mut self, address: &str, line: &str) {
+        for line in line.split(['\r', '\n']).map(|line| line.trim_end()) {
+            if line.is_empty() {
+                continue;
+            }
+            let event = self.rules.parse(address, line);
+            let collector = self.collector(address);
+            collector.summary.lines += 1;
+            let Some(event) = event else {
+                continue;
+            };
+            match event.kind {
+                RuleKind::HASHRATE => match (event.value, event.threshold) {
+                    (_, Threshold::ABOVE) => collector.verifying += 1,
+                    (Some(value), threshold) => {
+                        collector.values.push(value);
+                        if threshold == Threshold::BELOW {
+                            collector.below += 1;
+                        }
+                    }
+                    (None, _) => {}
+                },
+                RuleKind::PROGRESS => {
+                    let field = |name: &str| event.fields.get(name).cloned();
+                    let phase = collector.summary.downloads.entry(event.key()).or_default();
+                    phase.percent = field("percent").unwrap_or_default();
+                    phase.progress = field("progress").and_then(|v| v.parse::<u64>().ok());
+                    phase.total = field("total").and_then(|v| v.parse::<u64>().ok());
+                    phase.lines += 1;
+                }
+                RuleKind::ERROR | RuleKind::RESTART => {
+                    *collector.summary.failures.entry(event.rule).or_default() += 1;
+                }
+                RuleKind::COMPLETION => collector.summary.tasks.completions += 1,
+                RuleKind::IGNORE => {}
+            }
+        }
+    }
+
+    /// 整个日志文件，地址为文件名
+    pub fn feed_log_file(&mut self, path: &Path) -> Result<(), String> {
+        let address = path
+            .file_stem()
+            .and_then(|name| name.to_str())
+            .ok_or(format!("文件名错误:{:?}", path))?
+            .to_string();
+        let bytes = std::fs::read(path).map_err(|e| format!("{:?}:{}", path, e))?;
+        self.feed_line(&address, &String::from_utf8_lossy(&bytes));
+        if let Ok(modified) = std::fs::metadata(path).and_then(|metadata| metadata.modified()) {
+            self.collector(&address)
+                .seen(DateTime::<Local>::from(modified));
+        }
+        Ok(())
+    }
+
+    /// my_logs.json导出的任务记录
+    pub fn feed_run_logs(&mut self, run_logs: &RunLogs) {
+        for log in run_logs.iter() {
+            let collector = self.collector(&log.wallet_addr);
+            let tasks = &mut collector.summary.tasks;
+            match log.status {
+                Status::Success => tasks.success += 1,
+                Status::Failed => tasks.failed += 1,
+            }
+            tasks.runtime += log.trainrun_time;
+            if let Some(completed) = log.get_completed_time() {
+                collector
+                    .seen(completed - Duration::milliseconds((log.trainrun_time * 1000f64) as i64));
+                collector.seen(completed);
+            }
+        }
+    }
+
+    /// 目录下的<address>.txt日志和my_logs*.json，包括子目录
+    pub fn feed_dir(&mut self, dir: &Path, include: &regex::Regex) -> Result<(), String> {
+        let mut paths = Vec::<PathBuf>::new();
+        let mut dirs = vec![dir.to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            let entries = dir.read_dir().map_err(|e| format!("{:?}:{}", dir, e))?;
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else {
+                    paths.push(path);
+                }
+            }
+        }
+        paths.sort();
+        for path in paths.iter() {
+            let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            if name.starts_with("my_logs") && name.ends_with(".json") {
+                let result = std::fs::read_to_string(path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|text| text.parse::<RunLogs>());
+                match result {
+                    Ok(run_logs) => self.feed_run_logs(&run_logs),
+                    Err(e) => warn!("解析任务记录失败:{:?},{}", path, e),
+                }
+            } else if include.is_match(name) {
+                if let Err(e) = self.feed_log_file(path) {
+                    warn!("读取日志失败:{}", e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn summaries(&self) -> Vec<Summary> {
+        self.collectors
+            .values()
+            .map(|collector| {
+                let mut summary = collector.summary.clone();
+                summary.hashrate = HashrateDistribution::new(
+                    collector.values.clone(),
+                    collector.below,
+                    collector.verifying,
+                );
+                let tasks = &mut summary.tasks;
+                let total = tasks.success + tasks.failed;
+                if total > 0 {
+                    tasks.success_rate = Some(tasks.success as f64 / total as f64);
+                }
+                // 只有一个时间点时无法计算运行时长
+                summary.uptime = match (summary.first_seen, summary.last_seen) {
+                    (Some(first), Some(last)) if first < last => {
+                        Some((last - first).num_milliseconds() as f64 / 1000f64)
+                    }
+                    _ => None,
+                };
+                summary
+            })
+            .collect()
+    }
+}
+
+fn format_option(value: Option<f64>) -> String {
+    value
+        .map(|value| format!("{:.2}", value))
+        .unwrap_or("-".to_string())
+}
+
+/// 表格输出，每个地址一行，下载阶段和错误附在下面
+pub fn format_summaries(summaries: &[Summary]) -> String {
+    let mut text = format!(
+        "{:<46} {:>8} {:>9} {:>7} {:>7} {:>7} {:>7} {:>6} {:>8} {:>8}\n",
+        "地址", "行数", "运行(时)", "平均it", "p50", "p90", "最低", "低于%", "任务", "成功率"
+    );
+    for summary in summaries.iter() {
+        let hashrate = &summary.hashrate;
+        let tasks = &summary.tasks;
+        text.push_str(&format!(
+            "{:<46} {:>8} {:>9} {:>7} {:>7} {:>7} {:>7} {:>6.1} {:>8} {:>8}\n",
+            summary.address,
+            summary.lines,
+            format_option(summary.uptime.map(|uptime| uptime / 3600f64)),
+            format_option(hashrate.mean),
+            format_option(hashrate.p50),
+            format_option(hashrate.p90),
+            format_option(hashrate.min),
+            hashrate.below * 100f64,
+            format!("{}/{}", tasks.success, tasks.success + tasks.failed),
+            tasks
+                .success_rate
+                .map(|rate| format!("{:.1}%", rate * 100f64))
+                .unwrap_or("-".to_string()),
+        ));
+        for (stage, phase) in summary.downloads.iter() {
+            text.push_str(&format!(
+                "    {} {} {}/{}\n",
+                stage,
+                phase.percent,
+                phase.progress.unwrap_or_default(),
+                phase.total.unwrap_or_default()
+            ));
+        }
+        for (rule, count) in summary.failures.iter() {
+            text.push_str(&format!("    {}: {}次\n", rule, count));
+        }
+    }
+    text
+}
@@ -0,0 +1,180 @@
+use std::{
+    collections::HashMap,
+    io::SeekFrom,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncSeekExt},
+    sync::Mutex,
+};
+use tracing::warn;
+
+lazy_static! {
+    pub static ref OFFSETS: Arc<Mutex<Offsets>> = Arc::new(Mutex::new(Offsets::load(
+        std::env::current_dir().unwrap().join(".log_offsets.json")
+    )));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Offset {
+    pub inode: u64,
+    pub offset: u64,
+}
+
+/// 每个日志文件已读取的位置，监控重启后从这里继续读取
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Offsets {
+    #[serde(skip)]
+    path: PathBuf,
+    offsets: HashMap<PathBuf, Offset>,
+}
+
+impl Offsets {
+    pub fn load(path: PathBuf) -> Offsets {
+        let mut offsets = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| serde_json::from_str::<Offsets>(&text).ok())
+            .unwrap_or_default();
+        offsets.path = path;
+        offsets
+    }
+
+    pub fn get(&self, file: &Path) -> Option<Offset> {
+        self.offsets.get(file).copied()
+    }
+
+    pub fn set(&mut self, file: &Path, offset: Offset) {
+        self.offsets.insert(file.to_path_buf(), offset);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&PathBuf, &Offset)> {
+        self.offsets.iter()
+    }
+
+    pub fn remove(&mut self, file: &Path) {
+        self.offsets.remove(file);
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let text = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        // 先写临时文件再改名，避免写到一半被中断
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, text).map_err(|e| e.to_string())?;
+        std::fs::rename(&tmp, &self.path).map_err(|e| e.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileState {
+    NORMAL,
+    TRUNCATED,
+    ROTATED,
+    REMOVED,
+}
+
+/// 持续跟踪日志文件，类似tail -F
+pub struct Follower {
+    path: PathBuf,
+    file: File,
+    inode: u64,
+    offset: u64,
+    partial: Vec<u8>,
+}
+
+impl Follower {
+    /// 打开日志文件，inode一致时从上次的位置继续读取
+    pub async fn open(path: &Path, resume: Option<Offset>) -> Result<Follower, String> {
+        let mut file = File::open(path).await.map_err(|e| e.to_string())?;
+        let metadata = file.metadata().await.map_err(|e| e.to_string())?;
+        let inode = metadata.ino();
+        let offset = match resume {
+            Some(resume) if resume.inode == inode && resume.offset <= metadata.len() => {
+                resume.offset
+            }
+            _ => 0,
+        };
+        file.seek(SeekFrom::Start(offset))
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(Follower {
+            path: path.to_path_buf(),
+            file,
+            inode,
+            offset,
+            partial: Vec::new(),
+        })
+    }
+
+    /// 已经读取完整行的位置
+    pub fn offset(&self) -> Offset {
+        Offset {
+            inode: self.inode,
+            offset: self.offset,
+        }
+    }
+
+    /// 读取新增的完整行，\r 也作为换行处理(tqdm进度条)
+    pub async fn read_lines(&mut self) -> Result<(FileState, Vec<String>), String> {
+        let mut lines = Vec::new();
+        // 先把旧文件中剩余的内容读完
+        self.read_available(&mut lines).await?;
+        let state = match tokio::fs::metadata(&self.path).await {
+            Err(_) => FileState::REMOVED,
+            Ok(metadata) if metadata.ino() != self.inode => {
+                self.file = File::open(&self.path).await.map_err(|e| e.to_string())?;
+                self.inode = metadata.ino();
+                self.reset();
+                FileState::ROTATED
+            }
+            Ok(metadata) if metadata.len() < self.offset + self.partial.len() as u64 => {
+                self.file
+                    .seek(SeekFrom::Start(0))
+                    .await
+                    .map_err(|e| e.to_string())?;
+                self.reset();
+                FileState::TRUNCATED
+            }
+            Ok(_) => FileState::NORMAL,
+        };
+        if state == FileState::ROTATED || state == FileState::TRUNCATED {
+            warn!("日志文件{:?}:{:?},从头读取", state, self.path);
+            self.read_available(&mut lines).await?;
+        }
+        Ok((state, lines))
+    }
+
+    fn reset(&mut self) {
+        self.offset = 0;
+        self.partial.clear();
+    }
+
+    async fn read_available(&mut self, lines: &mut Vec<String>) -> Result<(), String> {
+        let mut buf = Vec::new();
+        self.file
+            .read_to_end(&mut buf)
+            .await
+            .map_err(|e| e.to_string())?;
+        self.partial.extend(buf);
+        while let Some(position) = self
+            .partial
+            .iter()
+            .position(|byte| *byte == b'\n' || *byte == b'\r')
+        {
+            let line = self.partial.drain(..=position).collect::<Vec<u8>>();
+            self.offset += line.len() as u64;
+            let line = String::from_utf8_lossy(&line[..line.len() - 1])
+                .trim()
+                .to_string();
+            if !line.is_empty() {
+                lines.push(line);
+            }
+        }
+        Ok(())
+    }
+}
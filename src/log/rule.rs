@@ -0,0 +1,230 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Local};
+use indexmap::IndexMap;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use strum::Display;
+use tracing::error;
+
+use crate::config::CONFIG;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Display)]
+pub enum RuleKind {
+    PROGRESS,
+    HASHRATE,
+    ERROR,
+    RESTART,
+    COMPLETION,
+    IGNORE,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Display)]
+pub enum Threshold {
+    NORMAL,
+    BELOW,
+    ABOVE,
+}
+
+/// 日志解析规则，fields按顺序对应正则中的捕获组
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rule {
+    pub name: String,
+    pub regex: String,
+    #[serde(default)]
+    pub fields: Vec<String>,
+    pub kind: RuleKind,
+    /// 用于阈值判断的字段
+    pub value: Option<String>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogEvent {
+    pub address: String,
+    pub rule: String,
+    pub kind: RuleKind,
+    pub fields: IndexMap<String, String>,
+    pub value: Option<f64>,
+    pub threshold: Threshold,
+    pub time: DateTime<Local>,
+    pub line: String,
+}
+
+impl LogEvent {
+    pub fn need_restart(&self) -> bool {
+        self.kind == RuleKind::RESTART || self.threshold == Threshold::BELOW
+    }
+
+    /// 同一个key只保留最新的事件，下载进度按阶段区分
+    pub fn key(&self) -> String {
+        match (self.kind, self.fields.first()) {
+            (RuleKind::PROGRESS, Some((_, stage))) => format!("{}:{}", self.rule, stage),
+            _ => self.rule.clone(),
+        }
+    }
+}
+
+impl std::fmt::Display for LogEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fields = self
+            .fields
+            .iter()
+            .map(|(key, value)| format!("{}:{}", key, value))
+            .collect::<Vec<String>>()
+            .join(",");
+        write!(
+            f,
+            "{} [{}] {} {} {}",
+            self.address,
+            self.kind,
+            self.rule,
+            self.threshold,
+            if fields.is_empty() {
+                &self.line
+            } else {
+                &fields
+            }
+        )
+    }
+}
+
+struct CompiledRule {
+    rule: Rule,
+    regex: Regex,
+}
+
+pub struct Rules(Vec<CompiledRule>);
+
+impl Rules {
+    pub fn new(rules: Vec<Rule>) -> Result<Rules, String> {
+        let mut compiled = Vec::new();
+        for rule in rules.into_iter() {
+            let regex = Regex::new(&rule.regex)
+                .map_err(|e| format!("日志规则:{} 正则错误:{}", rule.name, e))?;
+            compiled.push(CompiledRule { rule, regex });
+        }
+        Ok(Rules(compiled))
+    }
+
+    /// 从配置文件加载规则，未配置或配置错误时使用内置规则
+    pub async fn load() -> Rules {
+        let config = Arc::clone(&CONFIG);
+        let rules = config.lock().await.log.rules.clone();
+        if rules.is_empty() {
+            return Rules::default();
+        }
+        Rules::new(rules).unwrap_or_else(|e| {
+            error!("{}", e);
+            Rules::default()
+        })
+    }
+
+    pub fn default_rules() -> Vec<Rule> {
+        let rule = |name: &str, regex: &str, fields: &[&str], kind: RuleKind| Rule {
+            name: name.to_string(),
+            regex: regex.to_string(),
+            fields: fields.iter().map(|field| field.to_string()).collect(),
+            kind,
+            value: None,
+            min: None,
+            max: None,
+        };
+        vec![
+            // 验算输出
+            rule(
+                "eval_loss",
+                r"\{'(loss|eval_loss).*}",
+                &[],
+                RuleKind::IGNORE,
+            ),
+            //第一次启动需要下载相关任务
+            rule(
+                "download",
+                r"(Generating|Downloading|Map)([\w ]*:)[\t ]+([\d\.]+\%)\|[\S ]+\|[ ]+([\d]+)\/([\d]+)[ +][\[\S]+[ ]+([\d\.]+)[\w<? \w\/\]]+",
+                &["operate", "extra", "percent", "progress", "total", "speed"],
+                RuleKind::PROGRESS,
+            ),
+            //获取显卡算力，验算时算力非常大，低于下限需要重启
+            Rule {
+                value: Some("it".to_string()),
+                min: Some(11f64),
+                max: Some(20f64),
+                ..rule(
+                    "hashrate",
+                    r"([\d]+%)\|[\S ]+\|[ ]+([\d]+)\/([\d]+)[ +][\[\S]+[ ]+([\d\.]+)[ \w\/\]<?]+",
+                    &["percent", "progress", "total", "it"],
+                    RuleKind::HASHRATE,
+                )
+            },
+            //拉取任务失败
+            rule(
+                "init_particle_failed",
+                r"Failed to init particle",
+                &[],
+                RuleKind::RESTART,
+            ),
+            rule(
+                "exception",
+                r"(Traceback|CUDA out of memory|RuntimeError: .*)",
+                &["error"],
+                RuleKind::ERROR,
+            ),
+            // 任务完成时输出
+            rule(
+                "task_completed",
+                r"completed the task.*",
+                &[],
+                RuleKind::COMPLETION,
+            ),
+        ]
+    }
+
+    /// 按顺序匹配规则，返回第一个命中的事件
+    pub fn parse(&self, address: &str, line: &str) -> Option<LogEvent> {
+        for CompiledRule { rule, regex } in self.0.iter() {
+            let Some(captures) = regex.captures(line) else {
+                continue;
+            };
+            let mut fields = IndexMap::new();
+            for (index, name) in rule.fields.iter().enumerate() {
+                if let Some(capture) = captures.get(index + 1) {
+                    fields.insert(name.clone(), capture.as_str().trim().to_string());
+                }
+            }
+            for name in regex.capture_names().flatten() {
+                if let Some(capture) = captures.name(name) {
+                    fields.insert(name.to_string(), capture.as_str().trim().to_string());
+                }
+            }
+            let value = rule
+                .value
+                .as_ref()
+                .and_then(|field| fields.get(field))
+                .and_then(|value| value.parse::<f64>().ok());
+            let threshold = match value {
+                Some(value) if rule.min.is_some_and(|min| value < min) => Threshold::BELOW,
+                Some(value) if rule.max.is_some_and(|max| value > max) => Threshold::ABOVE,
+                _ => Threshold::NORMAL,
+            };
+            return Some(LogEvent {
+                address: address.to_string(),
+                rule: rule.name.clone(),
+                kind: rule.kind,
+                fields,
+                value,
+                threshold,
+                time: Local::now(),
+                line: line.to_string(),
+            });
+        }
+        None
+    }
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules::new(Rules::default_rules()).unwrap()
+    }
+}
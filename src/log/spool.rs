@@ -0,0 +1,231 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::Write,
+    path::PathBuf,
+    sync::Arc,
+};
+
+use lazy_static::lazy_static;
+use reqwest::Client;
+use tokio::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tracing::{error, info, warn};
+
+use crate::auth::Signer;
+
+lazy_static! {
+    pub static ref SPOOL: Arc<Mutex<Spool>> = Arc::new(Mutex::new(Spool::new(
+        std::env::current_dir().unwrap().join("spool"),
+        crate::config::Log::default().spool_max_records,
+        crate::config::Log::default().spool_batch_size,
+    )));
+    /// 完成的任务逐条上报，服务端按任务去重，失败后重试不会重复记账
+    pub static ref TASK_SPOOL: Arc<Mutex<Spool>> = Arc::new(Mutex::new(Spool::new(
+        std::env::current_dir().unwrap().join("spool").join("tasks"),
+        crate::config::Log::default().spool_max_records,
+        1,
+    )));
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Retry {
+    failures: u32,
+    next: Instant,
+}
+
+/// 待上报日志的磁盘队列，每个地址一个文件，每行一条记录，按顺序上报
+#[derive(Debug)]
+pub struct Spool {
+    dir: PathBuf,
+    max_records: usize,
+    batch_size: usize,
+    retries: HashMap<String, Retry>,
+}
+
+impl Spool {
+    pub fn new(dir: PathBuf, max_records: usize, batch_size: usize) -> Spool {
+        Spool {
+            dir,
+            max_records: max_records.max(1),
+            batch_size: batch_size.max(1),
+            retries: HashMap::new(),
+        }
+    }
+
+    pub fn set_limit(&mut self, max_records: usize, batch_size: usize) {
+        self.max_records = max_records.max(1);
+        self.batch_size = batch_size.max(1);
+    }
+
+    fn path(&self, address: &str) -> PathBuf {
+        self.dir.join(format!("{}.jsonl", address))
+    }
+
+    fn read(&self, address: &str) -> Result<Vec<String>, String> {
+        let path = self.path(address);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Ok(text
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| line.to_string())
+            .collect())
+    }
+
+    fn write(&self, address: &str, records: &[String]) -> Result<(), String> {
+        let path = self.path(address);
+        if records.is_empty() {
+            let _ = std::fs::remove_file(path);
+            return Ok(());
+        }
+        let tmp = path.with_extension("tmp");
+        let mut text = records.join("\n");
+        text.push('\n');
+        std::fs::write(&tmp, text).map_err(|e| e.to_string())?;
+        std::fs::rename(&tmp, &path).map_err(|e| e.to_string())
+    }
+
+    /// 加入队列，超过上限时丢弃最早的记录，返回队列长度
+    pub fn push(&mut self, address: &str, record: &str) -> Result<usize, String> {
+        std::fs::create_dir_all(&self.dir).map_err(|e| e.to_string())?;
+        let record = record.replace(['\r', '\n'], " ");
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path(address))
+            .map_err(|e| e.to_string())?;
+        writeln!(file, "{}", record).map_err(|e| e.to_string())?;
+        drop(file);
+
+        let mut records = self.read(address)?;
+        if records.len() > self.max_records {
+            let dropped = records.len() - self.max_records;
+            warn!("上报队列已满,丢弃{}条最早的记录:{}", dropped, address);
+            records.drain(..dropped);
+            self.write(address, &records)?;
+        }
+        Ok(records.len())
+    }
+
+    /// 队列最前面的一批记录
+    pub fn peek(&self, address: &str) -> Result<Vec<String>, String> {
+        let mut records = self.read(address)?;
+        records.truncate(self.batch_size);
+        Ok(records)
+    }
+
+    /// 上报成功后移除已发送的记录，上报期间超过上限已被丢弃的部分不再移除
+    pub fn ack(&mut self, address: &str, sent: &[String]) -> Result<(), String> {
+        let mut records = self.read(address)?;
+        let skip = (0..=sent.len())
+            .find(|skip| records.starts_with(&sent[*skip..]))
+            .unwrap_or(sent.len());
+        records.drain(..sent.len() - skip);
+        self.write(address, &records)
+    }
+
+    pub fn depth(&self, address: &str) -> usize {
+        self.read(address).map(|records| records.len()).unwrap_or(0)
+    }
+
+    /// 每个地址的队列长度
+    pub fn depths(&self) -> BTreeMap<String, usize> {
+        let mut depths = BTreeMap::new();
+        let Ok(entries) = self.dir.read_dir() else {
+            return depths;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "jsonl") {
+                if let Some(address) = path.file_stem().and_then(|name| name.to_str()) {
+                    depths.insert(address.to_string(), self.depth(address));
+                }
+            }
+        }
+        depths
+    }
+
+    pub fn is_due(&self, address: &str, now: Instant) -> bool {
+        self.retries
+            .get(address)
+            .is_none_or(|retry| retry.next <= now)
+    }
+
+    /// 上报失败，按5s,10s,20s...最长5分钟退避，返回下次重试的等待时间
+    pub fn failed(&mut self, address: &str, now: Instant) -> Duration {
+        let retry = self.retries.entry(address.to_string()).or_insert(Retry {
+            failures: 0,
+            next: now,
+        });
+        retry.failures += 1;
+        let backoff = Duration::from_secs(5 * 2u64.pow((retry.failures - 1).min(6)))
+            .min(Duration::from_secs(300));
+        retry.next = now + backoff;
+        backoff
+    }
+
+    pub fn succeeded(&mut self, address: &str) {
+        self.retries.remove(address);
+    }
+
+    /// 按顺序上报到期的队列，某个地址失败后等待退避时间，不影响其他地址
+    /// 只在读取和确认时持有锁，上报期间不阻塞入队
+    pub async fn flush(spool: &Mutex<Spool>, client: &Client, api: &str, signer: &Signer) {
+        let addresses = spool
+            .lock()
+            .await
+            .depths()
+            .into_keys()
+            .collect::<Vec<String>>();
+        for address in addresses {
+            if !spool.lock().await.is_due(&address, Instant::now()) {
+                continue;
+            }
+            loop {
+                let records = spool.lock().await.peek(&address);
+                let records = match records {
+                    Ok(records) => records,
+                    Err(e) => {
+                        error!("读取上报队列失败:{},{}", address, e);
+                        break;
+                    }
+                };
+                if records.is_empty() {
+                    break;
+                }
+                let url = format!("{}/{}/{}", api, signer.server_id, address);
+                let result = signer
+                    .post(client, &url, records.join("\n").into_bytes())
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status());
+                let mut locked = spool.lock().await;
+                match result {
+                    Ok(_) => {
+                        info!("上报数据:{},{}条", address, records.len());
+                        locked.succeeded(&address);
+                        if let Err(e) = locked.ack(&address, &records) {
+                            error!("更新上报队列失败:{},{}", address, e);
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        let backoff = locked.failed(&address, Instant::now());
+                        error!(
+                            "上报数据失败:{},队列{}条,{:?}后重试,{:?}",
+                            address,
+                            locked.depth(&address),
+                            backoff,
+                            e
+                        );
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
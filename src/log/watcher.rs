@@ -0,0 +1,78 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use futures::StreamExt;
+use inotify::{EventMask, EventStream, Inotify, WatchDescriptor, WatchMask};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WatchEvent {
+    /// 文件新建、写入或移入
+    CHANGED(PathBuf),
+    /// 文件删除或移出
+    REMOVED(PathBuf),
+    /// 事件队列溢出，需要重新扫描目录
+    OVERFLOW,
+}
+
+/// 基于inotify监听目录下文件的变化
+pub struct Watcher {
+    stream: EventStream<[u8; 4096]>,
+    dirs: HashMap<WatchDescriptor, PathBuf>,
+}
+
+impl Watcher {
+    pub fn new(dirs: &[PathBuf]) -> Result<Watcher, String> {
+        let inotify = Inotify::init().map_err(|e| e.to_string())?;
+        let mut watches = HashMap::new();
+        for dir in dirs.iter() {
+            let wd = inotify
+                .watches()
+                .add(
+                    dir,
+                    WatchMask::CREATE
+                        | WatchMask::MODIFY
+                        | WatchMask::CLOSE_WRITE
+                        | WatchMask::DELETE
+                        | WatchMask::MOVED_FROM
+                        | WatchMask::MOVED_TO,
+                )
+                .map_err(|e| format!("监听目录失败:{:?},{}", dir, e))?;
+            watches.insert(wd, dir.clone());
+        }
+        let stream = inotify
+            .into_event_stream([0u8; 4096])
+            .map_err(|e| e.to_string())?;
+        Ok(Watcher {
+            stream,
+            dirs: watches,
+        })
+    }
+
+    /// 等待下一个文件事件，inotify出错时返回Err
+    pub async fn next(&mut self) -> Result<WatchEvent, String> {
+        loop {
+            let event = self
+                .stream
+                .next()
+                .await
+                .ok_or("inotify已关闭".to_string())?
+                .map_err(|e| e.to_string())?;
+            if event.mask.contains(EventMask::Q_OVERFLOW) {
+                return Ok(WatchEvent::OVERFLOW);
+            }
+            if event.mask.contains(EventMask::ISDIR) {
+                continue;
+            }
+            let (Some(dir), Some(name)) = (self.dirs.get(&event.wd), event.name) else {
+                continue;
+            };
+            let path = dir.join(name);
+            if event
+                .mask
+                .intersects(EventMask::DELETE | EventMask::MOVED_FROM)
+            {
+                return Ok(WatchEvent::REMOVED(path));
+            }
+            return Ok(WatchEvent::CHANGED(path));
+        }
+    }
+}
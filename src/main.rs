@@ -1,5 +1,8 @@
-use monitor::monitor::monitor;
+use actix_web::{App, HttpServer};
+use monitor::metrics::metrics;
+use monitor::monitor::{monitor, Monitor};
 use time::{macros::format_description, UtcOffset};
+use tracing::error;
 use tracing_subscriber::fmt::time::OffsetTime;
 #[tokio::main]
 async fn main() -> std::io::Result<()> {
@@ -8,6 +11,20 @@ async fn main() -> std::io::Result<()> {
         format_description!("[year]-[month]-[day] [hour]:[minute]:[second].[subsecond digits:3]"),
     );
     tracing_subscriber::fmt().with_timer(local_time).init();
+
+    // /metrics启动失败不影响监控
+    let config = Monitor::get_config().await;
+    let (ip, port) = (config.metrics_ip, config.metrics_port);
+    match HttpServer::new(|| App::new().service(metrics))
+        .workers(1)
+        .bind((ip, port))
+    {
+        Ok(server) => {
+            tokio::spawn(server.run());
+        }
+        Err(e) => error!("metrics端口{}:{}监听失败:{}", ip, port, e),
+    }
+
     monitor().await;
     Ok(())
 }
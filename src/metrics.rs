@@ -0,0 +1,261 @@
+use std::{collections::BTreeMap, future::Future, sync::Arc};
+
+use actix_web::{get, HttpResponse};
+use lazy_static::lazy_static;
+use strum::Display;
+use tokio::{sync::Mutex, time::Instant};
+use tracing::error;
+
+lazy_static! {
+    pub static ref METRICS: Arc<Mutex<Metrics>> = Arc::new(Mutex::new(Metrics::default()));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Display)]
+pub enum MetricKind {
+    COUNTER,
+    GAUGE,
+    SUMMARY,
+}
+
+/// 所有指标，名称、类型、说明
+const DESCRIPTIONS: &[(&str, MetricKind, &str)] = &[
+    // 服务端
+    (
+        "clore_wallets",
+        MetricKind::GAUGE,
+        "Sub wallets by deploy state",
+    ),
+    ("clore_active_orders", MetricKind::GAUGE, "Active orders"),
+    (
+        "clore_rental_spend_per_day",
+        MetricKind::GAUGE,
+        "Daily rental price of active orders",
+    ),
+    (
+        "clore_api_request_duration_seconds",
+        MetricKind::SUMMARY,
+        "Clore API request latency by endpoint",
+    ),
+    (
+        "clore_api_errors_total",
+        MetricKind::COUNTER,
+        "Clore API request errors by endpoint",
+    ),
+    (
+        "clore_cancellations_total",
+        MetricKind::COUNTER,
+        "Cancelled orders by reason",
+    ),
+    ("clore_blocklist_size", MetricKind::GAUGE, "Blocked servers"),
+    // 监控端
+    (
+        "monitor_hashrate",
+        MetricKind::GAUGE,
+        "Latest hashrate (it/s) by address and gpu",
+    ),
+    (
+        "monitor_miner_restarts_total",
+        MetricKind::COUNTER,
+        "Miner restarts by address",
+    ),
+    (
+        "monitor_gpu_temperature_celsius",
+        MetricKind::GAUGE,
+        "GPU temperature",
+    ),
+    (
+        "monitor_gpu_utilization_percent",
+        MetricKind::GAUGE,
+        "GPU utilization",
+    ),
+    (
+        "monitor_gpu_power_watts",
+        MetricKind::GAUGE,
+        "GPU power draw",
+    ),
+    (
+        "monitor_gpu_memory_used_bytes",
+        MetricKind::GAUGE,
+        "GPU memory used",
+    ),
+    (
+        "monitor_gpu_memory_total_bytes",
+        MetricKind::GAUGE,
+        "GPU memory total",
+    ),
+    (
+        "monitor_gpu_fan_percent",
+        MetricKind::GAUGE,
+        "GPU fan speed",
+    ),
+    (
+        "monitor_spool_depth",
+        MetricKind::GAUGE,
+        "Log records waiting for upload by address",
+    ),
+];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Sample {
+    value: f64,
+    count: u64,
+}
+
+#[derive(Debug)]
+struct Family {
+    kind: MetricKind,
+    help: &'static str,
+    samples: BTreeMap<String, Sample>,
+}
+
+/// Prometheus文本格式的指标，由租用循环和日志监控更新，/metrics读取
+#[derive(Debug)]
+pub struct Metrics(BTreeMap<&'static str, Family>);
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics(
+            DESCRIPTIONS
+                .iter()
+                .map(|(name, kind, help)| {
+                    (
+                        *name,
+                        Family {
+                            kind: *kind,
+                            help,
+                            samples: BTreeMap::new(),
+                        },
+                    )
+                })
+                .collect(),
+        )
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', "\\\"")
+        .replace('\n', r"\n")
+}
+
+fn format_labels(labels: &[(&str, &str)]) -> String {
+    if labels.is_empty() {
+        return String::new();
+    }
+    let labels = labels
+        .iter()
+        .map(|(name, value)| format!("{}=\"{}\"", name, escape(value)))
+        .collect::<Vec<String>>();
+    format!("{{{}}}", labels.join(","))
+}
+
+impl Metrics {
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)]) -> Option<&mut Sample> {
+        let Some(family) = self.0.get_mut(name) else {
+            error!("未注册的指标:{}", name);
+            return None;
+        };
+        Some(family.samples.entry(format_labels(labels)).or_default())
+    }
+
+    pub fn set(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        if let Some(sample) = self.sample(name, labels) {
+            sample.value = value;
+        }
+    }
+
+    pub fn inc(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        if let Some(sample) = self.sample(name, labels) {
+            sample.value += value;
+        }
+    }
+
+    /// summary记录一次观测值，输出_sum和_count
+    pub fn observe(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        if let Some(sample) = self.sample(name, labels) {
+            sample.value += value;
+            sample.count += 1;
+        }
+    }
+
+    /// 清空某个指标，用于按当前状态重新统计的gauge
+    pub fn clear(&mut self, name: &str) {
+        if let Some(family) = self.0.get_mut(name) {
+            family.samples.clear();
+        }
+    }
+
+    pub fn get(&self, name: &str, labels: &[(&str, &str)]) -> Option<f64> {
+        self.0
+            .get(name)
+            .and_then(|family| family.samples.get(&format_labels(labels)))
+            .map(|sample| sample.value)
+    }
+
+    /// 只输出有数据的指标
+    pub fn render(&self) -> String {
+        let mut text = String::new();
+        for (name, family) in self.0.iter() {
+            if family.samples.is_empty() {
+                continue;
+            }
+            text.push_str(&format!("# HELP {} {}\n", name, family.help));
+            text.push_str(&format!(
+                "# TYPE {} {}\n",
+                name,
+                family.kind.to_string().to_lowercase()
+            ));
+            for (labels, sample) in family.samples.iter() {
+                if family.kind == MetricKind::SUMMARY {
+                    text.push_str(&format!("{}_sum{} {}\n", name, labels, sample.value));
+                    text.push_str(&format!("{}_count{} {}\n", name, labels, sample.count));
+                } else {
+                    text.push_str(&format!("{}{} {}\n", name, labels, sample.value));
+                }
+            }
+        }
+        text
+    }
+}
+
+/// 记录Clore接口的耗时和错误
+pub async fn timed<T>(
+    endpoint: &str,
+    request: impl Future<Output = Result<T, String>>,
+) -> Result<T, String> {
+    let start = Instant::now();
+    let result = request.await;
+    let registry = Arc::clone(&METRICS);
+    let mut locked = registry.lock().await;
+    locked.observe(
+        "clore_api_request_duration_seconds",
+        &[("endpoint", endpoint)],
+        start.elapsed().as_secs_f64(),
+    );
+    if result.is_err() {
+        locked.inc("clore_api_errors_total", &[("endpoint", endpoint)], 1f64);
+    }
+    result
+}
+
+/// 请求成功但返回内容表示失败时，同样计入Clore接口错误
+pub async fn checked<T>(endpoint: &str, result: Result<T, String>) -> Result<T, String> {
+    if result.is_err() {
+        let registry = Arc::clone(&METRICS);
+        registry
+            .lock()
+            .await
+            .inc("clore_api_errors_total", &[("endpoint", endpoint)], 1f64);
+    }
+    result
+}
+
+#[get("/metrics")]
+pub async fn metrics() -> HttpResponse {
+    let registry = Arc::clone(&METRICS);
+    let text = registry.lock().await.render();
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(text)
+}
@@ -1,18 +1,29 @@
+use chrono::Local;
 use lazy_static::lazy_static;
 use pm::{Action, Process};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::process::{Command, Stdio};
+use std::process::Stdio;
 use std::sync::Arc;
 use tokio::sync::Mutex;
+use tokio::time::{Duration, Instant};
 use tracing::{error, info, warn};
 
+use self::hashrate::Hashrates;
 use self::nvidia::GeForces;
+use self::procfs::{MinerProcess, ProcFs};
+use crate::alert::{self, Alert, Severity};
+use crate::auth::Signer;
 use crate::config::CONFIG;
+use crate::log::rule::{LogEvent, RuleKind};
+use crate::log::spool::TASK_SPOOL;
 use crate::log::{self, LOG_CHANNEL};
+use crate::metrics::METRICS;
 
+pub mod hashrate;
 pub mod nvidia;
 pub mod pm;
+pub mod procfs;
 lazy_static! {
     pub static ref MONITOR: Arc<Mutex<Monitor>> = Arc::new(Mutex::new(Monitor::new()));
 }
@@ -23,15 +34,21 @@ pub struct Monitor {
     address: Vec<String>,
     nvidias: GeForces,
     upload_log: HashMap<String, Vec<String>>,
+    hashrates: Hashrates,
+    #[serde(skip)]
+    signer: Option<Signer>,
 }
 
 impl Monitor {
     fn new() -> Monitor {
+        let server_id = Monitor::get_server_id();
         Monitor {
-            server_id: Monitor::get_server_id(),
+            server_id,
             address: Monitor::get_address(),
             nvidias: GeForces::new(),
             upload_log: HashMap::<String, Vec<String>>::new(),
+            hashrates: Hashrates::default(),
+            signer: server_id.map(Signer::from_env),
         }
     }
 
@@ -77,55 +94,41 @@ impl Monitor {
         card_number
     }
 
-    async fn py_pros(&self) -> Result<Vec<String>, String> {
+    async fn py_pros(&self) -> Result<Vec<MinerProcess>, String> {
         info!("检测后台python挖矿程序");
-        //ps -aeo command |grep execute.py |grep -v grep
-        let mut address = Vec::new();
-        let py_proc = Command::new("ps")
-            .stdin(Stdio::null())
-            .stdout(Stdio::piped())
-            .args(["-aeo", "command"])
-            .spawn()
-            .map_err(|e| e.to_string())?
-            .stdout
-            .ok_or("ps命令运行失败！")?;
-        let grep_py = Command::new("grep")
-            .stdin(py_proc)
-            .stdout(Stdio::piped())
-            .args(["execute.py"])
-            .spawn()
-            .map_err(|e| e.to_string())?
-            .stdout
-            .ok_or("运行grep execute.py 失败！")?;
-        let grep = Command::new("grep")
-            .stdin(grep_py)
-            .stdout(Stdio::piped())
-            .args(["-v", "grep"])
-            .output()
-            .map_err(|e| e.to_string())?
-            .stdout;
-        let row = String::from_utf8(grep).map_err(|e| e.to_string())?;
-
-        info!("检测挖矿挖矿程序,程序输出:\n{}", row);
-        let reg: regex::Regex = regex::Regex::new(r"(nimble[\w]+)").map_err(|e| e.to_string())?;
-        for command in row.split("\n") {
-            if !command.is_empty() {
-                info!("{:?}", command);
-                let (_, [addr]) = reg.captures(command).ok_or("无匹配值！")?.extract::<1>();
-                address.push(addr.to_string());
-            }
+        let processes = ProcFs::default().miner_processes()?;
+        for process in processes.iter() {
+            info!(
+                "pid:{},地址:{},CUDA_VISIBLE_DEVICES:{},运行时长:{:.0}s,cpu占用:{:.1}%",
+                process.pid,
+                process.address,
+                process.cuda_visible_devices.clone().unwrap_or_default(),
+                process.uptime,
+                process.cpu_usage
+            );
         }
-        if address.len() == 0 {
-            let massge = format!("挖矿进程已退出！");
+        if processes.is_empty() {
+            let massge = "挖矿进程已退出！".to_string();
             error!("{}", massge);
+            let server_id = self.server_id.map(|id| id.to_string()).unwrap_or_default();
+            alert::notify(Alert::new(
+                format!("miner_exited:{}", server_id),
+                Severity::CRITICAL,
+                massge.clone(),
+                format!("serverid:{},地址:{}", server_id, self.address.join(",")),
+            ));
             Err(massge)
         } else {
             info!(
                 "后台运行地址数量:{}个,地址信息:{}",
-                address.len(),
-                address.join(",")
+                processes.len(),
+                processes
+                    .iter()
+                    .map(|process| process.address.clone())
+                    .collect::<Vec<String>>()
+                    .join(",")
             );
-            Ok(address)
+            Ok(processes)
         }
     }
 
@@ -191,6 +194,154 @@ impl Monitor {
         Ok(())
     }
 
+    // 重启单个地址的挖矿程序，并清空该地址的算力记录
+    async fn restart(&mut self, address: &str) -> Result<(), String> {
+        let index = self
+            .address
+            .iter()
+            .position(|addr| addr == address)
+            .ok_or(format!("未知的挖矿地址:{}", address))?;
+        if let Some(series) = self.hashrates.get_mut(address) {
+            series.clear();
+        }
+        let metrics = Arc::clone(&METRICS);
+        metrics.lock().await.inc(
+            "monitor_miner_restarts_total",
+            &[("address", address)],
+            1f64,
+        );
+        self.pm2(Action::RESTART, index, address.to_string()).await
+    }
+
+    // 处理日志事件，算力按时间序列判断是否需要重启
+    pub async fn handle(&mut self, msg: log::Massage) {
+        if self.server_id.is_some() {
+            log::Logs::upload(msg.clone()).await;
+        }
+        let Some(event) = &msg.event else {
+            return;
+        };
+        if self.hashrates.record(event) {
+            self.record_hashrate(event).await;
+            let stats = self
+                .hashrates
+                .get(&msg.address)
+                .map(|series| series.stats(Local::now()))
+                .unwrap_or_default();
+            if stats.need_restart() {
+                warn!("算力持续偏低,需要重启:{},{:?}", msg.address, stats);
+                if let Err(e) = self.restart(&msg.address).await {
+                    error!("重启失败:{}", e);
+                }
+            }
+            return;
+        }
+        if event.kind == RuleKind::COMPLETION && event.rule == "my_logs" {
+            self.report_task(event).await;
+            return;
+        }
+        if let log::MsgType::RESTART = msg.msg_type {
+            warn!("需要重启:{}", event);
+            if let Err(e) = self.restart(&msg.address).await {
+                error!("重启失败:{}", e);
+            }
+        }
+    }
+
+    // 每个地址对应一张显卡，按地址顺序编号
+    async fn record_hashrate(&self, event: &LogEvent) {
+        let Some(it) = event.value else {
+            return;
+        };
+        let gpu = self
+            .address
+            .iter()
+            .position(|addr| *addr == event.address)
+            .map(|index| index.to_string())
+            .unwrap_or_default();
+        let metrics = Arc::clone(&METRICS);
+        metrics.lock().await.set(
+            "monitor_hashrate",
+            &[("address", &event.address), ("gpu", &gpu)],
+            it,
+        );
+    }
+
+    // 显卡温度、功耗、显存等
+    async fn record_telemetry(&self) {
+        let telemetry = match GeForces::telemetry() {
+            Ok(telemetry) => telemetry,
+            Err(e) => {
+                error!("获取显卡状态失败:{}", e);
+                return;
+            }
+        };
+        let metrics = Arc::clone(&METRICS);
+        let mut locked = metrics.lock().await;
+        for gpu in telemetry.iter() {
+            let index = gpu.index.to_string();
+            let labels = [("gpu", index.as_str()), ("uuid", gpu.uuid.as_str())];
+            let mib = 1024f64 * 1024f64;
+            let values = [
+                ("monitor_gpu_temperature_celsius", gpu.temperature),
+                ("monitor_gpu_utilization_percent", gpu.utilization),
+                ("monitor_gpu_power_watts", gpu.power),
+                (
+                    "monitor_gpu_memory_used_bytes",
+                    gpu.memory_used.map(|v| v * mib),
+                ),
+                (
+                    "monitor_gpu_memory_total_bytes",
+                    gpu.memory_total.map(|v| v * mib),
+                ),
+                ("monitor_gpu_fan_percent", gpu.fan),
+            ];
+            for (name, value) in values {
+                if let Some(value) = value {
+                    locked.set(name, &labels, value);
+                }
+            }
+        }
+    }
+
+    // 上报nimble完成的任务，用于服务端收益统计，先加入队列，服务端不可用时重试
+    async fn report_task(&self, event: &LogEvent) {
+        if self.signer.is_none() {
+            return;
+        }
+        let spool = Arc::clone(&TASK_SPOOL);
+        let mut locked = spool.lock().await;
+        if let Err(e) = (*locked).push(&event.address, &event.line) {
+            error!("加入任务上报队列失败:{},{}", event.address, e);
+        }
+    }
+
+    // 上报每个地址的算力统计
+    pub async fn report_hashrate(&self) {
+        let Some(signer) = &self.signer else {
+            return;
+        };
+        let api = Monitor::get_config().await.get_api_report_hashrate();
+        let client = reqwest::ClientBuilder::new()
+            .timeout(std::time::Duration::from_secs(10))
+            .build()
+            .unwrap();
+        for (address, stats) in self.hashrates.stats(Local::now()) {
+            info!("算力统计:{},{:?}", address, stats);
+            let url = format!("{}/{}/{}", api, signer.server_id, address);
+            let body = serde_json::to_vec(&stats).unwrap_or_default();
+            let result = signer
+                .post(&client, &url, body)
+                .header("Content-Type", "application/json")
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            if let Err(e) = result {
+                error!("上报算力失败:{:?}", e);
+            }
+        }
+    }
+
     pub async fn dispatch(&mut self) {
         //监控是否掉线
 
@@ -198,6 +349,7 @@ impl Monitor {
         if let Err(e) = result {
             error!("调用程序失败:{}", e);
         }
+        self.record_telemetry().await;
     }
 
     pub async fn get_config() -> crate::config::Monitor {
@@ -208,30 +360,37 @@ impl Monitor {
 }
 
 pub async fn monitor() {
-    // tokio::spawn(log::Logs::monitor());
+    tokio::spawn(log::Logs::monitor());
+    tokio::spawn(log::Logs::upload_spool(
+        Monitor::get_server_id().map(Signer::from_env),
+    ));
 
+    let mut dispatch_time: Option<Instant> = None;
+    let mut report_time = Instant::now();
     loop {
         let monitor = Arc::clone(&MONITOR);
         let mut monitor_locked = monitor.lock().await;
         let log_channel = Arc::clone(&LOG_CHANNEL);
         let mut log_channel_locked = log_channel.lock().await;
+        let mut messages = Vec::new();
+        while let Ok(msg) = (*log_channel_locked).1.try_recv() {
+            messages.push(msg);
+        }
+        drop(log_channel_locked);
+        for msg in messages.into_iter() {
+            (*monitor_locked).handle(msg).await;
+        }
 
-        tokio::select! {
-            _ = (*monitor_locked).dispatch() => {
-
-            },
-            Some(msg) = (*log_channel_locked).1.recv() =>{
-                match msg.msg_type {
-                    log::MsgType::RESTART => {
-                        warn!("需要重启:{:?}",msg);
-                    },
-                    _=>{}
-                }
-            }
+        if dispatch_time.is_none_or(|instant| instant.elapsed() >= Duration::from_secs(10)) {
+            (*monitor_locked).dispatch().await;
+            dispatch_time = Some(Instant::now());
+        }
+        if report_time.elapsed() >= Duration::from_secs(60) {
+            (*monitor_locked).report_hashrate().await;
+            report_time = Instant::now();
         }
 
-        // (*monitor_locked).dispatch().await;
         drop(monitor_locked);
-        tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+        tokio::time::sleep(Duration::from_secs(1)).await;
     }
 }
@@ -0,0 +1,145 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    ops::{Deref, DerefMut},
+};
+
+use chrono::{DateTime, Duration, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::log::rule::{LogEvent, RuleKind, Threshold};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sample {
+    pub time: DateTime<Local>,
+    pub it: f64,
+    pub below: bool,
+}
+
+/// 单个地址的算力统计，窗口外或没有样本的平均值为None
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HashrateStats {
+    pub avg_1m: Option<f64>,
+    pub avg_15m: Option<f64>,
+    pub avg_1h: Option<f64>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// 一小时内低于阈值的时间占比(0-1)
+    pub under_threshold: f64,
+    /// 一小时内样本覆盖的时长(秒)
+    pub span: f64,
+    pub samples: usize,
+}
+
+impl HashrateStats {
+    /// 至少覆盖10分钟且八成时间低于阈值才需要重启，避免单个样本抖动
+    pub fn need_restart(&self) -> bool {
+        self.span >= 600f64 && self.under_threshold >= 0.8
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct HashrateSeries(VecDeque<Sample>);
+
+impl HashrateSeries {
+    pub fn push(&mut self, time: DateTime<Local>, it: f64, below: bool) {
+        self.0.push_back(Sample { time, it, below });
+        while let Some(first) = self.0.front() {
+            if time - first.time > Duration::hours(1) {
+                self.0.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    pub fn stats(&self, now: DateTime<Local>) -> HashrateStats {
+        let average = |window: Duration| {
+            let samples = self
+                .0
+                .iter()
+                .filter(|sample| now - sample.time <= window)
+                .map(|sample| sample.it)
+                .collect::<Vec<f64>>();
+            if samples.is_empty() {
+                None
+            } else {
+                Some(samples.iter().sum::<f64>() / samples.len() as f64)
+            }
+        };
+        let window = self
+            .0
+            .iter()
+            .filter(|sample| now - sample.time <= Duration::hours(1))
+            .collect::<Vec<&Sample>>();
+
+        // 每个样本的值持续到下一个样本，最后一个样本持续到now
+        let mut span = 0f64;
+        let mut under = 0f64;
+        for (index, sample) in window.iter().enumerate() {
+            let end = window.get(index + 1).map(|next| next.time).unwrap_or(now);
+            let seconds = (end - sample.time).num_milliseconds().max(0) as f64 / 1000f64;
+            span += seconds;
+            if sample.below {
+                under += seconds;
+            }
+        }
+
+        HashrateStats {
+            avg_1m: average(Duration::minutes(1)),
+            avg_15m: average(Duration::minutes(15)),
+            avg_1h: average(Duration::hours(1)),
+            min: window.iter().map(|sample| sample.it).reduce(f64::min),
+            max: window.iter().map(|sample| sample.it).reduce(f64::max),
+            under_threshold: if span > 0f64 { under / span } else { 0f64 },
+            span,
+            samples: window.len(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+/// 每个地址的算力时间序列
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Hashrates(HashMap<String, HashrateSeries>);
+
+impl Deref for Hashrates {
+    type Target = HashMap<String, HashrateSeries>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for Hashrates {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl Hashrates {
+    /// 记录算力事件，验算阶段(高于上限)的算力不计入
+    pub fn record(&mut self, event: &LogEvent) -> bool {
+        if event.kind != RuleKind::HASHRATE || event.threshold == Threshold::ABOVE {
+            return false;
+        }
+        let Some(it) = event.value else {
+            return false;
+        };
+        self.0.entry(event.address.clone()).or_default().push(
+            event.time,
+            it,
+            event.threshold == Threshold::BELOW,
+        );
+        true
+    }
+
+    pub fn stats(&self, now: DateTime<Local>) -> HashMap<String, HashrateStats> {
+        self.0
+            .iter()
+            .map(|(address, series)| (address.clone(), series.stats(now)))
+            .collect()
+    }
+}
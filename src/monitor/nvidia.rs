@@ -1,13 +1,12 @@
 use std::{
     ops::{Deref, DerefMut},
     process::Command,
-    str::FromStr,
 };
 
 use serde::{Deserialize, Serialize};
 use tracing::{error, info, warn};
 
-use crate::server::clore::model::CardType;
+use crate::server::clore::model::{gpu, CardType};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum GeForce {
@@ -19,6 +18,76 @@ pub enum GeForce {
     ERROR(String),
 }
 
+impl GeForce {
+    /// 解析nvidia-smi -L的一行，如GPU 0: NVIDIA GeForce RTX 4090 (UUID: GPU-13d44c72-...)
+    pub fn parse(line: &str) -> GeForce {
+        let card = line
+            .strip_prefix("GPU ")
+            .and_then(|card| card.split_once(": "))
+            .and_then(|(id, card)| {
+                let (name, uuid) = card.rsplit_once(" (UUID: ")?;
+                Some((id.parse::<u32>().ok()?, name, uuid.trim_end_matches(')')))
+            });
+        match card {
+            Some((id, name, uuid)) => {
+                info!("显卡:{},{}", id, name);
+                GeForce::CARD {
+                    id,
+                    uuid: uuid.to_string(),
+                    card_type: gpu::parse(name),
+                }
+            }
+            None => {
+                let e = format!("识别显卡错误:{:?}", line);
+                warn!(e);
+                GeForce::ERROR(e)
+            }
+        }
+    }
+}
+
+/// nvidia-smi --query-gpu读取的显卡状态，不支持的项为None
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GpuTelemetry {
+    pub index: u32,
+    pub uuid: String,
+    pub temperature: Option<f64>,
+    pub utilization: Option<f64>,
+    pub power: Option<f64>,
+    /// 显存(MiB)
+    pub memory_used: Option<f64>,
+    pub memory_total: Option<f64>,
+    pub fan: Option<f64>,
+}
+
+impl GpuTelemetry {
+    pub const QUERY: &'static str =
+        "index,uuid,temperature.gpu,utilization.gpu,power.draw,memory.used,memory.total,fan.speed";
+
+    /// 解析csv,noheader,nounits格式的输出
+    pub fn parse(text: &str) -> Vec<GpuTelemetry> {
+        text.lines()
+            .map(|line| line.split(',').map(|s| s.trim()).collect::<Vec<&str>>())
+            .filter_map(|fields| match &fields[..] {
+                [index, uuid, temperature, utilization, power, memory_used, memory_total, fan, ..] => {
+                    let value = |field: &str| field.parse::<f64>().ok();
+                    Some(GpuTelemetry {
+                        index: index.parse::<u32>().ok()?,
+                        uuid: uuid.to_string(),
+                        temperature: value(temperature),
+                        utilization: value(utilization),
+                        power: value(power),
+                        memory_used: value(memory_used),
+                        memory_total: value(memory_total),
+                        fan: value(fan),
+                    })
+                }
+                _ => None,
+            })
+            .collect()
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GeForces(Vec<GeForce>);
 
@@ -44,51 +113,7 @@ impl GeForces {
                 .split("\n")
                 .map(|nvidia| nvidia.trim())
                 .filter(|nvidia| !nvidia.is_empty())
-                .map(|nvidia| {
-                    let card = nvidia
-                        .split(" ")
-                        .map(|s| {
-                            s.trim()
-                                .trim_matches(|c| c == ':' || c == '(' || c == '\\')
-                                .to_string()
-                        })
-                        .collect::<Vec<String>>();
-                    info!("{:?}", card);
-                    match &card[..] {
-                        [_, id, card, _, _, card_type, _flag1, _flag2, _, uuid, ..] => {
-                            let card_type = format!("{}{}S", card, card_type);
-                            let geforce = GeForce::CARD {
-                                id: id.parse::<u32>().unwrap_or_default(),
-                                uuid: uuid.clone(),
-                                card_type: CardType::from_str(&card_type).unwrap(),
-                            };
-                            geforce
-                        }
-                        [_, id, card, _, _, card_type, _flag1, _, uuid, ..] => {
-                            let card_type = format!("{}{}TI", card, card_type);
-                            let geforce = GeForce::CARD {
-                                id: id.parse::<u32>().unwrap_or_default(),
-                                uuid: uuid.clone(),
-                                card_type: CardType::from_str(&card_type).unwrap(),
-                            };
-                            geforce
-                        }
-                        [_, id, card, _, _, card_type, _, uuid, ..] => {
-                            let card_type = format!("{}{}", card, card_type);
-                            let geforce = GeForce::CARD {
-                                id: id.parse::<u32>().unwrap_or_default(),
-                                uuid: uuid.clone(),
-                                card_type: CardType::from_str(&card_type).unwrap(),
-                            };
-                            geforce
-                        }
-                        _ => {
-                            let e = format!("识别显卡错误:{:?}", card);
-                            warn!(e);
-                            GeForce::ERROR(e)
-                        }
-                    }
-                })
+                .map(GeForce::parse)
                 .collect::<Vec<GeForce>>(),
             Err(e) => {
                 error!("获取显卡信息失败:{:?}", e);
@@ -112,6 +137,17 @@ impl GeForces {
         nvidias
     }
 
+    pub fn telemetry() -> Result<Vec<GpuTelemetry>, String> {
+        let output = Command::new("nvidia-smi")
+            .arg(format!("--query-gpu={}", GpuTelemetry::QUERY))
+            .arg("--format=csv,noheader,nounits")
+            .output()
+            .map_err(|e| e.to_string())?
+            .stdout;
+        let text = String::from_utf8(output).map_err(|e| e.to_string())?;
+        Ok(GpuTelemetry::parse(&text))
+    }
+
     fn command() -> Result<String, String> {
         //         let output = r"
         // GPU 0: NVIDIA GeForce RTX 4070 (UUID: GPU-5e4c623f-998d-912c-3743-3465506f63ad)
@@ -0,0 +1,149 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+// 内核时钟频率，/proc/<pid>/stat 中的时间单位，绝大多数发行版为100
+const CLK_TCK: f64 = 100f64;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MinerProcess {
+    pub pid: u32,
+    pub address: String,
+    pub cuda_visible_devices: Option<String>,
+    /// 进程运行时长(秒)
+    pub uptime: f64,
+    /// 进程生命周期内平均cpu占用(百分比)
+    pub cpu_usage: f64,
+}
+
+struct Stat {
+    utime: u64,
+    stime: u64,
+    starttime: u64,
+}
+
+/// 读取procfs检测挖矿进程，root可指向伪造的目录以便测试
+pub struct ProcFs {
+    root: PathBuf,
+}
+
+impl Default for ProcFs {
+    fn default() -> Self {
+        ProcFs::new("/proc")
+    }
+}
+
+impl ProcFs {
+    pub fn new(root: impl Into<PathBuf>) -> ProcFs {
+        ProcFs { root: root.into() }
+    }
+
+    /// 查找所有运行中的execute.py挖矿进程
+    pub fn miner_processes(&self) -> Result<Vec<MinerProcess>, String> {
+        let reg = regex::Regex::new(r"(nimble[\w]+)").map_err(|e| e.to_string())?;
+        let system_uptime = self.system_uptime()?;
+        let mut processes = Vec::new();
+        for entry in self.root.read_dir().map_err(|e| e.to_string())? {
+            let Ok(entry) = entry else {
+                continue;
+            };
+            let Some(pid) = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.parse::<u32>().ok())
+            else {
+                continue;
+            };
+            // 进程可能在遍历过程中退出，读取失败直接跳过
+            let Some(cmdline) = self.read_cmdline(pid) else {
+                continue;
+            };
+            if !cmdline.iter().any(|arg| arg.ends_with("execute.py")) {
+                continue;
+            }
+            let Some(address) = cmdline
+                .iter()
+                .find_map(|arg| reg.captures(arg))
+                .map(|captures| captures.extract::<1>().1[0].to_string())
+            else {
+                continue;
+            };
+            let stat = match self.read_stat(pid) {
+                Ok(stat) => stat,
+                Err(e) => {
+                    warn!("读取进程{}状态失败:{}", pid, e);
+                    continue;
+                }
+            };
+            let uptime = (system_uptime - stat.starttime as f64 / CLK_TCK).max(0f64);
+            let cpu_time = (stat.utime + stat.stime) as f64 / CLK_TCK;
+            let cpu_usage = if uptime > 0f64 {
+                cpu_time / uptime * 100f64
+            } else {
+                0f64
+            };
+            processes.push(MinerProcess {
+                pid,
+                address,
+                cuda_visible_devices: self.read_environ(pid).remove("CUDA_VISIBLE_DEVICES"),
+                uptime,
+                cpu_usage,
+            });
+        }
+        processes.sort_by_key(|process| process.pid);
+        Ok(processes)
+    }
+
+    fn read_cmdline(&self, pid: u32) -> Option<Vec<String>> {
+        let bytes = std::fs::read(self.root.join(pid.to_string()).join("cmdline")).ok()?;
+        let args = bytes
+            .split(|byte| *byte == 0)
+            .filter(|arg| !arg.is_empty())
+            .map(|arg| String::from_utf8_lossy(arg).to_string())
+            .collect::<Vec<String>>();
+        Some(args)
+    }
+
+    fn read_environ(&self, pid: u32) -> HashMap<String, String> {
+        let bytes =
+            std::fs::read(self.root.join(pid.to_string()).join("environ")).unwrap_or_default();
+        bytes
+            .split(|byte| *byte == 0)
+            .filter_map(|env| {
+                let env = String::from_utf8_lossy(env);
+                env.split_once('=')
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+            })
+            .collect()
+    }
+
+    fn read_stat(&self, pid: u32) -> Result<Stat, String> {
+        let row = std::fs::read_to_string(self.root.join(pid.to_string()).join("stat"))
+            .map_err(|e| e.to_string())?;
+        // 进程名可能包含空格和括号，从最后一个')'之后开始解析，第一个字段为state(第3列)
+        let (_, fields) = row.rsplit_once(')').ok_or("stat格式错误")?;
+        let fields = fields.split_whitespace().collect::<Vec<&str>>();
+        let field = |column: usize| -> Result<u64, String> {
+            fields
+                .get(column - 3)
+                .ok_or(format!("stat缺少第{}列", column))?
+                .parse::<u64>()
+                .map_err(|e| e.to_string())
+        };
+        Ok(Stat {
+            utime: field(14)?,
+            stime: field(15)?,
+            starttime: field(22)?,
+        })
+    }
+
+    fn system_uptime(&self) -> Result<f64, String> {
+        let row = std::fs::read_to_string(self.root.join("uptime")).map_err(|e| e.to_string())?;
+        row.split_whitespace()
+            .next()
+            .ok_or("uptime格式错误")?
+            .parse::<f64>()
+            .map_err(|e| e.to_string())
+    }
+}
@@ -1,66 +1,319 @@
-use std::fs::OpenOptions;
-use std::io::{Read, Write};
+use std::sync::Arc;
 
 use actix_web::web;
-use actix_web::{get, post};
+use actix_web::{get, post, HttpRequest, HttpResponse};
+use chrono::Local;
+use serde::Deserialize;
+use tokio::sync::Mutex;
 use tracing::error;
 use tracing::info;
+use tracing::warn;
 
+use crate::auth::Verifier;
+use crate::config::CONFIG;
+use crate::log::RunLog;
+use crate::monitor::hashrate::HashrateStats;
+use crate::server::accounting::{Accounting, ACCOUNTING};
+use crate::server::address::WALLETS_STATE;
+use crate::server::clore::Clore;
+use crate::server::report::Profitability;
+use crate::server::store::{validate_identifier, LogQuery, LOG_STORE};
+use crate::server::stream::StreamQuery;
+
+pub mod accounting;
 pub mod address;
+pub mod admin;
+pub mod api;
 pub mod clore;
+pub mod fleet;
+pub mod operator;
+pub mod provision;
+pub mod pull;
+pub mod report;
 pub mod ssh;
+pub mod store;
+pub mod stream;
+pub mod verify;
+
+lazy_static::lazy_static! {
+    pub static ref VERIFIER: Arc<Mutex<Option<Verifier>>> = Arc::new(Mutex::new(None));
+}
+
+async fn init_verifier(verifier: &mut Option<Verifier>) {
+    if verifier.is_none() {
+        let config = Arc::clone(&CONFIG);
+        let auth = config.lock().await.auth.clone();
+        *verifier = Some(Verifier::new(&auth));
+    }
+}
 
+/// 订单取消或释放后吊销该服务器已签发的token，之前租用者手里的token不能再上报
+pub async fn revoke_token(server_id: u32) {
+    let verifier = Arc::clone(&VERIFIER);
+    let mut locked = verifier.lock().await;
+    init_verifier(&mut locked).await;
+    locked
+        .as_mut()
+        .unwrap()
+        .revoke(server_id, Local::now().timestamp_millis());
+    warn!("已吊销服务器token:{}", server_id);
+}
+
+/// 校验监控程序的签名，路径中的server_id需要和签名一致，且有钱包部署在该服务器上
+pub async fn authorize(
+    req: &HttpRequest,
+    body: &[u8],
+    server_id: &str,
+) -> Result<u32, HttpResponse> {
+    let verifier = Arc::clone(&VERIFIER);
+    let mut locked = verifier.lock().await;
+    init_verifier(&mut locked).await;
+    let header = |name: &str| {
+        req.headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string())
+    };
+    let result = locked.as_mut().unwrap().verify(
+        header,
+        req.method().as_str(),
+        req.path(),
+        body,
+        Local::now().timestamp(),
+    );
+    drop(locked);
+    let signed = result.map_err(|e| {
+        error!("鉴权失败:{},{}", req.path(), e);
+        HttpResponse::Unauthorized().body(e)
+    })?;
+    if server_id != signed.to_string() {
+        error!("鉴权失败:server_id不一致:{},{}", server_id, signed);
+        return Err(HttpResponse::Unauthorized().body("server_id mismatch"));
+    }
+    let wallets = Arc::clone(&WALLETS_STATE);
+    if !wallets.lock().await.is_deployed_server(signed) {
+        error!("鉴权失败:未知服务器:{}", signed);
+        return Err(HttpResponse::Unauthorized().body("unknown server"));
+    }
+    Ok(signed)
+}
+
+/// 部署在该服务器上的钱包地址，最多card_number个，第i个地址对应第i张显卡
 #[get("/distribute_address/{card_number}/{server_id}")]
-pub async fn distribute_address() -> String {
-    unimplemented!()
+pub async fn distribute_address(
+    req: HttpRequest,
+    pathinfo: web::Path<(u32, String)>,
+) -> HttpResponse {
+    let (card_number, server_id) = pathinfo.into_inner();
+    let server_id = match authorize(&req, &[], &server_id).await {
+        Ok(server_id) => server_id,
+        Err(response) => return response,
+    };
+    let wallets = Arc::clone(&WALLETS_STATE);
+    let mut addresses = wallets.lock().await.server_addresses(server_id);
+    addresses.truncate(card_number as usize);
+    info!("分配地址:server_id:{},{:?}", server_id, addresses);
+    HttpResponse::Ok().json(addresses)
 }
 
-#[post("/printlnlog/{server_id}/{filename}")]
-pub async fn printlnlog(body: String, pathinfo: web::Path<(String, String)>) -> String {
-    let regex = regex::Regex::new(r"err|Err").unwrap();
-    if regex.is_match(&body) {
-        error!("{:?}", body);
-    } else {
-        info!("{:?}", body);
-    }
-    let (server_id, filename) = pathinfo.into_inner();
-    if let Ok(filepath) = std::env::current_dir() {
-        let filename = format!("{}_{}.log", filename, server_id);
-        let mut path = filepath.join("server_logs");
-        if !path.exists() {
-            let _ = std::fs::create_dir_all(path.clone());
+#[post("/printlnlog/{server_id}/{address}")]
+pub async fn printlnlog(
+    req: HttpRequest,
+    body: web::Bytes,
+    pathinfo: web::Path<(String, String)>,
+) -> HttpResponse {
+    let (server_id, address) = pathinfo.into_inner();
+    let server_id = match authorize(&req, &body, &server_id).await {
+        Ok(server_id) => server_id,
+        Err(response) => return response,
+    };
+    let body = String::from_utf8_lossy(&body);
+    let store = Arc::clone(&LOG_STORE);
+    let locked = store.lock().await;
+    match locked.ingest(server_id, &address, &body, Local::now()) {
+        Ok(records) => {
+            info!(
+                "保存日志:server_id:{},{},{}条",
+                server_id,
+                address,
+                records.len()
+            );
+            stream::publish(&records);
+            HttpResponse::Ok().body("ok")
+        }
+        Err(e) => {
+            error!("保存日志失败:server_id:{},{:?},{}", server_id, address, e);
+            HttpResponse::BadRequest().body(e)
+        }
+    }
+}
+
+#[get("/logs/stream")]
+pub async fn logs_stream(req: HttpRequest, query: web::Query<StreamQuery>) -> HttpResponse {
+    if let Err(response) = admin::authorize_admin(&req).await {
+        return response;
+    }
+    match stream::subscribe(&query).await {
+        Ok(events) => HttpResponse::Ok()
+            .content_type("text/event-stream")
+            .insert_header(("Cache-Control", "no-cache"))
+            .streaming(events),
+        Err(e) => HttpResponse::BadRequest().body(e),
+    }
+}
+
+#[get("/logs")]
+pub async fn logs(req: HttpRequest, query: web::Query<LogQuery>) -> HttpResponse {
+    if let Err(response) = admin::authorize_admin(&req).await {
+        return response;
+    }
+    let store = Arc::clone(&LOG_STORE);
+    let locked = store.lock().await;
+    match locked.query(&query) {
+        Ok(records) => HttpResponse::Ok().json(records),
+        Err(e) => HttpResponse::BadRequest().body(e),
+    }
+}
+
+#[post("/report_hashrate/{server_id}/{address}")]
+pub async fn report_hashrate(
+    req: HttpRequest,
+    body: web::Bytes,
+    pathinfo: web::Path<(String, String)>,
+) -> HttpResponse {
+    let (server_id, address) = pathinfo.into_inner();
+    let server_id = match authorize(&req, &body, &server_id).await {
+        Ok(server_id) => server_id,
+        Err(response) => return response,
+    };
+    let stats = match serde_json::from_slice::<HashrateStats>(&body) {
+        Ok(stats) => stats,
+        Err(e) => return HttpResponse::BadRequest().body(e.to_string()),
+    };
+    info!("算力上报:server_id:{},{},{:?}", server_id, address, stats);
+    // 算力上报同时作为心跳保存，实时日志中可以看到
+    if validate_identifier(&address).is_ok() {
+        let store = Arc::clone(&LOG_STORE);
+        let locked = store.lock().await;
+        let body = String::from_utf8_lossy(&body);
+        match locked.insert(
+            server_id,
+            &address,
+            "HEARTBEAT",
+            Local::now().timestamp(),
+            &body,
+        ) {
+            Ok(record) => stream::publish(&[record]),
+            Err(e) => error!("保存心跳失败:{}", e),
         }
-        path = path.join(filename.clone());
-
-        let isopened = if filename.find("my_logs").is_some() {
-            OpenOptions::new()
-                .create(true)
-                .read(true)
-                .write(true)
-                .open(path.clone())
-        } else {
-            OpenOptions::new()
-                .create(true)
-                .read(true)
-                .write(true)
-                .append(true)
-                .open(path.clone())
-        };
-
-        if let Ok(mut file) = isopened {
-            if filename.find("my_logs").is_some() {
-                let mut contents = String::new();
-                let mut reader = std::io::BufReader::new(file);
-                let _ = reader.read_to_string(&mut contents);
-                file = reader.into_inner();
-            }
-            let mut buf = std::io::BufWriter::new(file);
-            let row = format!("{}\n", body);
-            let _ = buf.write_all(row.as_bytes());
-        } else {
-            error!("打开文件:{} 写入失败!", path.as_path().display());
+    }
+    let wallets = Arc::clone(&WALLETS_STATE);
+    let mut locked = wallets.lock().await;
+    if locked.report_hashrate(&address, stats).await {
+        HttpResponse::Ok().body("ok")
+    } else {
+        error!("算力上报地址不存在:{}", address);
+        HttpResponse::Ok().body("unknown address")
+    }
+}
+
+#[post("/report_task/{server_id}/{address}")]
+pub async fn report_task(
+    req: HttpRequest,
+    body: web::Bytes,
+    pathinfo: web::Path<(String, String)>,
+) -> HttpResponse {
+    let (server_id, address) = pathinfo.into_inner();
+    let server_id = match authorize(&req, &body, &server_id).await {
+        Ok(server_id) => server_id,
+        Err(response) => return response,
+    };
+    let task = match serde_json::from_slice::<RunLog>(&body) {
+        Ok(task) => task,
+        Err(e) => return HttpResponse::BadRequest().body(e.to_string()),
+    };
+    if task.wallet_addr != address {
+        error!("任务地址不一致:{},{}", address, task.wallet_addr);
+        return HttpResponse::BadRequest().body("address mismatch");
+    }
+    let reward_per_task = Accounting::get_reward_per_task().await;
+    let accounting = Arc::clone(&ACCOUNTING);
+    let locked = accounting.lock().await;
+    match locked.record_task(server_id, &task, reward_per_task) {
+        Ok(_) => HttpResponse::Ok().body("ok"),
+        Err(e) => {
+            error!("记录任务失败:{}", e);
+            HttpResponse::InternalServerError().body(e)
         }
     }
+}
+
+#[get("/earnings/{group}")]
+pub async fn earnings(req: HttpRequest, group: web::Path<String>) -> HttpResponse {
+    if let Err(response) = admin::authorize_admin(&req).await {
+        return response;
+    }
+    let orders = if group.as_str() == "server" {
+        Clore::default()
+            .my_orders()
+            .await
+            .map(|my_orders| my_orders.to_vec())
+            .unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+    let accounting = Arc::clone(&ACCOUNTING);
+    let locked = accounting.lock().await;
+    let result = match group.as_str() {
+        "address" => locked.earnings_by_address().map(|e| serde_json::json!(e)),
+        "server" => locked
+            .earnings_by_server()
+            .map(|e| serde_json::json!(Accounting::earnings_with_price(e, &orders))),
+        "day" => locked.earnings_by_day().map(|e| serde_json::json!(e)),
+        "balance" => locked.balance_by_day().map(|e| serde_json::json!(e)),
+        _ => return HttpResponse::NotFound().body("unknown group"),
+    };
+    match result {
+        Ok(value) => HttpResponse::Ok().json(value),
+        Err(e) => HttpResponse::InternalServerError().body(e),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReportQuery {
+    pub format: Option<String>,
+}
 
-    "ok".to_string()
+#[get("/report/profitability")]
+pub async fn profitability(req: HttpRequest, query: web::Query<ReportQuery>) -> HttpResponse {
+    if let Err(response) = admin::authorize_admin(&req).await {
+        return response;
+    }
+    let orders = match Clore::default().my_orders().await {
+        Ok(my_orders) => my_orders.to_vec(),
+        Err(e) => return HttpResponse::BadGateway().body(e),
+    };
+    let nim_price = CONFIG.lock().await.accounting.nim_price;
+    let wallets = Arc::clone(&WALLETS_STATE);
+    let wallets_locked = wallets.lock().await;
+    let accounting = Arc::clone(&ACCOUNTING);
+    let accounting_locked = accounting.lock().await;
+    let result = Profitability::build(
+        &orders,
+        &wallets_locked,
+        &accounting_locked,
+        nim_price,
+        Local::now(),
+    );
+    match (result, query.format.as_deref()) {
+        (Ok(report), Some("csv")) => HttpResponse::Ok()
+            .content_type("text/csv; charset=utf-8")
+            .insert_header((
+                "Content-Disposition",
+                "attachment; filename=\"profitability.csv\"",
+            ))
+            .body(report.to_csv()),
+        (Ok(report), _) => HttpResponse::Ok().json(report),
+        (Err(e), _) => HttpResponse::InternalServerError().body(e),
+    }
 }
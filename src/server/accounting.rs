@@ -0,0 +1,261 @@
+use std::{path::Path, sync::Arc};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use sqlite::{ConnectionThreadSafe, State, Statement, Value};
+use tokio::sync::Mutex;
+use tracing::info;
+
+use crate::{config::CONFIG, log::RunLog, server::clore::model::my_orders::Order};
+
+lazy_static::lazy_static! {
+    pub static ref ACCOUNTING: Arc<Mutex<Accounting>> = {
+        Arc::new(Mutex::new(Accounting::default()))
+    };
+}
+
+/// 按地址、服务器或日期汇总的收益
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Earning {
+    pub key: String,
+    pub tasks: i64,
+    pub success: i64,
+    pub runtime: f64,
+    pub reward: f64,
+}
+
+/// 服务器收益以及租用价格(CLORE/天)，已退租的服务器没有价格
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServerEarning {
+    #[serde(flatten)]
+    pub earning: Earning,
+    pub price: Option<f64>,
+}
+
+/// 主钱包每天的余额变化
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BalanceChange {
+    pub address: String,
+    pub day: String,
+    pub open: f64,
+    pub close: f64,
+    pub change: f64,
+}
+
+pub struct Accounting {
+    connection: ConnectionThreadSafe,
+}
+
+impl Default for Accounting {
+    fn default() -> Self {
+        let path = std::env::current_dir().unwrap().join("accounting.db");
+        Accounting::open(path).unwrap()
+    }
+}
+
+impl Accounting {
+    pub fn open(path: impl AsRef<Path>) -> Result<Accounting, String> {
+        let connection = sqlite::Connection::open_thread_safe(path).map_err(|e| e.to_string())?;
+        connection
+            .execute(
+                "
+                CREATE TABLE IF NOT EXISTS tasks (
+                    address TEXT NOT NULL,
+                    server_id INTEGER NOT NULL,
+                    completed_time INTEGER NOT NULL,
+                    runtime REAL NOT NULL,
+                    status TEXT NOT NULL,
+                    reward REAL NOT NULL,
+                    PRIMARY KEY (address, completed_time)
+                );
+                CREATE TABLE IF NOT EXISTS balances (
+                    address TEXT NOT NULL,
+                    time INTEGER NOT NULL,
+                    balance REAL NOT NULL
+                );
+                CREATE INDEX IF NOT EXISTS balances_address_time ON balances (address, time);
+                ",
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(Accounting { connection })
+    }
+
+    pub async fn get_reward_per_task() -> f64 {
+        let mutex_conf = Arc::clone(&CONFIG);
+        let config = &mutex_conf.lock().await;
+        config.accounting.reward_per_task
+    }
+
+    /// 记录完成的任务，同一地址同一完成时间只记录一次，返回是否为新任务
+    pub fn record_task(
+        &self,
+        server_id: u32,
+        task: &RunLog,
+        reward_per_task: f64,
+    ) -> Result<bool, String> {
+        let completed_time = task
+            .get_completed_time()
+            .ok_or(format!("任务完成时间格式错误:{}", task.completed_time))?;
+        let status = task.status.to_string();
+        let reward = if status == "Success" {
+            reward_per_task
+        } else {
+            0f64
+        };
+        let mut statement = self
+            .connection
+            .prepare(
+                "INSERT OR IGNORE INTO tasks (address, server_id, completed_time, runtime, status, reward)
+                 VALUES (?, ?, ?, ?, ?, ?)",
+            )
+            .map_err(|e| e.to_string())?;
+        statement
+            .bind::<&[Value]>(
+                &[
+                    task.wallet_addr.as_str().into(),
+                    (server_id as i64).into(),
+                    completed_time.timestamp().into(),
+                    task.trainrun_time.into(),
+                    status.as_str().into(),
+                    reward.into(),
+                ][..],
+            )
+            .map_err(|e| e.to_string())?;
+        statement.next().map_err(|e| e.to_string())?;
+        let inserted = self.connection.change_count() > 0;
+        if inserted {
+            info!(
+                "记录任务:{},server_id:{},{},{}",
+                task.wallet_addr, server_id, task.completed_time, status
+            );
+        }
+        Ok(inserted)
+    }
+
+    /// 记录主钱包余额快照，余额没有变化时不重复记录
+    pub fn record_balance(
+        &self,
+        address: &str,
+        balance: f64,
+        time: DateTime<Local>,
+    ) -> Result<bool, String> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT balance FROM balances WHERE address = ? ORDER BY time DESC LIMIT 1")
+            .map_err(|e| e.to_string())?;
+        statement.bind((1, address)).map_err(|e| e.to_string())?;
+        if let State::Row = statement.next().map_err(|e| e.to_string())? {
+            let last = statement.read::<f64, _>(0).map_err(|e| e.to_string())?;
+            if last == balance {
+                return Ok(false);
+            }
+        }
+        let mut statement = self
+            .connection
+            .prepare("INSERT INTO balances (address, time, balance) VALUES (?, ?, ?)")
+            .map_err(|e| e.to_string())?;
+        statement
+            .bind::<&[Value]>(&[address.into(), time.timestamp().into(), balance.into()][..])
+            .map_err(|e| e.to_string())?;
+        statement.next().map_err(|e| e.to_string())?;
+        Ok(true)
+    }
+
+    pub fn earnings_by_address(&self) -> Result<Vec<Earning>, String> {
+        self.earnings("address")
+    }
+
+    pub fn earnings_by_server(&self) -> Result<Vec<Earning>, String> {
+        self.earnings("server_id")
+    }
+
+    pub fn earnings_with_price(earnings: Vec<Earning>, orders: &[Order]) -> Vec<ServerEarning> {
+        earnings
+            .into_iter()
+            .map(|earning| {
+                let price = orders
+                    .iter()
+                    .find(|order| order.server_id.to_string() == earning.key)
+                    .map(|order| order.price);
+                ServerEarning { earning, price }
+            })
+            .collect()
+    }
+
+    pub fn earnings_by_day(&self) -> Result<Vec<Earning>, String> {
+        self.earnings("date(completed_time, 'unixepoch', 'localtime')")
+    }
+
+    fn earnings(&self, group: &str) -> Result<Vec<Earning>, String> {
+        let sql = format!(
+            "SELECT CAST({group} AS TEXT), COUNT(*), SUM(status = 'Success'), SUM(runtime), SUM(reward)
+             FROM tasks GROUP BY {group} ORDER BY {group}"
+        );
+        let mut statement = self.connection.prepare(sql).map_err(|e| e.to_string())?;
+        let mut earnings = Vec::new();
+        while let State::Row = statement.next().map_err(|e| e.to_string())? {
+            earnings.push(Accounting::read_earning(&statement)?);
+        }
+        Ok(earnings)
+    }
+
+    /// 某台服务器从租用开始完成的任务
+    pub fn earning_for_server(&self, server_id: u32, since: i64) -> Result<Earning, String> {
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT '', COUNT(*), IFNULL(SUM(status = 'Success'), 0),
+                        IFNULL(SUM(runtime), 0.0), IFNULL(SUM(reward), 0.0)
+                 FROM tasks WHERE server_id = ? AND completed_time >= ?",
+            )
+            .map_err(|e| e.to_string())?;
+        statement
+            .bind::<&[Value]>(&[(server_id as i64).into(), since.into()][..])
+            .map_err(|e| e.to_string())?;
+        statement.next().map_err(|e| e.to_string())?;
+        Ok(Earning {
+            key: server_id.to_string(),
+            ..Accounting::read_earning(&statement)?
+        })
+    }
+
+    fn read_earning(statement: &Statement) -> Result<Earning, String> {
+        Ok(Earning {
+            key: statement.read::<String, _>(0).map_err(|e| e.to_string())?,
+            tasks: statement.read::<i64, _>(1).map_err(|e| e.to_string())?,
+            success: statement.read::<i64, _>(2).map_err(|e| e.to_string())?,
+            runtime: statement.read::<f64, _>(3).map_err(|e| e.to_string())?,
+            reward: statement.read::<f64, _>(4).map_err(|e| e.to_string())?,
+        })
+    }
+
+    /// 主钱包按天统计的余额变化，取当天第一条和最后一条快照
+    pub fn balance_by_day(&self) -> Result<Vec<BalanceChange>, String> {
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT address, date(time, 'unixepoch', 'localtime') AS day,
+                        (SELECT balance FROM balances b WHERE b.address = a.address
+                            AND date(b.time, 'unixepoch', 'localtime') = date(a.time, 'unixepoch', 'localtime')
+                            ORDER BY b.time ASC LIMIT 1),
+                        (SELECT balance FROM balances b WHERE b.address = a.address
+                            AND date(b.time, 'unixepoch', 'localtime') = date(a.time, 'unixepoch', 'localtime')
+                            ORDER BY b.time DESC LIMIT 1)
+                 FROM balances a GROUP BY address, day ORDER BY address, day",
+            )
+            .map_err(|e| e.to_string())?;
+        let mut changes = Vec::new();
+        while let State::Row = statement.next().map_err(|e| e.to_string())? {
+            let open = statement.read::<f64, _>(2).map_err(|e| e.to_string())?;
+            let close = statement.read::<f64, _>(3).map_err(|e| e.to_string())?;
+            changes.push(BalanceChange {
+                address: statement.read::<String, _>(0).map_err(|e| e.to_string())?,
+                day: statement.read::<String, _>(1).map_err(|e| e.to_string())?,
+                open,
+                close,
+                change: close - open,
+            });
+        }
+        Ok(changes)
+    }
+}
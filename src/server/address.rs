@@ -1,13 +1,33 @@
 use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fmt::Display, sync::Arc};
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 use strum::Display;
 use tokio::sync::Mutex;
 use tracing::{error, info, warn};
 
-use crate::{config::CONFIG, server::clore::Clore};
-
-use super::{clore::model::CardType, ssh};
+use crate::{
+    alert::{self, Alert, Severity},
+    config::CONFIG,
+    metrics::METRICS,
+    monitor::hashrate::HashrateStats,
+    server::{clore::Clore, revoke_token},
+};
+
+use super::{
+    accounting::ACCOUNTING,
+    clore::model::CardType,
+    provision::{self, Provisioning},
+    ssh,
+    store::validate_identifier,
+    verify,
+};
 
 lazy_static::lazy_static! {
     pub static ref WALLETS_STATE:Arc<Mutex<Address>> = {
@@ -15,6 +35,17 @@ lazy_static::lazy_static! {
     };
 }
 
+/// 暂停后租用循环只同步订单和钱包状态，不再租用新的服务器
+pub static POOL_PAUSED: AtomicBool = AtomicBool::new(false);
+
+pub fn set_pool_paused(paused: bool) {
+    POOL_PAUSED.store(paused, Ordering::SeqCst);
+}
+
+pub fn is_pool_paused() -> bool {
+    POOL_PAUSED.load(Ordering::SeqCst)
+}
+
 #[derive(Debug, Display, PartialEq, Clone, Serialize, Deserialize)]
 pub enum AddressType {
     MASTER,
@@ -30,6 +61,11 @@ pub struct Wallet {
     pub start_time: Option<DateTime<Local>>,
     pub report_last_time: Option<DateTime<Local>>,
     pub deploy: Deployed,
+    #[serde(default)]
+    pub hashrate: Option<HashrateStats>,
+    /// 当前订单的部署步骤
+    #[serde(default)]
+    pub provision: Option<Provisioning>,
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize, Display)]
@@ -58,6 +94,8 @@ impl Wallet {
             start_time: None,
             report_last_time: None,
             deploy: Deployed::NOTASSIGNED,
+            hashrate: None,
+            provision: None,
         }
     }
 
@@ -104,9 +142,20 @@ impl Display for Address {
                         } else {
                             format!("")
                         };
+                        let provision = wallet
+                            .provision
+                            .as_ref()
+                            .map(|provision| format!("\n,provision {}", provision))
+                            .unwrap_or_default();
                         format!(
-                            "addr:{},type:{},deploy:{},serverid:{},orderid:{}{}\n",
-                            address, wallet.addr_type, wallet.deploy, serverid, orderid, ssh
+                            "addr:{},type:{},deploy:{},serverid:{},orderid:{}{}{}\n",
+                            address,
+                            wallet.addr_type,
+                            wallet.deploy,
+                            serverid,
+                            orderid,
+                            ssh,
+                            provision
                         )
                     }
                     Deployed::DEPLOYED {
@@ -198,6 +247,11 @@ impl Address {
         }
     }
 
+    /// 主钱包的nimble余额
+    pub async fn get_balance(address: &str) -> Option<f64> {
+        Address::mstaddress(address).await
+    }
+
     #[allow(dead_code)]
     async fn subaddress(address: &str) -> AddressType {
         let url = "https://mainnet.nimble.technology/register_particle";
@@ -262,6 +316,11 @@ impl Address {
             }
             if let Some(balance) = Address::mstaddress(&mst_address).await {
                 self.get_mut(mst_address).unwrap().set_balance(balance);
+                let accounting = Arc::clone(&ACCOUNTING);
+                let locked = accounting.lock().await;
+                if let Err(e) = locked.record_balance(mst_address, balance, Local::now()) {
+                    error!("记录余额失败:{}", e);
+                }
             }
         }
 
@@ -282,6 +341,15 @@ impl Address {
 
         if let Ok(my_orders) = result {
             let orders = (*my_orders).clone();
+            let metrics = Arc::clone(&METRICS);
+            let mut locked = metrics.lock().await;
+            locked.set("clore_active_orders", &[], orders.len() as f64);
+            locked.set(
+                "clore_rental_spend_per_day",
+                &[],
+                orders.iter().map(|order| order.price).sum::<f64>(),
+            );
+            drop(locked);
             // 过滤掉已经知道的serverid和钱包地址，已经知道的订单对应的不去链接ssh获取挖矿进程
             // 如果有对应的serverid但是orderid 为零，则补充上相关信息
             // 提取当钱包所有已知的serverid
@@ -314,6 +382,11 @@ impl Address {
                 };
             }
 
+            // 新订单先检查硬件是否和标注一致
+            verify::schedule(self, &orders).await;
+            // ssh可用的新订单在后台上传脚本并启动挖矿
+            provision::schedule(self, &orders).await;
+
             let mut filter_orders = Vec::new();
             for order in orders.iter() {
                 if !serverids.contains(&order.server_id) {
@@ -346,6 +419,12 @@ impl Address {
 
             if my_orders.get_total_card_number() == self.get_total_sub_addr() {
                 warn!("当前已经满卡");
+                alert::notify(Alert::new(
+                    "full_cards",
+                    Severity::INFO,
+                    "当前已经满卡",
+                    format!("显卡数量:{}", my_orders.get_total_card_number()),
+                ));
                 wallets.clear();
             }
         }
@@ -396,6 +475,72 @@ impl Address {
         }
     }
 
+    /// 手动租用服务器，钱包需要都是未分配的子钱包，下单成功后标记为部署中
+    pub async fn rent_server(
+        &mut self,
+        clore: &Clore,
+        server_id: u32,
+        addresses: &[String],
+        force: bool,
+    ) -> Result<(), String> {
+        if addresses.is_empty() {
+            return Err("没有指定钱包地址".to_string());
+        }
+        for (index, address) in addresses.iter().enumerate() {
+            if addresses[..index].contains(address) {
+                return Err(format!("钱包地址重复:{}", address));
+            }
+            let wallet = (*self)
+                .get(address)
+                .ok_or(format!("不存在钱包地址:{}", address))?;
+            if wallet.addr_type != AddressType::SUB {
+                return Err(format!("不是子钱包地址:{}", address));
+            }
+            if !matches!(wallet.deploy, Deployed::NOTASSIGNED) {
+                return Err(format!("钱包已分配:{},{:?}", address, wallet.deploy));
+            }
+        }
+        let candidates = clore
+            .market()
+            .await?
+            .candidates(&Clore::import_block_server_ids());
+        let candidate = candidates
+            .iter()
+            .find(|candidate| candidate.card.server_id == server_id)
+            .ok_or(format!("市场上没有该服务器:{}", server_id))?;
+        if !force {
+            if !candidate.is_available() {
+                return Err(format!(
+                    "服务器不能租用:{:?},使用--force忽略",
+                    candidate.exclusions
+                ));
+            }
+            if candidate.card.card_number as usize != addresses.len() {
+                return Err(format!(
+                    "显卡数量{}和地址数量{}不一致,使用--force忽略",
+                    candidate.card.card_number,
+                    addresses.len()
+                ));
+            }
+        }
+        clore
+            .create_order_web_api(&candidate.card, addresses.to_vec())
+            .await?;
+        for address in addresses.iter() {
+            self.assgin_server(
+                address,
+                Deployed::DEPLOYING {
+                    orderid: 0,
+                    serverid: server_id,
+                    sshaddr: None,
+                    sshport: None,
+                },
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
     // 分配服务器
     pub async fn assgin_server(
         &mut self,
@@ -447,43 +592,240 @@ impl Address {
         true
     }
 
+    // 监控端上报算力统计，同时视为一次日志上报
+    pub async fn report_hashrate(&mut self, wallet_adress: &str, stats: HashrateStats) -> bool {
+        if !self.update_log_collect_time(wallet_adress).await {
+            return false;
+        }
+        let wallet = (*self).get_mut(wallet_adress).unwrap();
+        wallet.report_last_time = Some(Local::now());
+        wallet.hashrate = Some(stats);
+        true
+    }
+
+    /// 是否有钱包部署在该服务器上，用于拒绝未知服务器的上报
+    pub fn is_deployed_server(&self, server_id: u32) -> bool {
+        self.iter().any(|(_, wallet)| {
+            matches!(
+                &wallet.deploy,
+                Deployed::DEPLOYED { serverid, .. } | Deployed::DEPLOYING { serverid, .. }
+                    if *serverid == server_id
+            )
+        })
+    }
+
+    /// 部署在该服务器上的钱包地址，按字母排序对应显卡序号
+    pub fn server_addresses(&self, server_id: u32) -> Vec<String> {
+        let mut addresses = self
+            .values()
+            .filter(|wallet| {
+                matches!(
+                    &wallet.deploy,
+                    Deployed::DEPLOYED { serverid, .. } | Deployed::DEPLOYING { serverid, .. }
+                        if *serverid == server_id
+                )
+            })
+            .map(|wallet| wallet.address.clone())
+            .collect::<Vec<String>>();
+        addresses.sort();
+        addresses
+    }
+
+    /// 按服务器统计一小时平均算力，从高到低排序
+    pub fn rank_servers(&self) -> Vec<(u32, f64)> {
+        let mut servers = HashMap::<u32, Vec<f64>>::new();
+        for (_, wallet) in self.iter() {
+            let serverid = match &wallet.deploy {
+                Deployed::DEPLOYED { serverid, .. } | Deployed::DEPLOYING { serverid, .. } => {
+                    *serverid
+                }
+                Deployed::NOTASSIGNED => continue,
+            };
+            if let Some(avg_1h) = wallet.hashrate.as_ref().and_then(|stats| stats.avg_1h) {
+                servers.entry(serverid).or_default().push(avg_1h);
+            }
+        }
+        let mut ranks = servers
+            .into_iter()
+            .map(|(serverid, hashrates)| {
+                (
+                    serverid,
+                    hashrates.iter().sum::<f64>() / hashrates.len() as f64,
+                )
+            })
+            .collect::<Vec<(u32, f64)>>();
+        ranks.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranks
+    }
+
     // 超时未上报时间，则取消该机器订单号，重置所有钱包信息
     pub async fn filter_log_timeout(&mut self, clore: &Clore) {
-        let mut order_ids: Vec<u32> = Vec::new();
+        let mut order_ids: Vec<(u32, u32, &str)> = Vec::new();
         for (_, wallet) in (*self).iter_mut() {
             let nowtime = Local::now();
             match &wallet.deploy {
                 Deployed::NOTASSIGNED => {}
-                Deployed::DEPLOYING { orderid, .. } => {
+                Deployed::DEPLOYING {
+                    orderid, serverid, ..
+                } => {
                     // 创建时间超过25分钟，还未有上报时间则，进行取消订单
-                    if let Some(start_time) = wallet.start_time {
+                    // 部署步骤执行中时从步骤开始时间计算，步骤超时都小于25分钟
+                    let running_since = wallet
+                        .provision
+                        .as_ref()
+                        .and_then(|provision| provision.running_since());
+                    if let Some(start_time) = wallet.start_time.max(running_since) {
                         if nowtime.timestamp() - start_time.timestamp() > 25 * 60 {
                             if orderid != &0 {
-                                order_ids.push(orderid.clone());
+                                order_ids.push((*orderid, *serverid, "deploy_timeout"));
                             }
                         }
                     }
                 }
-                Deployed::DEPLOYED { orderid, .. } => {
+                Deployed::DEPLOYED {
+                    orderid, serverid, ..
+                } => {
                     // 上报时间若是超过了十分钟，则也取消，订单号
                     if let Some(report_last_time) = wallet.report_last_time {
                         if nowtime.timestamp() - report_last_time.timestamp() > 10 * 60 {
                             if orderid != &0 {
-                                order_ids.push(orderid.clone());
+                                order_ids.push((*orderid, *serverid, "report_timeout"));
                             }
                         }
                     }
                 }
             }
         }
-        for order_id in order_ids.iter() {
-            let result = clore.cancel_order(order_id.clone()).await;
+        for (order_id, server_id, reason) in order_ids.iter() {
+            let result = clore.cancel_order(*order_id).await;
             if let Err(e) = result {
                 error!("订单:{:?}取消失败,错误码：{:?}", order_id, e);
             } else {
                 warn!("已取消{:?}该订单", order_id);
+                revoke_token(*server_id).await;
+                count_cancellation(reason).await;
+                alert::notify(Alert::new(
+                    format!("cancel:{}", order_id),
+                    Severity::WARNING,
+                    "订单已取消",
+                    format!("订单:{},原因:{}", order_id, reason),
+                ));
+            }
+        }
+    }
+
+    /// 强制将钱包重置为未分配，返回原来的部署状态
+    pub async fn reset_wallet(&mut self, wallet_adress: &str) -> Result<Deployed, String> {
+        let wallet = (*self)
+            .get_mut(wallet_adress)
+            .ok_or("不存在钱包地址！".to_string())?;
+        if wallet.addr_type != AddressType::SUB {
+            return Err(format!("不是子钱包地址:{}", wallet_adress));
+        }
+        let deploy = std::mem::replace(&mut wallet.deploy, Deployed::NOTASSIGNED);
+        wallet.start_time = None;
+        wallet.report_last_time = None;
+        wallet.hashrate = None;
+        wallet.provision = None;
+        warn!("钱包已重置:{},原状态:{:?}", wallet_adress, deploy);
+        // 服务器上没有其他钱包时，吊销这次租用签发的token
+        if let Deployed::DEPLOYING { serverid, .. } | Deployed::DEPLOYED { serverid, .. } = deploy {
+            if !self.is_deployed_server(serverid) {
+                revoke_token(serverid).await;
             }
         }
+        Ok(deploy)
+    }
+
+    /// 取消订单，并重置绑定在该订单上的钱包，返回重置的钱包地址
+    pub async fn cancel_order(
+        &mut self,
+        clore: &Clore,
+        order_id: u32,
+    ) -> Result<Vec<String>, String> {
+        clore.cancel_order(order_id).await?;
+        warn!("已取消{:?}该订单", order_id);
+        count_cancellation("manual").await;
+        self.release_order(order_id).await
+    }
+
+    /// 重置绑定在该订单上的钱包，返回重置的钱包地址
+    pub async fn release_order(&mut self, order_id: u32) -> Result<Vec<String>, String> {
+        let addresses = (*self)
+            .values()
+            .filter(|wallet| match wallet.deploy {
+                Deployed::NOTASSIGNED => false,
+                Deployed::DEPLOYING { orderid, .. } | Deployed::DEPLOYED { orderid, .. } => {
+                    orderid == order_id
+                }
+            })
+            .map(|wallet| wallet.address.clone())
+            .collect::<Vec<String>>();
+        for address in addresses.iter() {
+            self.reset_wallet(address).await?;
+        }
+        Ok(addresses)
+    }
+
+    /// 运行时添加子钱包，同时加入配置，重启后以.conf.toml为准
+    pub async fn add_sub_address(&mut self, wallet_adress: &str) -> Result<(), String> {
+        let wallet_adress = validate_identifier(wallet_adress)?;
+        if (*self).contains_key(wallet_adress) {
+            return Err(format!("钱包地址已存在:{}", wallet_adress));
+        }
+        let mutex_conf = Arc::clone(&CONFIG);
+        let mut config = mutex_conf.lock().await;
+        if !config
+            .address
+            .sub_address
+            .iter()
+            .any(|item| item == wallet_adress)
+        {
+            config.address.sub_address.push(wallet_adress.to_string());
+        }
+        (*self).insert(
+            wallet_adress.to_string(),
+            Wallet::new(wallet_adress.to_string(), AddressType::SUB),
+        );
+        info!("添加子钱包:{}", wallet_adress);
+        Ok(())
+    }
+
+    /// 运行时移除子钱包，正在使用的钱包需要先取消订单或重置
+    pub async fn remove_sub_address(&mut self, wallet_adress: &str) -> Result<(), String> {
+        let wallet = (*self)
+            .get(wallet_adress)
+            .ok_or("不存在钱包地址！".to_string())?;
+        if wallet.addr_type != AddressType::SUB {
+            return Err(format!("不是子钱包地址:{}", wallet_adress));
+        }
+        if wallet.deploy != Deployed::NOTASSIGNED {
+            return Err(format!("钱包正在使用:{},{}", wallet_adress, wallet.deploy));
+        }
+        let mutex_conf = Arc::clone(&CONFIG);
+        let mut config = mutex_conf.lock().await;
+        config
+            .address
+            .sub_address
+            .retain(|item| item != wallet_adress);
+        (*self).remove(wallet_adress);
+        info!("移除子钱包:{}", wallet_adress);
+        Ok(())
+    }
+
+    /// 按部署状态统计子钱包数量
+    pub async fn record_metrics(&self) {
+        let metrics = Arc::clone(&METRICS);
+        let mut locked = metrics.lock().await;
+        for state in ["NOTASSIGNED", "DEPLOYING", "DEPLOYED"] {
+            let count = (*self)
+                .values()
+                .filter(|wallet| {
+                    wallet.addr_type == AddressType::SUB && wallet.deploy.to_string() == state
+                })
+                .count();
+            locked.set("clore_wallets", &[("state", state)], count as f64);
+        }
     }
 
     fn get_total_sub_addr(&self) -> u32 {
@@ -497,6 +839,12 @@ impl Address {
     }
 }
 
+pub async fn count_cancellation(reason: &str) {
+    let metrics = Arc::clone(&METRICS);
+    let mut locked = metrics.lock().await;
+    locked.inc("clore_cancellations_total", &[("reason", reason)], 1f64);
+}
+
 pub async fn pool() {
     loop {
         let wallets = Arc::clone(&WALLETS_STATE);
@@ -504,8 +852,24 @@ pub async fn pool() {
         let other = Address::default().load_address_file().await;
         locked.check(&other).await;
         let wallets = locked.get_unused_wallet().await;
+        locked.record_metrics().await;
+        let block_server_ids = Clore::import_block_server_ids();
+        let metrics = Arc::clone(&METRICS);
+        metrics.lock().await.set(
+            "clore_blocklist_size",
+            &[],
+            block_server_ids.iter().filter(|id| **id != 0).count() as f64,
+        );
         info!("当前绑定信息:{}", *locked);
-        locked.resent_server(wallets).await;
+        if is_pool_paused() {
+            warn!("租用已暂停,待分配地址:{}", wallets.len());
+        } else {
+            locked.resent_server(wallets).await;
+        }
+        for (serverid, hashrate) in locked.rank_servers() {
+            info!("serverid:{:7},平均算力:{:.2}it/s", serverid, hashrate);
+        }
+        drop(locked);
 
         // let address = wallets
         //     .iter()
@@ -524,7 +888,6 @@ pub async fn pool() {
         //         info!("server_ids:{:?}", server_ids);
         //     }
         // }
-        // drop(locked);
         tokio::time::sleep(std::time::Duration::from_secs(30)).await;
         // tokio::time::sleep(std::time::Duration::from_secs(60 * 5)).await;
     }
@@ -0,0 +1,256 @@
+use std::sync::Arc;
+
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
+
+use crate::{auth::verify_admin, config::CONFIG};
+
+use super::{
+    address::{is_pool_paused, set_pool_paused, WALLETS_STATE},
+    clore::Clore,
+    pull::pull_order,
+};
+
+/// 管理操作的结果
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AdminResult {
+    pub ok: bool,
+    pub message: String,
+}
+
+impl AdminResult {
+    fn response(result: Result<String, String>) -> HttpResponse {
+        match result {
+            Ok(message) => {
+                warn!("管理操作:{}", message);
+                HttpResponse::Ok().json(AdminResult { ok: true, message })
+            }
+            Err(message) => {
+                error!("管理操作失败:{}", message);
+                HttpResponse::BadRequest().json(AdminResult { ok: false, message })
+            }
+        }
+    }
+}
+
+/// 校验管理token
+pub async fn authorize_admin(req: &HttpRequest) -> Result<(), HttpResponse> {
+    let config = Arc::clone(&CONFIG);
+    let admin_token = config.lock().await.auth.admin_token.clone();
+    let authorization = req
+        .headers()
+        .get("Authorization")
+        .and_then(|value| value.to_str().ok());
+    verify_admin(admin_token.as_deref(), authorization).map_err(|e| {
+        error!("管理鉴权失败:{},{}", req.path(), e);
+        HttpResponse::Unauthorized().json(AdminResult {
+            ok: false,
+            message: e,
+        })
+    })
+}
+
+#[post("/admin/orders/{order_id}/cancel")]
+pub async fn cancel_order(req: HttpRequest, order_id: web::Path<u32>) -> HttpResponse {
+    if let Err(response) = authorize_admin(&req).await {
+        return response;
+    }
+    let order_id = order_id.into_inner();
+    let wallets = Arc::clone(&WALLETS_STATE);
+    let mut locked = wallets.lock().await;
+    let result = locked
+        .cancel_order(&Clore::default(), order_id)
+        .await
+        .map(|addresses| format!("已取消订单:{},重置钱包:{:?}", order_id, addresses));
+    AdminResult::response(result)
+}
+
+#[post("/admin/orders/{order_id}/pull_logs")]
+pub async fn pull_logs(req: HttpRequest, order_id: web::Path<u32>) -> HttpResponse {
+    if let Err(response) = authorize_admin(&req).await {
+        return response;
+    }
+    let order_id = order_id.into_inner();
+    let result = match Clore::default().my_orders().await {
+        Ok(orders) => match orders.iter().find(|order| order.order_id == order_id) {
+            Some(order) => pull_order(order)
+                .await
+                .map(|summary| format!("已拉取日志:{},{}", order_id, summary)),
+            None => Err(format!("订单不存在:{}", order_id)),
+        },
+        Err(e) => Err(e),
+    };
+    AdminResult::response(result)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RentQuery {
+    /// 逗号分隔的子钱包地址，每张卡一个
+    pub addresses: String,
+    /// 忽略不能租用的原因和显卡数量检查
+    pub force: Option<bool>,
+}
+
+/// 手动租用服务器，钱包需要都是未分配的，下单后标记为部署中
+#[post("/admin/rent/{server_id}")]
+pub async fn rent_server(
+    req: HttpRequest,
+    server_id: web::Path<u32>,
+    query: web::Query<RentQuery>,
+) -> HttpResponse {
+    if let Err(response) = authorize_admin(&req).await {
+        return response;
+    }
+    let server_id = server_id.into_inner();
+    let addresses = query
+        .addresses
+        .split(',')
+        .map(|address| address.trim().to_string())
+        .filter(|address| !address.is_empty())
+        .collect::<Vec<String>>();
+    let wallets = Arc::clone(&WALLETS_STATE);
+    let mut locked = wallets.lock().await;
+    let result = locked
+        .rent_server(
+            &Clore::default(),
+            server_id,
+            &addresses,
+            query.force.unwrap_or(false),
+        )
+        .await
+        .map(|_| format!("下单成功:{},钱包:{:?}", server_id, addresses));
+    AdminResult::response(result)
+}
+
+#[post("/admin/blocklist/{server_id}")]
+pub async fn block_server(req: HttpRequest, server_id: web::Path<u32>) -> HttpResponse {
+    if let Err(response) = authorize_admin(&req).await {
+        return response;
+    }
+    let server_id = server_id.into_inner();
+    let result = if Clore::append_block_server_id(server_id) {
+        Ok(format!("已拉黑服务器:{}", server_id))
+    } else {
+        Err(format!("拉黑服务器失败:{}", server_id))
+    };
+    AdminResult::response(result)
+}
+
+#[delete("/admin/blocklist/{server_id}")]
+pub async fn unblock_server(req: HttpRequest, server_id: web::Path<u32>) -> HttpResponse {
+    if let Err(response) = authorize_admin(&req).await {
+        return response;
+    }
+    let server_id = server_id.into_inner();
+    let result = Clore::remove_block_server_id(server_id).and_then(|removed| {
+        if removed {
+            Ok(format!("已移出黑名单:{}", server_id))
+        } else {
+            Err(format!("服务器不在黑名单中:{}", server_id))
+        }
+    });
+    AdminResult::response(result)
+}
+
+#[post("/admin/wallets/{address}/reset")]
+pub async fn reset_wallet(req: HttpRequest, address: web::Path<String>) -> HttpResponse {
+    if let Err(response) = authorize_admin(&req).await {
+        return response;
+    }
+    let wallets = Arc::clone(&WALLETS_STATE);
+    let mut locked = wallets.lock().await;
+    let result = locked
+        .reset_wallet(&address)
+        .await
+        .map(|deploy| format!("钱包已重置:{},原状态:{:?}", address, deploy));
+    AdminResult::response(result)
+}
+
+#[post("/admin/sub_address/{address}")]
+pub async fn add_sub_address(req: HttpRequest, address: web::Path<String>) -> HttpResponse {
+    if let Err(response) = authorize_admin(&req).await {
+        return response;
+    }
+    let wallets = Arc::clone(&WALLETS_STATE);
+    let mut locked = wallets.lock().await;
+    let result = locked
+        .add_sub_address(&address)
+        .await
+        .map(|_| format!("添加子钱包:{}", address));
+    AdminResult::response(result)
+}
+
+#[delete("/admin/sub_address/{address}")]
+pub async fn remove_sub_address(req: HttpRequest, address: web::Path<String>) -> HttpResponse {
+    if let Err(response) = authorize_admin(&req).await {
+        return response;
+    }
+    let wallets = Arc::clone(&WALLETS_STATE);
+    let mut locked = wallets.lock().await;
+    let result = locked
+        .remove_sub_address(&address)
+        .await
+        .map(|_| format!("移除子钱包:{}", address));
+    AdminResult::response(result)
+}
+
+#[get("/admin/pool")]
+pub async fn pool_status(req: HttpRequest) -> HttpResponse {
+    if let Err(response) = authorize_admin(&req).await {
+        return response;
+    }
+    HttpResponse::Ok().json(AdminResult {
+        ok: true,
+        message: if is_pool_paused() {
+            "paused"
+        } else {
+            "running"
+        }
+        .to_string(),
+    })
+}
+
+#[post("/admin/pool/pause")]
+pub async fn pause_pool(req: HttpRequest) -> HttpResponse {
+    if let Err(response) = authorize_admin(&req).await {
+        return response;
+    }
+    set_pool_paused(true);
+    AdminResult::response(Ok("租用已暂停".to_string()))
+}
+
+#[post("/admin/pool/resume")]
+pub async fn resume_pool(req: HttpRequest) -> HttpResponse {
+    if let Err(response) = authorize_admin(&req).await {
+        return response;
+    }
+    set_pool_paused(false);
+    AdminResult::response(Ok("租用已恢复".to_string()))
+}
+
+/// 命令行调用管理接口，返回服务端的说明
+pub async fn request(
+    server: &str,
+    token: &str,
+    method: reqwest::Method,
+    path: &str,
+) -> Result<String, String> {
+    let url = format!("{}{}", server.trim_end_matches('/'), path);
+    let response = reqwest::Client::new()
+        .request(method, url)
+        .bearer_auth(token)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    let status = response.status();
+    let text = response.text().await.map_err(|e| e.to_string())?;
+    let message = serde_json::from_str::<AdminResult>(&text)
+        .map(|result| result.message)
+        .unwrap_or(text);
+    if status.is_success() {
+        Ok(message)
+    } else {
+        Err(format!("{}:{}", status, message))
+    }
+}
@@ -0,0 +1,83 @@
+use std::sync::Arc;
+
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use serde::Deserialize;
+
+use crate::metrics::METRICS;
+
+use super::{
+    address::{Wallet, WALLETS_STATE},
+    admin::authorize_admin,
+    clore::Clore,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct MarketQuery {
+    /// 只返回可以租用的服务器
+    pub available: Option<bool>,
+}
+
+/// 钱包以及部署状态，主钱包在前
+#[get("/api/wallets")]
+pub async fn wallets(req: HttpRequest) -> HttpResponse {
+    if let Err(response) = authorize_admin(&req).await {
+        return response;
+    }
+    let wallets = Arc::clone(&WALLETS_STATE);
+    let locked = wallets.lock().await;
+    let mut wallets = locked.values().cloned().collect::<Vec<Wallet>>();
+    drop(locked);
+    wallets.sort_by(|a, b| {
+        (a.addr_type.to_string(), &a.address).cmp(&(b.addr_type.to_string(), &b.address))
+    });
+    HttpResponse::Ok().json(wallets)
+}
+
+#[get("/api/orders")]
+pub async fn orders(req: HttpRequest) -> HttpResponse {
+    if let Err(response) = authorize_admin(&req).await {
+        return response;
+    }
+    match Clore::default().my_orders().await {
+        Ok(my_orders) => HttpResponse::Ok().json(my_orders.to_vec()),
+        Err(e) => HttpResponse::BadGateway().body(e),
+    }
+}
+
+/// 市场上的服务器以及不能租用的原因
+#[get("/api/market")]
+pub async fn market(req: HttpRequest, query: web::Query<MarketQuery>) -> HttpResponse {
+    if let Err(response) = authorize_admin(&req).await {
+        return response;
+    }
+    let marketplace = match Clore::default().market().await {
+        Ok(marketplace) => marketplace,
+        Err(e) => return HttpResponse::BadGateway().body(e),
+    };
+    let mut candidates = marketplace.candidates(&Clore::import_block_server_ids());
+    if query.available.unwrap_or(false) {
+        candidates.retain(|candidate| candidate.is_available());
+    }
+    HttpResponse::Ok().json(candidates)
+}
+
+#[get("/api/blocklist")]
+pub async fn blocklist(req: HttpRequest) -> HttpResponse {
+    if let Err(response) = authorize_admin(&req).await {
+        return response;
+    }
+    HttpResponse::Ok().json(Clore::import_block_server_ids())
+}
+
+/// 服务端的指标需要管理token，监控端的/metrics不需要
+#[get("/metrics")]
+pub async fn metrics(req: HttpRequest) -> HttpResponse {
+    if let Err(response) = authorize_admin(&req).await {
+        return response;
+    }
+    let registry = Arc::clone(&METRICS);
+    let text = registry.lock().await.render();
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(text)
+}
@@ -1,3 +1,4 @@
+use chrono::Local;
 use futures::executor::block_on;
 use model::resent::ResentWeb;
 #[allow(dead_code)]
@@ -13,12 +14,17 @@ use std::{
     io::{Read, Write},
     sync::Arc,
 };
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 use self::model::{resent::Resent, Card};
 use crate::{
+    alert::{self, Alert, Severity},
     config::{self, CONFIG},
-    server::clore::model::{market::Marketplace, my_orders::MyOrders, wallet::Wallets},
+    metrics,
+    server::{
+        clore::model::{market::Marketplace, my_orders::MyOrders, wallet::Wallets},
+        ssh::Ssh,
+    },
 };
 
 pub mod model;
@@ -32,44 +38,52 @@ impl Default for Clore {
 
 impl Clore {
     pub async fn marketplace(&self) -> Result<Vec<Card>, String> {
+        let markets = self.market().await?.filter();
+        info!("可用卡:{:?}", &markets);
+        Ok(markets)
+    }
+
+    /// 市场上的全部服务器
+    pub async fn market(&self) -> Result<Marketplace, String> {
         info!("获取市场数据");
         let config::Clore { api_host, .. } = Clore::get_config().await;
         let url = format!("{}{}", api_host, "v1/marketplace");
-        let text = Clore::get_client()
-            .map_err(|e| e.to_string())?
-            .get(url)
-            .send()
-            .await
-            .map_err(|e| e.to_string())?
-            .text()
-            .await
-            .map_err(|e| e.to_string())?;
-        let mut file = File::open(env::current_dir().unwrap().join("market.json")).unwrap();
-        let _ = file.write_all(text.as_bytes());
+        let text = metrics::timed("v1/marketplace", async {
+            Clore::get_client()
+                .map_err(|e| e.to_string())?
+                .get(url)
+                .send()
+                .await
+                .map_err(|e| e.to_string())?
+                .text()
+                .await
+                .map_err(|e| e.to_string())
+        })
+        .await?;
+        // 保存一份市场数据，方便离线分析
+        if let Err(e) = std::fs::write(env::current_dir().unwrap().join("market.json"), &text) {
+            warn!("保存市场数据失败:{}", e);
+        }
         // info!("服务器响应:{:?}", &text);
 
-        let markets = serde_json::from_str::<Marketplace>(&text)
-            .map_err(|e| e.to_string())?
-            .filter()
-            .iter()
-            .map(|card| card.clone())
-            .collect::<Vec<_>>();
-        info!("可用卡:{:?}", &markets);
-        Ok(markets)
+        serde_json::from_str::<Marketplace>(&text).map_err(|e| e.to_string())
     }
 
     pub async fn wallet(&self) -> Result<f64, String> {
         let config::Clore { api_host, .. } = Clore::get_config().await;
         let url = format!("{}{}", api_host, "v1/wallets");
-        let text = Clore::get_client()
-            .map_err(|e| e.to_string())?
-            .get(url)
-            .send()
-            .await
-            .map_err(|e| e.to_string())?
-            .text()
-            .await
-            .map_err(|e| e.to_string())?;
+        let text = metrics::timed("v1/wallets", async {
+            Clore::get_client()
+                .map_err(|e| e.to_string())?
+                .get(url)
+                .send()
+                .await
+                .map_err(|e| e.to_string())?
+                .text()
+                .await
+                .map_err(|e| e.to_string())
+        })
+        .await?;
 
         let wallets = text.parse::<Wallets>()?;
         let balance = wallets.filter();
@@ -94,33 +108,48 @@ impl Clore {
         env.insert("SERVER_ID".to_string(), card.server_id.to_string());
         env.insert("CARD_NUMBER".to_string(), card.card_number.to_string());
         env.insert("ADDRESS".to_string(), address.join("-"));
-        info!("body:{}", serde_json::to_string(&resent).unwrap());
+        if let Some((issued, token)) = Clore::issue_token(card.server_id).await {
+            env.insert("MONITOR_TOKEN".to_string(), token);
+            env.insert("MONITOR_TOKEN_ISSUED".to_string(), issued.to_string());
+        }
+        Clore::inject_public_key(card.server_id, env, &mut resent.command).await;
+        // env中有MONITOR_TOKEN等密钥，不记录请求体
+        info!(
+            "创建订单:server_id:{},price:{}",
+            card.server_id, card.price_demand
+        );
         let mut headers: HashMap<_, _> = HashMap::new();
         headers.insert("Content-type", HeaderValue::from_str("application/json"));
-        let text = Clore::get_client()
-            .map_err(|e| e.to_string())?
-            .post(url)
-            .json(&resent)
-            .send()
-            .await
-            .map_err(|e| e.to_string())?
-            .text()
-            .await
-            .map_err(|e| e.to_string())?;
+        let text = metrics::timed("v1/create_order", async {
+            Clore::get_client()
+                .map_err(|e| e.to_string())?
+                .post(url)
+                .json(&resent)
+                .send()
+                .await
+                .map_err(|e| e.to_string())?
+                .text()
+                .await
+                .map_err(|e| e.to_string())
+        })
+        .await?;
         info!("{:?}", &text);
-        let result = serde_json::from_str::<Value>(&text).map_err(|e| e.to_string())?;
-        let code = result.get("code").map_or(-1i64, |val| {
-            val.as_number()
-                .unwrap_or(&Number::from(-1))
-                .as_i64()
-                .unwrap_or(-1)
-        });
-
-        if code == 0 {
-            Ok(())
-        } else {
-            Err(format!("创建服务器失败，错误码:{:?}", code))
-        }
+        let result = serde_json::from_str::<Value>(&text)
+            .map_err(|e| e.to_string())
+            .and_then(|result| {
+                let code = result.get("code").map_or(-1i64, |val| {
+                    val.as_number()
+                        .unwrap_or(&Number::from(-1))
+                        .as_i64()
+                        .unwrap_or(-1)
+                });
+                if code == 0 {
+                    Ok(())
+                } else {
+                    Err(format!("创建服务器失败，错误码:{:?}", code))
+                }
+            });
+        metrics::checked("v1/create_order", result).await
     }
 
     pub async fn create_order_web_api(
@@ -145,41 +174,73 @@ impl Clore {
         env.insert("SERVER_ID".to_string(), card.server_id.to_string());
         env.insert("CARD_NUMBER".to_string(), card.card_number.to_string());
         env.insert("ADDRESS".to_string(), address.join("-"));
-        info!("resent:{:?}", resent);
+        if let Some((issued, token)) = Clore::issue_token(card.server_id).await {
+            env.insert("MONITOR_TOKEN".to_string(), token);
+            env.insert("MONITOR_TOKEN_ISSUED".to_string(), issued.to_string());
+        }
+        Clore::inject_public_key(card.server_id, env, &mut resent.command).await;
+        // env中有MONITOR_TOKEN等密钥，不记录请求体
+        info!(
+            "创建订单:server_id:{},price:{}",
+            card.server_id, card.price_demand
+        );
 
         let client = Clore::get_client().map_err(|e| e.to_string())?;
         info!("command:{:?}", command.clone());
-        let text = client
-            .post(url)
-            .json(&resent)
-            .send()
-            .await
-            .map_err(|e| e.to_string())?
-            .text()
-            .await
-            .map_err(|e| e.to_string())?;
-        info!("{}", text);
-        if text.contains("completed") {
-            info!("下单成功！");
-            Ok(())
-        } else {
-            error!("下单失败:{:?}", text);
-            Err(text)
+        let result = metrics::timed("webapi/create_order", async {
+            client
+                .post(url)
+                .json(&resent)
+                .send()
+                .await
+                .map_err(|e| e.to_string())?
+                .text()
+                .await
+                .map_err(|e| e.to_string())
+        })
+        .await;
+        let result = match result {
+            Ok(text) if text.contains("completed") => {
+                info!("{}", text);
+                Ok(())
+            }
+            Ok(text) => metrics::checked("webapi/create_order", Err(text)).await,
+            Err(e) => Err(e),
+        };
+        // 请求失败和下单失败都告警
+        match result {
+            Ok(()) => {
+                info!("下单成功！");
+                Ok(())
+            }
+            Err(e) => {
+                error!("下单失败:{:?}", e);
+                alert::notify(Alert::new(
+                    format!("create_order:{}", card.server_id),
+                    Severity::WARNING,
+                    "下单失败",
+                    format!("serverid:{},{}", card.server_id, e),
+                ));
+                Err(e)
+            }
         }
     }
 
     pub async fn my_orders(&self) -> Result<MyOrders, String> {
         let config::Clore { api_host, .. } = Clore::get_config().await;
         let url = format!("{}{}", api_host, "v1/my_orders");
-        let text = Clore::get_client()
-            .map_err(|e| e.to_string())?
-            .get(url)
-            .send()
-            .await
-            .map_err(|e| e.to_string())?
-            .text()
-            .await
-            .map_err(|e| e.to_string())?;
+        let text = metrics::timed("v1/my_orders", async {
+            Clore::get_client()
+                .map_err(|e| e.to_string())?
+                .get(url)
+                .send()
+                .await
+                .map_err(|e| e.to_string())?
+                .text()
+                .await
+                .map_err(|e| e.to_string())
+        })
+        .await?;
         // info!("my_order_text:{}", text);
         let result: Result<MyOrders, String> =
             serde_json::from_str::<MyOrders>(&text).map_err(|e| e.to_string());
@@ -201,47 +262,58 @@ impl Clore {
             "Content-type",
             HeaderValue::from_str("application/json").unwrap(),
         );
-        let text = ClientBuilder::new()
-            .timeout(std::time::Duration::from_secs(30))
-            .default_headers(headers)
-            .build()
-            .map_err(|e| e.to_string())?
-            .post(url)
-            .body(body)
-            .send()
-            .await
-            .map_err(|e| e.to_string())?
-            .text()
-            .await
-            .map_err(|e| e.to_string())?;
+        let text = metrics::timed("v1/cancel_order", async {
+            ClientBuilder::new()
+                .timeout(std::time::Duration::from_secs(30))
+                .default_headers(headers)
+                .build()
+                .map_err(|e| e.to_string())?
+                .post(url)
+                .body(body)
+                .send()
+                .await
+                .map_err(|e| e.to_string())?
+                .text()
+                .await
+                .map_err(|e| e.to_string())
+        })
+        .await?;
         info!("cancel_order:{}", text);
-        let result = serde_json::from_str::<Value>(&text).map_err(|e| e.to_string())?;
-        let code = result.get("code").map_or(-1i64, |val| {
-            val.as_number()
-                .unwrap_or(&Number::from(-1))
-                .as_i64()
-                .unwrap_or(-1)
-        });
-
-        if code == 0 {
-            info!("取消订单成功");
-            Ok(())
-        } else {
-            let message = format!("取消失败:{:?}", code);
-            error!("{}", message);
-            Err(message)
-        }
+        let result = serde_json::from_str::<Value>(&text)
+            .map_err(|e| e.to_string())
+            .and_then(|result| {
+                let code = result.get("code").map_or(-1i64, |val| {
+                    val.as_number()
+                        .unwrap_or(&Number::from(-1))
+                        .as_i64()
+                        .unwrap_or(-1)
+                });
+                if code == 0 {
+                    info!("取消订单成功");
+                    Ok(())
+                } else {
+                    let message = format!("取消失败:{:?}", code);
+                    error!("{}", message);
+                    Err(message)
+                }
+            });
+        metrics::checked("v1/cancel_order", result).await
     }
 
     pub async fn cancel_order_web_api(&self, order_id: u32) -> Result<(), String> {
+        self.cancel_order_with_rating(order_id, 2).await
+    }
+
+    /// 通过网页接口取消订单并评分(1-5)
+    pub async fn cancel_order_with_rating(&self, order_id: u32, rating: u8) -> Result<(), String> {
         let config::Clore {
             web_api_host,
             web_token,
             ..
         } = Clore::get_config().await;
         let body = format!(
-            r#"{{"id":{},"rating":2,"token":"{}"}}"#,
-            order_id, web_token
+            r#"{{"id":{},"rating":{},"token":"{}"}}"#,
+            order_id, rating, web_token
         );
         let url = format!("{}webapi/marketplace/cancel_order", web_api_host);
         let mut headers = HeaderMap::new();
@@ -249,25 +321,28 @@ impl Clore {
             "Content-type",
             HeaderValue::from_str("application/json").unwrap(),
         );
-        let result = ClientBuilder::new()
-            .timeout(std::time::Duration::from_secs(30))
-            .default_headers(headers)
-            .build()
-            .map_err(|e| e.to_string())?
-            .post(url)
-            .body(body)
-            .send()
-            .await
-            .map_err(|e| e.to_string())?
-            .text()
-            .await
-            .map_err(|e| e.to_string())?;
+        let result = metrics::timed("webapi/marketplace/cancel_order", async {
+            ClientBuilder::new()
+                .timeout(std::time::Duration::from_secs(30))
+                .default_headers(headers)
+                .build()
+                .map_err(|e| e.to_string())?
+                .post(url)
+                .body(body)
+                .send()
+                .await
+                .map_err(|e| e.to_string())?
+                .text()
+                .await
+                .map_err(|e| e.to_string())
+        })
+        .await?;
         if r#"{"status":"ok"}"# == &result {
             info!("订单取消成功:{}", result);
             Ok(())
         } else {
             error!("取消失败:{}", result);
-            Err(result)
+            metrics::checked("webapi/marketplace/cancel_order", Err(result)).await
         }
     }
 
@@ -288,6 +363,51 @@ impl Clore {
         (*config).clore.clone()
     }
 
+    /// 配置了key_dir时生成新密钥，公钥通过SSH_PUBLIC_KEY传入，由启动命令写入authorized_keys
+    async fn inject_public_key(
+        server_id: u32,
+        env: &mut HashMap<String, String>,
+        command: &mut String,
+    ) {
+        let Some(key_dir) = Ssh::get_config().await.key_dir else {
+            return;
+        };
+        // ssh-keygen是阻塞调用，不占用异步线程
+        let result =
+            tokio::task::spawn_blocking(move || Ssh::generate_keypair(&key_dir, server_id))
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|result| result);
+        match result {
+            Ok(public_key) => {
+                env.insert("SSH_PUBLIC_KEY".to_string(), public_key);
+                *command = Ssh::authorize_key_command(command);
+            }
+            Err(e) => warn!("生成ssh密钥失败,只使用密码:{}", e),
+        }
+    }
+
+    /// 给服务器签发本次订单的上报token，返回签发时间和token，未配置auth.secret时不签发
+    pub async fn issue_token(server_id: u32) -> Option<(i64, String)> {
+        let mutex_conf = Arc::clone(&CONFIG);
+        let config = &mutex_conf.lock().await;
+        match config
+            .auth
+            .secret
+            .as_ref()
+            .filter(|secret| !secret.is_empty())
+        {
+            Some(secret) => {
+                let issued = Local::now().timestamp_millis();
+                Some((issued, crate::auth::issue_token(secret, server_id, issued)))
+            }
+            None => {
+                warn!("未配置auth.secret,服务器{}上报会被拒绝", server_id);
+                None
+            }
+        }
+    }
+
     pub fn import_block_server_ids() -> Vec<u32> {
         let mut black_server_ids = Vec::<u32>::new();
         let openfile = Clore::open_block_file();
@@ -301,13 +421,23 @@ impl Clore {
         black_server_ids = ids
             .split("\n")
             .into_iter()
-            .map(|item| item.trim().parse::<u32>().unwrap_or_default())
+            .map(|item| {
+                item.split_whitespace()
+                    .next()
+                    .and_then(|id| id.parse::<u32>().ok())
+                    .unwrap_or_default()
+            })
             .collect::<Vec<u32>>();
         info!("黑名单:{:?}", black_server_ids);
         black_server_ids
     }
 
     pub fn append_block_server_id(server_id: u32) -> bool {
+        Clore::append_block_server_id_with_reason(server_id, "")
+    }
+
+    /// 每行为服务器id，原因写在id后面
+    pub fn append_block_server_id_with_reason(server_id: u32, reason: &str) -> bool {
         let block_server_ids = Clore::import_block_server_ids();
 
         let openfile = Clore::open_block_file();
@@ -317,11 +447,34 @@ impl Clore {
         }
         if !block_server_ids.contains(&server_id) {
             let mut writer = std::io::BufWriter::new(openfile.unwrap());
-            let _ = writer.write_all(format!("\n{}", server_id).as_bytes());
+            let line = format!("{} {}", server_id, reason);
+            let _ = writer.write_all(format!("\n{}", line.trim()).as_bytes());
         }
         true
     }
 
+    /// 从拉黑文件中移除，返回是否在黑名单中
+    pub fn remove_block_server_id(server_id: u32) -> Result<bool, String> {
+        let block_server_ids = Clore::import_block_server_ids();
+        if !block_server_ids.contains(&server_id) {
+            return Ok(false);
+        }
+        let dir = std::env::current_dir().map_err(|e| e.to_string())?;
+        let file = dir.join("block_server_ids.txt");
+        let text = std::fs::read_to_string(&file).map_err(|e| e.to_string())?;
+        // 保留其它服务器的拉黑原因
+        let lines = text
+            .lines()
+            .filter(|line| {
+                let id = line.split_whitespace().next().unwrap_or_default();
+                !id.is_empty() && id != server_id.to_string()
+            })
+            .collect::<Vec<&str>>();
+        std::fs::write(file, lines.join("\n")).map_err(|e| e.to_string())?;
+        info!("已移出黑名单:{}", server_id);
+        Ok(true)
+    }
+
     fn open_block_file() -> Result<File, String> {
         let dir = std::env::current_dir().map_err(|e| e.to_string())?;
         let file = dir.join("block_server_ids.txt");
@@ -3,6 +3,7 @@ use std::str::FromStr;
 use serde::{Deserialize, Serialize};
 use strum::{Display, EnumString};
 
+/// 显卡型号，后缀TI、S(SUPER)、D、L(笔记本)、ADA、80G(显存)
 #[repr(u8)]
 #[derive(
     Debug, PartialEq, EnumString, Display, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize,
@@ -20,9 +21,88 @@ pub enum CardType {
     NVIDIA3080 = 10,
     NVIDIA1080TI = 11,
     NVIDIA1080 = 12,
+    NVIDIA4090D = 13,
+    NVIDIA4070TIS = 14,
+    NVIDIA4060TI = 15,
+    NVIDIA4060 = 16,
+    NVIDIA5090 = 17,
+    NVIDIA5080 = 18,
+    NVIDIA5070TI = 19,
+    NVIDIA5070 = 20,
+    NVIDIA3070TI = 21,
+    NVIDIA3070 = 22,
+    NVIDIA3060TI = 23,
+    NVIDIA3060 = 24,
+    NVIDIA2080TI = 25,
+    NVIDIA2080S = 26,
+    NVIDIA2080 = 27,
+    NVIDIA2070S = 28,
+    NVIDIA2070 = 29,
+    NVIDIA2060S = 30,
+    NVIDIA2060 = 31,
+    NVIDIA1660S = 32,
+    NVIDIA1660TI = 33,
+    NVIDIA1660 = 34,
+    NVIDIA1070TI = 35,
+    NVIDIA1070 = 36,
+    NVIDIA4090L = 37,
+    NVIDIA4080L = 38,
+    NVIDIA4070L = 39,
+    NVIDIA4060L = 40,
+    NVIDIA3080L = 41,
+    NVIDIA3070L = 42,
+    NVIDIA3060L = 43,
+    NVIDIAA2000 = 44,
+    NVIDIAA4000 = 45,
+    NVIDIAA4500 = 46,
+    NVIDIAA5000 = 47,
+    NVIDIAA6000 = 48,
+    NVIDIA4000ADA = 49,
+    NVIDIA6000ADA = 50,
+    NVIDIAA10 = 51,
+    NVIDIAA40 = 52,
+    NVIDIAA100 = 53,
+    NVIDIAA10080G = 54,
+    NVIDIAH100 = 55,
+    NVIDIAL4 = 56,
+    NVIDIAL40 = 57,
+    NVIDIAL40S = 58,
+    NVIDIAT4 = 59,
+    NVIDIAV100 = 60,
+    AMD7900XTX = 61,
+    AMD7900XT = 62,
+    AMD7900GRE = 63,
+    AMD7800XT = 64,
+    AMD7700XT = 65,
+    AMD6950XT = 66,
+    AMD6900XT = 67,
+    AMD6800XT = 68,
+    AMD6800 = 69,
+    AMD6700XT = 70,
+    AMDMI100 = 71,
+    AMDMI210 = 72,
+    AMDMI250 = 73,
+    AMDMI300X = 74,
     UNKNOWN(String),
 }
 
+/// 显卡架构
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, Serialize, Deserialize)]
+pub enum Generation {
+    PASCAL,
+    VOLTA,
+    TURING,
+    AMPERE,
+    ADA,
+    HOPPER,
+    BLACKWELL,
+    RDNA2,
+    RDNA3,
+    CDNA,
+    CDNA2,
+    CDNA3,
+}
+
 impl CardType {
     pub fn get_max_price(&self, card_number: f64) -> f64 {
         let price = match self {
@@ -38,11 +118,239 @@ impl CardType {
             CardType::NVIDIA3080 => 15f64,
             CardType::NVIDIA1080TI => 10f64,
             CardType::NVIDIA1080 => 10f64,
-            CardType::UNKNOWN(_) => 0f64,
+            // 其它型号不租用
+            _ => 0f64,
         };
 
         price * card_number
     }
+
+    /// 显存(GB)，同型号有多种显存时取较小的
+    pub fn vram(&self) -> Option<u32> {
+        let vram = match self {
+            CardType::NVIDIA5090 => 32,
+            CardType::NVIDIA4090 | CardType::NVIDIA4090D => 24,
+            CardType::NVIDIA5080
+            | CardType::NVIDIA5070TI
+            | CardType::NVIDIA4080S
+            | CardType::NVIDIA4080
+            | CardType::NVIDIA4070TIS
+            | CardType::NVIDIA4090L => 16,
+            CardType::NVIDIA5070
+            | CardType::NVIDIA4070TI
+            | CardType::NVIDIA4070S
+            | CardType::NVIDIA4070
+            | CardType::NVIDIA3080TI
+            | CardType::NVIDIA4080L => 12,
+            CardType::NVIDIA3090TI | CardType::NVIDIA3090 => 24,
+            CardType::NVIDIA3080 => 10,
+            CardType::NVIDIA2080TI | CardType::NVIDIA1080TI => 11,
+            CardType::NVIDIA4060TI
+            | CardType::NVIDIA4060
+            | CardType::NVIDIA3070TI
+            | CardType::NVIDIA3070
+            | CardType::NVIDIA3060TI
+            | CardType::NVIDIA3060
+            | CardType::NVIDIA2080S
+            | CardType::NVIDIA2080
+            | CardType::NVIDIA2070S
+            | CardType::NVIDIA2070
+            | CardType::NVIDIA2060S
+            | CardType::NVIDIA1080
+            | CardType::NVIDIA1070TI
+            | CardType::NVIDIA1070
+            | CardType::NVIDIA4070L
+            | CardType::NVIDIA4060L
+            | CardType::NVIDIA3080L
+            | CardType::NVIDIA3070L => 8,
+            CardType::NVIDIA2060
+            | CardType::NVIDIA1660S
+            | CardType::NVIDIA1660TI
+            | CardType::NVIDIA1660
+            | CardType::NVIDIA3060L
+            | CardType::NVIDIAA2000 => 6,
+            CardType::NVIDIAA4000 | CardType::NVIDIAT4 | CardType::NVIDIAV100 => 16,
+            CardType::NVIDIAA4500 | CardType::NVIDIA4000ADA => 20,
+            CardType::NVIDIAA5000 | CardType::NVIDIAA10 | CardType::NVIDIAL4 => 24,
+            CardType::NVIDIAA6000
+            | CardType::NVIDIA6000ADA
+            | CardType::NVIDIAA40
+            | CardType::NVIDIAL40
+            | CardType::NVIDIAL40S => 48,
+            CardType::NVIDIAA100 => 40,
+            CardType::NVIDIAA10080G | CardType::NVIDIAH100 => 80,
+            CardType::AMD7900XTX => 24,
+            CardType::AMD7900XT => 20,
+            CardType::AMD7900GRE
+            | CardType::AMD7800XT
+            | CardType::AMD6950XT
+            | CardType::AMD6900XT
+            | CardType::AMD6800XT
+            | CardType::AMD6800 => 16,
+            CardType::AMD7700XT | CardType::AMD6700XT => 12,
+            CardType::AMDMI100 => 32,
+            CardType::AMDMI210 => 64,
+            CardType::AMDMI250 => 128,
+            CardType::AMDMI300X => 192,
+            CardType::UNKNOWN(_) => return None,
+        };
+        Some(vram)
+    }
+
+    pub fn generation(&self) -> Option<Generation> {
+        let generation = match self {
+            CardType::NVIDIA5090
+            | CardType::NVIDIA5080
+            | CardType::NVIDIA5070TI
+            | CardType::NVIDIA5070 => Generation::BLACKWELL,
+            CardType::NVIDIA4090
+            | CardType::NVIDIA4090D
+            | CardType::NVIDIA4080S
+            | CardType::NVIDIA4080
+            | CardType::NVIDIA4070TIS
+            | CardType::NVIDIA4070TI
+            | CardType::NVIDIA4070S
+            | CardType::NVIDIA4070
+            | CardType::NVIDIA4060TI
+            | CardType::NVIDIA4060
+            | CardType::NVIDIA4090L
+            | CardType::NVIDIA4080L
+            | CardType::NVIDIA4070L
+            | CardType::NVIDIA4060L
+            | CardType::NVIDIA4000ADA
+            | CardType::NVIDIA6000ADA
+            | CardType::NVIDIAL4
+            | CardType::NVIDIAL40
+            | CardType::NVIDIAL40S => Generation::ADA,
+            CardType::NVIDIA3090TI
+            | CardType::NVIDIA3090
+            | CardType::NVIDIA3080TI
+            | CardType::NVIDIA3080
+            | CardType::NVIDIA3070TI
+            | CardType::NVIDIA3070
+            | CardType::NVIDIA3060TI
+            | CardType::NVIDIA3060
+            | CardType::NVIDIA3080L
+            | CardType::NVIDIA3070L
+            | CardType::NVIDIA3060L
+            | CardType::NVIDIAA2000
+            | CardType::NVIDIAA4000
+            | CardType::NVIDIAA4500
+            | CardType::NVIDIAA5000
+            | CardType::NVIDIAA6000
+            | CardType::NVIDIAA10
+            | CardType::NVIDIAA40
+            | CardType::NVIDIAA100
+            | CardType::NVIDIAA10080G => Generation::AMPERE,
+            CardType::NVIDIA2080TI
+            | CardType::NVIDIA2080S
+            | CardType::NVIDIA2080
+            | CardType::NVIDIA2070S
+            | CardType::NVIDIA2070
+            | CardType::NVIDIA2060S
+            | CardType::NVIDIA2060
+            | CardType::NVIDIA1660S
+            | CardType::NVIDIA1660TI
+            | CardType::NVIDIA1660
+            | CardType::NVIDIAT4 => Generation::TURING,
+            CardType::NVIDIA1080TI
+            | CardType::NVIDIA1080
+            | CardType::NVIDIA1070TI
+            | CardType::NVIDIA1070 => Generation::PASCAL,
+            CardType::NVIDIAH100 => Generation::HOPPER,
+            CardType::NVIDIAV100 => Generation::VOLTA,
+            CardType::AMD7900XTX
+            | CardType::AMD7900XT
+            | CardType::AMD7900GRE
+            | CardType::AMD7800XT
+            | CardType::AMD7700XT => Generation::RDNA3,
+            CardType::AMD6950XT
+            | CardType::AMD6900XT
+            | CardType::AMD6800XT
+            | CardType::AMD6800
+            | CardType::AMD6700XT => Generation::RDNA2,
+            CardType::AMDMI100 => Generation::CDNA,
+            CardType::AMDMI210 | CardType::AMDMI250 => Generation::CDNA2,
+            CardType::AMDMI300X => Generation::CDNA3,
+            CardType::UNKNOWN(_) => return None,
+        };
+        Some(generation)
+    }
+}
+
+/// 显卡名称解析，市场标注和本机nvidia-smi共用
+pub mod gpu {
+    use std::str::FromStr;
+
+    use lazy_static::lazy_static;
+    use regex::Regex;
+
+    use super::CardType;
+
+    lazy_static! {
+        /// 型号，后缀可能和型号连在一起，如4070TI、4090D、L40S、MI300X
+        static ref MODEL: Regex = Regex::new(
+            r"^(?P<model>\d{4}|[AHLTV]\d{1,4}|MI\d{3}X?)(?P<suffix>TI|SUPER|S|D|XTX|XT|GRE)?$"
+        )
+        .unwrap();
+    }
+
+    fn suffix(flag: &str) -> &'static str {
+        match flag {
+            "TI" => "TI",
+            "SUPER" | "S" => "S",
+            "D" => "D",
+            "XTX" => "XTX",
+            "XT" => "XT",
+            "GRE" => "GRE",
+            "ADA" => "ADA",
+            "LAPTOP" | "MOBILE" => "L",
+            _ => "",
+        }
+    }
+
+    /// 解析显卡名称，可以带数量，如2x NVIDIA GeForce RTX 4070 Ti SUPER、NVIDIA A100-SXM4-80GB、
+    /// AMD Radeon RX 7900 XTX，不认识的型号为UNKNOWN(name)
+    pub fn parse(name: &str) -> CardType {
+        let upper = name.to_uppercase().replace(['-', '_', '(', ')'], " ");
+        let mut tokens = upper.split_whitespace().collect::<Vec<&str>>();
+        let is_number = tokens
+            .first()
+            .and_then(|token| token.strip_suffix('X'))
+            .is_some_and(|number| number.parse::<u32>().is_ok());
+        if is_number {
+            tokens.remove(0);
+        }
+        let vendor = if tokens
+            .iter()
+            .any(|token| ["AMD", "RADEON", "INSTINCT"].contains(token))
+        {
+            "AMD"
+        } else {
+            "NVIDIA"
+        };
+        let Some((index, captures)) = tokens
+            .iter()
+            .enumerate()
+            .find_map(|(index, token)| Some((index, MODEL.captures(token)?)))
+        else {
+            return CardType::UNKNOWN(name.to_string());
+        };
+        let model = &captures["model"];
+        let mut key = format!("{}{}", vendor, model);
+        let attached = captures.name("suffix").map(|flag| flag.as_str());
+        for flag in attached
+            .into_iter()
+            .chain(tokens[index + 1..].iter().copied())
+        {
+            key.push_str(suffix(flag));
+            // A100有40GB和80GB两种
+            if model == "A100" && flag == "80GB" {
+                key.push_str("80G");
+            }
+        }
+        CardType::from_str(&key).unwrap_or_else(|_| CardType::UNKNOWN(name.to_string()))
+    }
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
@@ -74,7 +382,7 @@ impl ToString for Currency {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Card {
     pub server_id: u32,
     pub avg_score: f64,
@@ -92,16 +400,16 @@ pub mod market {
     use std::{
         collections::HashMap,
         ops::{Deref, DerefMut},
-        str::FromStr,
     };
 
     use regex::Regex;
     use serde::{Deserialize, Serialize};
+    use strum::Display;
     use tracing::warn;
 
     use crate::server::clore::Clore;
 
-    use super::{Card, CardType};
+    use super::{gpu, Card, CardType};
 
     #[derive(Serialize, Deserialize, Debug, Clone)]
     pub struct Net {
@@ -137,32 +445,32 @@ pub mod market {
             card_number
         }
 
+        /// 去掉数量的显卡名称，如2x NVIDIA GeForce RTX 4090中的NVIDIA GeForce RTX 4090
+        pub fn get_gpu_name(&self) -> &str {
+            self.gpu
+                .split_once(" ")
+                .map(|(_, name)| name)
+                .unwrap_or_default()
+        }
+
         pub fn get_card_type(&self) -> CardType {
-            let card_info = self
-                .gpu
+            gpu::parse(&self.gpu)
+        }
+
+        /// cpus格式为核心数/线程数
+        pub fn get_cpu_threads(&self) -> Option<u32> {
+            self.cpus
+                .rsplit('/')
+                .next()
+                .and_then(|threads| threads.trim().parse::<u32>().ok())
+        }
+
+        /// disk格式如nvme 1024GB
+        pub fn get_disk_size(&self) -> Option<f64> {
+            self.disk
                 .split(" ")
-                .map(|s| s.to_string())
-                .collect::<Vec<String>>();
-            let factory = card_info
-                .get(1)
-                .map(|item| item.to_owned())
-                .unwrap_or_default();
-            let card_type = card_info
-                .get(4)
-                .map(|item| item.to_owned())
-                .unwrap_or_default();
-            let mut flag = card_info
-                .get(5)
-                .map(|itme| itme.to_uppercase().to_owned())
-                .unwrap_or_default();
-            flag = match flag.as_str() {
-                "TI" => "TI".to_string(),
-                "SUPER" => "S".to_string(),
-                _ => "".to_string(),
-            };
-            let card_type = CardType::from_str(&format!("{}{}{}", factory, card_type, flag))
-                .unwrap_or_else(|_| CardType::UNKNOWN(card_info.join(" ")));
-            card_type
+                .find_map(|item| item.strip_suffix("GB"))
+                .and_then(|size| size.parse::<f64>().ok())
         }
     }
 
@@ -191,87 +499,148 @@ pub mod market {
         code: u32,
     }
 
+    /// 服务器不能租用的原因
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Display)]
+    pub enum Exclusion {
+        /// 不是需要的显卡型号
+        GPUMODEL,
+        /// 用户评分不高于3
+        LOWRATING,
+        /// 不支持CLORE支付
+        COINNOTALLOWED,
+        RENTED,
+        /// 最长租用时间不超过72小时
+        SHORTMRL,
+        /// 下载带宽不超过25Mbps
+        SLOWNETWORK,
+        CPUMODEL,
+        /// cpu核数格式错误或少于8核
+        FEWCPUS,
+        BLOCKED,
+        UNKNOWNCARD,
+        /// 只租用4090
+        CARDTYPE,
+        /// 单卡价格超过上限
+        OVERPRICED,
+    }
+
+    /// 市场上的服务器以及不能租用的原因，exclusions为空时可以租用
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct Candidate {
+        pub card: Card,
+        pub exclusions: Vec<Exclusion>,
+    }
+
+    impl Candidate {
+        pub fn is_available(&self) -> bool {
+            self.exclusions.is_empty()
+        }
+    }
+
+    impl Server {
+        pub fn to_card(&self) -> Card {
+            let number = self.specs.get_card_number();
+            let card_type = self.specs.get_card_type();
+            let price_demand = self
+                .price
+                .on_demand
+                .get("CLORE-Blockchain")
+                .copied()
+                .unwrap_or_default();
+            let avg_price_demand = price_demand / (number as f64);
+            let price_spot = self
+                .price
+                .spot
+                .get("CLORE-Blockchain")
+                .copied()
+                .unwrap_or_default();
+            let avg_price_spot = price_spot / (number as f64);
+            let avg_score = *self.rating.get("avg").unwrap_or(&0f32) as f64;
+            Card {
+                server_id: self.id,
+                avg_score,
+                price_demand,
+                avg_price_demand,
+                price_spot,
+                avg_price_spot,
+                mrl: self.mrl,
+                card_number: number,
+                rented: self.rented,
+                card_type,
+            }
+        }
+    }
+
     impl Marketplace {
-        pub fn filter(&self) -> Vec<Card> {
+        /// 所有服务器是否可以租用，不可以时列出全部原因
+        pub fn candidates(&self, blocked_server_ids: &[u32]) -> Vec<Candidate> {
             let regex_cpu = Regex::new(r"((?i)yzen|intel|core)").unwrap();
             let regex_gpu = Regex::new(r"(3080|3090|4070|4080|4080|4090)").unwrap();
-            let blocked_server_ids = Clore::import_block_server_ids();
-            let mut cards: Vec<Card> = (*self)
+            (*self)
                 .iter()
-                .filter(|item| {
-                    let machine_properties = &item.specs;
-                    let gpu = &machine_properties.gpu;
-                    let cpu = &machine_properties.cpu;
-                    let cpus = &machine_properties
+                .map(|item| {
+                    let card = item.to_card();
+                    let mut exclusions = Vec::new();
+                    let cpus = item
+                        .specs
                         .cpus
                         .split("/")
                         .map(|cpu| cpu.parse::<u32>().unwrap_or(0u32))
                         .collect::<Vec<u32>>();
-                    if cpus.len() != 2 {
-                        return false;
+                    if !regex_gpu.is_match(&item.specs.gpu) {
+                        exclusions.push(Exclusion::GPUMODEL);
                     }
-                    let used = cpus.get(0).unwrap_or(&0u32);
-                    #[allow(unused)]
-                    let total = cpus.get(1).unwrap_or(&0u32);
-                    regex_gpu.is_match(&gpu)
-                        && item.rating.get("avg").unwrap_or(&0f32) > &3f32
-                        && item.allowed_coins.contains(&"CLORE-Blockchain".to_string())
-                        && !item.rented
-                        && item.mrl > 72
-                        && item.specs.net.down > 25f64
-                        && regex_cpu.is_match(cpu)
-                        && used >= &8u32
-                })
-                .filter(|card| !blocked_server_ids.contains(&card.id))
-                .map(|item| {
-                    let number = item.specs.get_card_number();
-                    let card_type = item.specs.get_card_type();
-                    let price_demand = item
-                        .price
-                        .on_demand
-                        .get("CLORE-Blockchain")
-                        .and_then(|price| Some(price.clone()))
-                        .unwrap_or_default();
-                    let avg_price_demand = price_demand / (number as f64);
-                    let price_spot = item
-                        .price
-                        .spot
-                        .get("CLORE-Blockchain")
-                        .and_then(|price| Some(price.clone()))
-                        .unwrap_or_default();
-                    let avg_price_spot = price_spot / (number as f64);
-                    let avg_score = item.rating.get("avg").unwrap_or(&0f32);
-                    let avg_score = *avg_score as f64;
-                    let card = Card {
-                        server_id: item.id,
-                        avg_score: avg_score,
-                        price_demand: price_demand,
-                        avg_price_demand: avg_price_demand,
-                        price_spot: price_spot,
-                        avg_price_spot: avg_price_spot,
-                        mrl: item.mrl,
-                        card_number: number,
-                        rented: item.rented,
-                        card_type: card_type,
-                    };
-                    card
+                    if item.rating.get("avg").unwrap_or(&0f32) <= &3f32 {
+                        exclusions.push(Exclusion::LOWRATING);
+                    }
+                    if !item.allowed_coins.contains(&"CLORE-Blockchain".to_string()) {
+                        exclusions.push(Exclusion::COINNOTALLOWED);
+                    }
+                    if item.rented {
+                        exclusions.push(Exclusion::RENTED);
+                    }
+                    if item.mrl <= 72 {
+                        exclusions.push(Exclusion::SHORTMRL);
+                    }
+                    if item.specs.net.down <= 25f64 {
+                        exclusions.push(Exclusion::SLOWNETWORK);
+                    }
+                    if !regex_cpu.is_match(&item.specs.cpu) {
+                        exclusions.push(Exclusion::CPUMODEL);
+                    }
+                    if cpus.len() != 2 || cpus[0] < 8 {
+                        exclusions.push(Exclusion::FEWCPUS);
+                    }
+                    if blocked_server_ids.contains(&item.id) {
+                        exclusions.push(Exclusion::BLOCKED);
+                    }
+                    match card.card_type {
+                        CardType::UNKNOWN(_) => exclusions.push(Exclusion::UNKNOWNCARD),
+                        CardType::NVIDIA4090 => {}
+                        _ => exclusions.push(Exclusion::CARDTYPE),
+                    }
+                    if card.card_type.get_max_price(1f64) <= card.avg_price_demand {
+                        exclusions.push(Exclusion::OVERPRICED);
+                    }
+                    Candidate { card, exclusions }
                 })
-                .filter(|item| {
-                    let total_max_price = item.card_type.get_max_price(1f64);
-                    match item.card_type {
-                        CardType::UNKNOWN(_) => {
-                            warn!("未知显卡:{:?}", item.card_type);
-                            false
-                        }
-                        _ if total_max_price > item.avg_price_demand
-                            && item.card_type == CardType::NVIDIA4090 =>
-                        {
-                            true
-                        }
+                .collect()
+        }
 
-                        _ => false,
+        pub fn filter(&self) -> Vec<Card> {
+            let blocked_server_ids = Clore::import_block_server_ids();
+            let mut cards: Vec<Card> = self
+                .candidates(&blocked_server_ids)
+                .into_iter()
+                .filter(|candidate| {
+                    if let CardType::UNKNOWN(_) = candidate.card.card_type {
+                        if !candidate.exclusions.contains(&Exclusion::GPUMODEL) {
+                            warn!("未知显卡:{:?}", candidate.card.card_type);
+                        }
                     }
+                    candidate.is_available()
                 })
+                .map(|candidate| candidate.card)
                 .collect();
             cards.sort_by(|a, b| b.card_type.cmp(&a.card_type));
             cards.reverse();
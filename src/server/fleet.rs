@@ -0,0 +1,173 @@
+use futures::StreamExt;
+use tracing::info;
+
+use super::{
+    address::{Deployed, Wallet},
+    clore::{
+        model::{my_orders::Order, CardType},
+        Clore,
+    },
+    ssh::{Credential, ExecOutput, Ssh, SshError},
+};
+
+/// 订单筛选条件，条件之间为且，同一条件的多个值为或，为空时不限制
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FleetFilter {
+    pub order_ids: Vec<u32>,
+    pub server_ids: Vec<u32>,
+    pub card_types: Vec<CardType>,
+    /// 钱包地址，按部署的订单号或服务器匹配
+    pub wallets: Vec<String>,
+}
+
+impl FleetFilter {
+    pub fn is_empty(&self) -> bool {
+        self.order_ids.is_empty()
+            && self.server_ids.is_empty()
+            && self.card_types.is_empty()
+            && self.wallets.is_empty()
+    }
+
+    fn matches_wallet(&self, order: &Order, wallets: &[Wallet]) -> bool {
+        wallets
+            .iter()
+            .filter(|wallet| self.wallets.contains(&wallet.address))
+            .any(|wallet| match wallet.deploy {
+                Deployed::NOTASSIGNED => false,
+                Deployed::DEPLOYING {
+                    orderid, serverid, ..
+                }
+                | Deployed::DEPLOYED {
+                    orderid, serverid, ..
+                } => (orderid != 0 && orderid == order.order_id) || serverid == order.server_id,
+            })
+    }
+
+    pub fn select<'a>(&self, orders: &'a [Order], wallets: &[Wallet]) -> Vec<&'a Order> {
+        orders
+            .iter()
+            .filter(|order| self.order_ids.is_empty() || self.order_ids.contains(&order.order_id))
+            .filter(|order| {
+                self.server_ids.is_empty() || self.server_ids.contains(&order.server_id)
+            })
+            .filter(|order| {
+                self.card_types.is_empty() || self.card_types.contains(&order.specs.get_card_type())
+            })
+            .filter(|order| self.wallets.is_empty() || self.matches_wallet(order, wallets))
+            .collect()
+    }
+}
+
+/// 一台服务器的执行结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostResult {
+    pub order_id: u32,
+    pub server_id: u32,
+    pub result: Result<ExecOutput, SshError>,
+}
+
+impl HostResult {
+    pub fn is_success(&self) -> bool {
+        matches!(&self.result, Ok(output) if output.status == 0)
+    }
+}
+
+/// 输出完全相同的服务器为一组
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputGroup {
+    pub result: Result<ExecOutput, SshError>,
+    /// (server_id, order_id)
+    pub hosts: Vec<(u32, u32)>,
+}
+
+/// 按输出分组，服务器多的组在前
+pub fn group(results: &[HostResult]) -> Vec<OutputGroup> {
+    let mut groups: Vec<OutputGroup> = Vec::new();
+    for host in results.iter() {
+        let id = (host.server_id, host.order_id);
+        match groups.iter_mut().find(|group| group.result == host.result) {
+            Some(group) => group.hosts.push(id),
+            None => groups.push(OutputGroup {
+                result: host.result.clone(),
+                hosts: vec![id],
+            }),
+        }
+    }
+    for group in groups.iter_mut() {
+        group.hosts.sort();
+    }
+    groups.sort_by(|a, b| {
+        b.hosts
+            .len()
+            .cmp(&a.hosts.len())
+            .then(a.hosts.cmp(&b.hosts))
+    });
+    groups
+}
+
+pub fn format_groups(groups: &[OutputGroup]) -> String {
+    let mut text = String::new();
+    for group in groups.iter() {
+        let hosts = group
+            .hosts
+            .iter()
+            .map(|(server_id, order_id)| format!("{}/{}", server_id, order_id))
+            .collect::<Vec<String>>();
+        let state = match &group.result {
+            Ok(output) => format!("exit:{}", output.status),
+            Err(e) => format!("error:{}", e),
+        };
+        text.push_str(&format!(
+            "== {}台 {} serverid/orderid:{}\n",
+            group.hosts.len(),
+            state,
+            hosts.join(",")
+        ));
+        if let Ok(output) = &group.result {
+            if !output.stdout.is_empty() {
+                text.push_str(&output.stdout);
+                if !output.stdout.ends_with('\n') {
+                    text.push('\n');
+                }
+            }
+            if !output.stderr.is_empty() {
+                text.push_str("-- stderr\n");
+                text.push_str(&output.stderr);
+                if !output.stderr.ends_with('\n') {
+                    text.push('\n');
+                }
+            }
+        }
+    }
+    text
+}
+
+/// 在多台服务器上并发执行命令，concurrency为空时使用ssh配置
+pub async fn exec(orders: &[&Order], command: &str, concurrency: Option<usize>) -> Vec<HostResult> {
+    let ssh_passwd = Clore::get_config().await.ssh_passwd;
+    let timeouts = Ssh::get_config().await;
+    let concurrency = concurrency.unwrap_or(timeouts.concurrency).max(1);
+    info!(
+        "批量执行:{}台,并发:{},命令:{}",
+        orders.len(),
+        concurrency,
+        command
+    );
+    futures::stream::iter(orders.iter().map(|order| (*order).clone()))
+        .map(|order| {
+            let credential = Credential::for_order(&order, &ssh_passwd, &timeouts);
+            let timeouts = timeouts.clone();
+            let command = command.to_string();
+            async move {
+                let result = Ssh::run_with_timeouts(&order, &credential, &command, &timeouts).await;
+                HostResult {
+                    order_id: order.order_id,
+                    server_id: order.server_id,
+                    result,
+                }
+            }
+        })
+        .buffer_unordered(concurrency)
+        .collect::<Vec<HostResult>>()
+        .await
+}
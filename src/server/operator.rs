@@ -0,0 +1,68 @@
+use super::{
+    address::{Deployed, Wallet},
+    clore::model::{market::Candidate, my_orders::Order},
+};
+
+/// 市场服务器表格，按单卡价格排序
+pub fn format_candidates(candidates: &[Candidate]) -> String {
+    let mut candidates = candidates.iter().collect::<Vec<&Candidate>>();
+    candidates.sort_by(|a, b| a.card.avg_price_demand.total_cmp(&b.card.avg_price_demand));
+    let mut text = format!(
+        "{:>8} {:<12} {:>4} {:>10} {:>10} {:>6} {:>5}  {}\n",
+        "serverid", "显卡", "数量", "价格/天", "单卡/天", "评分", "mrl", "不可租用原因"
+    );
+    for candidate in candidates.iter() {
+        let card = &candidate.card;
+        let exclusions = candidate
+            .exclusions
+            .iter()
+            .map(|exclusion| exclusion.to_string())
+            .collect::<Vec<String>>();
+        text.push_str(&format!(
+            "{:>8} {:<12} {:>4} {:>10.2} {:>10.2} {:>6.2} {:>5}  {}\n",
+            card.server_id,
+            card.card_type.to_string(),
+            card.card_number,
+            card.price_demand,
+            card.avg_price_demand,
+            card.avg_score,
+            card.mrl,
+            exclusions.join(",")
+        ));
+    }
+    text
+}
+
+/// ssh目标可以是订单号或钱包地址，钱包地址按部署的订单号或服务器查找
+pub fn find_order<'a>(
+    target: &str,
+    orders: &'a [Order],
+    wallets: &[Wallet],
+) -> Result<&'a Order, String> {
+    if let Ok(order_id) = target.parse::<u32>() {
+        return orders
+            .iter()
+            .find(|order| order.order_id == order_id)
+            .ok_or(format!("订单不存在:{}", order_id));
+    }
+    let wallet = wallets
+        .iter()
+        .find(|wallet| wallet.address == target)
+        .ok_or(format!("钱包地址不存在:{}", target))?;
+    let (orderid, serverid) = match wallet.deploy {
+        Deployed::NOTASSIGNED => return Err(format!("钱包未分配服务器:{}", target)),
+        Deployed::DEPLOYING {
+            orderid, serverid, ..
+        }
+        | Deployed::DEPLOYED {
+            orderid, serverid, ..
+        } => (orderid, serverid),
+    };
+    orders
+        .iter()
+        .find(|order| (orderid != 0 && order.order_id == orderid) || order.server_id == serverid)
+        .ok_or(format!(
+            "钱包对应的订单不存在:{},serverid:{}",
+            target, serverid
+        ))
+}
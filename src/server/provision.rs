@@ -0,0 +1,361 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::Arc,
+};
+
+use chrono::{DateTime, Local};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use strum::Display;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use crate::{
+    alert::{self, Alert, Severity},
+    config::{self, CONFIG},
+};
+
+use super::{
+    address::{Address, Deployed, WALLETS_STATE},
+    clore::{model::my_orders::Order, Clore},
+    ssh::{Credential, Ssh},
+    verify,
+};
+
+/// 链接步骤，失败时说明ssh还未启动，下一轮直接重试
+pub const CONNECT: &str = "connect";
+pub const UPLOAD: &str = "upload";
+
+lazy_static! {
+    /// 正在部署的订单
+    static ref PROVISIONING: Arc<Mutex<HashSet<u32>>> = Arc::new(Mutex::new(HashSet::new()));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Display)]
+pub enum StepState {
+    RUNNING,
+    SUCCESS,
+    FAILED,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StepStatus {
+    pub name: String,
+    pub state: StepState,
+    pub message: String,
+    pub time: DateTime<Local>,
+}
+
+/// 钱包所在订单的部署进度
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Provisioning {
+    pub orderid: u32,
+    pub steps: Vec<StepStatus>,
+    /// 所有步骤成功的时间
+    pub finished: Option<DateTime<Local>>,
+}
+
+impl Provisioning {
+    pub fn new(orderid: u32) -> Provisioning {
+        Provisioning {
+            orderid,
+            steps: Vec::new(),
+            finished: None,
+        }
+    }
+
+    /// 同名步骤覆盖之前的状态
+    pub fn update(&mut self, name: &str, state: StepState, message: &str, time: DateTime<Local>) {
+        let status = StepStatus {
+            name: name.to_string(),
+            state,
+            message: message.to_string(),
+            time,
+        };
+        match self.steps.iter_mut().find(|step| step.name == name) {
+            Some(step) => *step = status,
+            None => self.steps.push(status),
+        }
+    }
+
+    /// 没有开始或链接失败时立即部署，其它步骤失败后间隔retry_interval秒重试
+    pub fn needs_run(&self, orderid: u32, retry_interval: u64, now: DateTime<Local>) -> bool {
+        if self.orderid != orderid {
+            return true;
+        }
+        if self.finished.is_some() {
+            return false;
+        }
+        match self
+            .steps
+            .iter()
+            .find(|step| step.state == StepState::FAILED)
+        {
+            Some(step) if step.name == CONNECT => true,
+            Some(step) => (now - step.time).num_seconds() >= retry_interval as i64,
+            None => self.steps.is_empty(),
+        }
+    }
+
+    /// 正在执行的步骤的开始时间，部署超时从这个时间重新计算
+    pub fn running_since(&self) -> Option<DateTime<Local>> {
+        self.steps
+            .iter()
+            .filter(|step| step.state == StepState::RUNNING)
+            .map(|step| step.time)
+            .max()
+    }
+}
+
+impl std::fmt::Display for Provisioning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let steps = self
+            .steps
+            .iter()
+            .map(|step| format!("{}:{}", step.name, step.state))
+            .collect::<Vec<String>>();
+        write!(f, "orderid:{},{}", self.orderid, steps.join(","))
+    }
+}
+
+/// 替换步骤命令中的{address}和{card_number}
+pub fn render_command(command: &str, address: &str, card_number: usize) -> String {
+    command
+        .replace("{address}", address)
+        .replace("{card_number}", &card_number.to_string())
+}
+
+/// 需要部署的订单和订单上的钱包地址，地址按字母排序对应显卡序号
+pub fn pending(
+    wallets: &Address,
+    orders: &[Order],
+    retry_interval: u64,
+    now: DateTime<Local>,
+) -> Vec<(Order, Vec<String>)> {
+    let mut addresses = BTreeMap::<u32, Vec<String>>::new();
+    let mut needs_run = HashSet::<u32>::new();
+    for wallet in wallets.values() {
+        let Deployed::DEPLOYING { orderid, .. } = wallet.deploy else {
+            continue;
+        };
+        if orderid == 0 {
+            continue;
+        }
+        addresses
+            .entry(orderid)
+            .or_default()
+            .push(wallet.address.clone());
+        let run = wallet
+            .provision
+            .as_ref()
+            .is_none_or(|provision| provision.needs_run(orderid, retry_interval, now));
+        if run {
+            needs_run.insert(orderid);
+        }
+    }
+    orders
+        .iter()
+        .filter(|order| needs_run.contains(&order.order_id))
+        .filter(|order| order.get_ssh_host().is_some() && order.get_map_ssh_port().is_some())
+        .filter_map(|order| {
+            let mut addresses = addresses.get(&order.order_id)?.clone();
+            addresses.sort();
+            Some((order.clone(), addresses))
+        })
+        .collect()
+}
+
+/// 记录到订单上指定钱包的部署状态
+async fn record(orderid: u32, addresses: &[String], name: &str, state: StepState, message: &str) {
+    let wallets = Arc::clone(&WALLETS_STATE);
+    let mut locked = wallets.lock().await;
+    for address in addresses.iter() {
+        let Some(wallet) = locked.get_mut(address) else {
+            continue;
+        };
+        match wallet.deploy {
+            Deployed::DEPLOYING { orderid: id, .. } | Deployed::DEPLOYED { orderid: id, .. }
+                if id == orderid => {}
+            _ => continue,
+        }
+        let provision = wallet
+            .provision
+            .get_or_insert_with(|| Provisioning::new(orderid));
+        if provision.orderid != orderid {
+            *provision = Provisioning::new(orderid);
+        }
+        provision.update(name, state, message, Local::now());
+    }
+}
+
+/// 新的一轮部署清空之前的步骤
+async fn start(orderid: u32, addresses: &[String]) {
+    let wallets = Arc::clone(&WALLETS_STATE);
+    let mut locked = wallets.lock().await;
+    for address in addresses.iter() {
+        if let Some(wallet) = locked.get_mut(address) {
+            wallet.provision = Some(Provisioning::new(orderid));
+        }
+    }
+}
+
+async fn finish(orderid: u32, addresses: &[String]) {
+    let wallets = Arc::clone(&WALLETS_STATE);
+    let mut locked = wallets.lock().await;
+    for address in addresses.iter() {
+        if let Some(provision) = locked
+            .get_mut(address)
+            .and_then(|wallet| wallet.provision.as_mut())
+            .filter(|provision| provision.orderid == orderid)
+        {
+            provision.finished = Some(Local::now());
+        }
+    }
+}
+
+/// 执行一个步骤并记录状态，命令退出码不为0视为失败
+async fn run_step(
+    order: &Order,
+    addresses: &[String],
+    name: &str,
+    command: &str,
+    credential: &Credential,
+    timeouts: &config::Ssh,
+) -> Result<(), String> {
+    record(order.order_id, addresses, name, StepState::RUNNING, "").await;
+    let result = match Ssh::run_with_timeouts(order, credential, command, timeouts).await {
+        Ok(output) if output.status == 0 => Ok(()),
+        Ok(output) => Err(format!(
+            "退出码:{},{}{}",
+            output.status,
+            output.stdout.trim(),
+            output.stderr.trim()
+        )),
+        Err(e) => Err(e.to_string()),
+    };
+    match &result {
+        Ok(_) => record(order.order_id, addresses, name, StepState::SUCCESS, "").await,
+        Err(e) => record(order.order_id, addresses, name, StepState::FAILED, e).await,
+    }
+    result
+}
+
+/// 链接、上传文件、依次执行部署步骤，失败时停止并返回失败的步骤
+async fn provision_order(
+    order: &Order,
+    addresses: &[String],
+    config: &config::Provision,
+) -> Result<(), (String, String)> {
+    let ssh_passwd = Clore::get_config().await.ssh_passwd;
+    let timeouts = Ssh::get_config().await;
+    let credential = Credential::for_order(order, &ssh_passwd, &timeouts);
+    let orderid = order.order_id;
+    start(orderid, addresses).await;
+
+    run_step(order, addresses, CONNECT, "true", &credential, &timeouts)
+        .await
+        .map_err(|e| (CONNECT.to_string(), e))?;
+
+    record(orderid, addresses, UPLOAD, StepState::RUNNING, "").await;
+    let upload_timeouts = config::Ssh {
+        exec_timeout: config.upload_timeout,
+        ..timeouts.clone()
+    };
+    let result = Ssh::upload_to_order(order, &credential, &config.files, &upload_timeouts)
+        .await
+        .map_err(|e| e.to_string());
+    match &result {
+        Ok(_) => record(orderid, addresses, UPLOAD, StepState::SUCCESS, "").await,
+        Err(e) => record(orderid, addresses, UPLOAD, StepState::FAILED, e).await,
+    }
+    result.map_err(|e| (UPLOAD.to_string(), e))?;
+
+    for step in config.steps.iter() {
+        let timeouts = config::Ssh {
+            exec_timeout: step.timeout,
+            ..timeouts.clone()
+        };
+        if step.per_address {
+            for (card_number, address) in addresses.iter().enumerate() {
+                let command = render_command(&step.command, address, card_number);
+                run_step(
+                    order,
+                    std::slice::from_ref(address),
+                    &step.name,
+                    &command,
+                    &credential,
+                    &timeouts,
+                )
+                .await
+                .map_err(|e| (step.name.clone(), e))?;
+            }
+        } else {
+            run_step(
+                order,
+                addresses,
+                &step.name,
+                &step.command,
+                &credential,
+                &timeouts,
+            )
+            .await
+            .map_err(|e| (step.name.clone(), e))?;
+        }
+    }
+    finish(orderid, addresses).await;
+    Ok(())
+}
+
+/// 对ssh可用的新订单在后台部署，同一订单同时只部署一次
+pub async fn schedule(wallets: &Address, orders: &[Order]) {
+    let config = Arc::clone(&CONFIG);
+    let provision = config.lock().await.provision.clone();
+    if !provision.enabled {
+        return;
+    }
+    for (order, addresses) in pending(wallets, orders, provision.retry_interval, Local::now()) {
+        // 硬件检查通过后再部署
+        if !verify::passed(order.order_id).await {
+            continue;
+        }
+        let provisioning = Arc::clone(&PROVISIONING);
+        if !provisioning.lock().await.insert(order.order_id) {
+            continue;
+        }
+        let provision = provision.clone();
+        tokio::spawn(async move {
+            info!(
+                "开始部署:serverid:{},orderid:{},地址:{:?}",
+                order.server_id, order.order_id, addresses
+            );
+            match provision_order(&order, &addresses, &provision).await {
+                Ok(_) => info!(
+                    "部署完成:serverid:{},orderid:{}",
+                    order.server_id, order.order_id
+                ),
+                // ssh还未启动，下一轮重试
+                Err((step, e)) if step == CONNECT => info!(
+                    "等待ssh:serverid:{},orderid:{},{}",
+                    order.server_id, order.order_id, e
+                ),
+                Err((step, e)) => {
+                    warn!(
+                        "部署失败:serverid:{},orderid:{},{},{}",
+                        order.server_id, order.order_id, step, e
+                    );
+                    alert::notify(Alert::new(
+                        format!("provision:{}", order.order_id),
+                        Severity::WARNING,
+                        "部署失败",
+                        format!(
+                            "serverid:{},orderid:{},步骤:{},{}",
+                            order.server_id, order.order_id, step, e
+                        ),
+                    ));
+                }
+            }
+            let provisioning = Arc::clone(&PROVISIONING);
+            provisioning.lock().await.remove(&order.order_id);
+        });
+    }
+}
@@ -0,0 +1,295 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use chrono::{DateTime, Local};
+use futures::StreamExt;
+use lazy_static::lazy_static;
+use ssh2::Session;
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+
+use crate::{
+    config::{self, CONFIG},
+    log::{
+        follower::{Offset, Offsets},
+        rule::{RuleKind, Rules},
+        RunLogs,
+    },
+};
+
+use super::{
+    accounting::{Accounting, ACCOUNTING},
+    clore::{model::my_orders::Order, Clore},
+    ssh::{Credential, Phase, Ssh, SshError},
+    store::{LogStore, LOG_STORE},
+    stream,
+};
+
+lazy_static! {
+    /// 远程文件已拉取的位置，远程文件没有inode，inode为0
+    pub static ref PULL_OFFSETS: Arc<Mutex<Offsets>> = Arc::new(Mutex::new(Offsets::load(
+        std::env::current_dir().unwrap().join(".pull_offsets.json")
+    )));
+}
+
+/// 远程文件新增的内容，end为下次读取的位置
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    pub path: String,
+    pub end: u64,
+    pub data: Vec<u8>,
+}
+
+/// 一次拉取的结果
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PullSummary {
+    pub files: usize,
+    pub lines: usize,
+    pub tasks: usize,
+}
+
+impl std::fmt::Display for PullSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "文件:{},日志:{}条,新任务:{}",
+            self.files, self.lines, self.tasks
+        )
+    }
+}
+
+fn offset_key(order_id: u32, path: &str) -> PathBuf {
+    PathBuf::from(format!("{}:{}", order_id, path))
+}
+
+/// 记录的位置大于文件大小时，文件被截断或重建，从头读取
+pub fn start_offset(stored: Option<u64>, size: u64) -> u64 {
+    match stored {
+        Some(offset) if offset <= size => offset,
+        _ => 0,
+    }
+}
+
+/// 完整行的长度，\r也作为换行(tqdm进度条)，没有换行时为0
+pub fn complete_lines(data: &[u8]) -> usize {
+    data.iter()
+        .rposition(|byte| *byte == b'\n' || *byte == b'\r')
+        .map_or(0, |index| index + 1)
+}
+
+fn read_range(sftp: &ssh2::Sftp, path: &Path, start: u64, len: u64) -> Result<Vec<u8>, SshError> {
+    let mut file = sftp
+        .open(path)
+        .map_err(|e| SshError::SFTP(format!("{:?}:{}", path, e)))?;
+    file.seek(SeekFrom::Start(start))
+        .map_err(|e| SshError::from_io(Phase::SFTP, e))?;
+    let mut data = Vec::new();
+    file.take(len)
+        .read_to_end(&mut data)
+        .map_err(|e| SshError::from_io(Phase::SFTP, e))?;
+    Ok(data)
+}
+
+/// 读取日志目录下<address>.txt新增的完整行，以及有变化的任务记录
+pub fn read_remote(
+    sess: &Session,
+    config: &config::Pull,
+    offsets: &HashMap<String, u64>,
+) -> Result<Vec<Chunk>, SshError> {
+    let sftp = sess.sftp().map_err(|e| SshError::SFTP(e.to_string()))?;
+    let mut chunks = Vec::new();
+    let entries = match sftp.readdir(Path::new(&config.logs_dir)) {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!("读取远程日志目录失败:{},{}", config.logs_dir, e);
+            Vec::new()
+        }
+    };
+    for (path, stat) in entries.iter() {
+        if !stat.is_file() || path.extension().and_then(|ext| ext.to_str()) != Some("txt") {
+            continue;
+        }
+        let key = path.to_string_lossy().to_string();
+        let size = stat.size.unwrap_or_default();
+        let start = start_offset(offsets.get(&key).copied(), size);
+        if size <= start {
+            continue;
+        }
+        let data = read_range(&sftp, path, start, (size - start).min(config.max_bytes))?;
+        let mut consumed = complete_lines(&data);
+        // 超长的一行没有换行时整体读取，避免一直卡住
+        if consumed == 0 && data.len() as u64 >= config.max_bytes {
+            consumed = data.len();
+        }
+        if consumed == 0 {
+            continue;
+        }
+        chunks.push(Chunk {
+            path: key,
+            end: start + consumed as u64,
+            data: data[..consumed].to_vec(),
+        });
+    }
+    // 任务记录是整个json文件，大小变化时重新读取，任务按完成时间去重
+    if let Ok(stat) = sftp.stat(Path::new(&config.run_logs)) {
+        let size = stat.size.unwrap_or_default();
+        if offsets.get(&config.run_logs) != Some(&size) {
+            let data = read_range(&sftp, Path::new(&config.run_logs), 0, size)?;
+            chunks.push(Chunk {
+                path: config.run_logs.clone(),
+                end: size,
+                data,
+            });
+        }
+    }
+    Ok(chunks)
+}
+
+/// 任务记录和监控上报相同，按report_task记账，返回新任务数
+pub fn ingest_tasks(
+    accounting: &Accounting,
+    server_id: u32,
+    chunk: &Chunk,
+    reward_per_task: f64,
+) -> Result<usize, String> {
+    let mut tasks = 0;
+    for task in String::from_utf8_lossy(&chunk.data)
+        .parse::<RunLogs>()?
+        .iter()
+    {
+        if accounting.record_task(server_id, task, reward_per_task)? {
+            tasks += 1;
+        }
+    }
+    Ok(tasks)
+}
+
+/// 日志按监控的规则解析，命中的保存为和监控上报相同的事件，没有命中的按原文保存，返回入库条数
+pub fn ingest_logs(
+    store: &LogStore,
+    rules: &Rules,
+    server_id: u32,
+    chunk: &Chunk,
+    now: DateTime<Local>,
+) -> Result<usize, String> {
+    let address = Path::new(&chunk.path)
+        .file_stem()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    // 进度条按\r刷新，按行解析
+    let body = String::from_utf8_lossy(&chunk.data)
+        .split(['\r', '\n'])
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .filter_map(|line| match rules.parse(address, line) {
+            Some(event) if event.kind == RuleKind::IGNORE => None,
+            Some(mut event) => {
+                event.time = now;
+                serde_json::to_string(&event).ok()
+            }
+            None => Some(line.to_string()),
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+    let records = store.ingest(server_id, address, &body, now)?;
+    stream::publish(&records);
+    Ok(records.len())
+}
+
+/// 拉取一个订单的日志和任务记录，每个文件入库成功后保存位置
+pub async fn pull_order(order: &Order) -> Result<PullSummary, String> {
+    let config = Arc::clone(&CONFIG);
+    let pull = config.lock().await.pull.clone();
+    let ssh_passwd = Clore::get_config().await.ssh_passwd;
+    let timeouts = Ssh::get_config().await;
+    let credential = Credential::for_order(order, &ssh_passwd, &timeouts);
+    let socket_addr = Ssh::resolve(order, &timeouts)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let offsets = Arc::clone(&PULL_OFFSETS);
+    let prefix = format!("{}:", order.order_id);
+    let known = offsets
+        .lock()
+        .await
+        .iter()
+        .filter_map(|(key, offset)| {
+            let path = key.to_str()?.strip_prefix(&prefix)?;
+            Some((path.to_string(), offset.offset))
+        })
+        .collect::<HashMap<String, u64>>();
+
+    let config = pull.clone();
+    let chunks = Ssh::blocking(Phase::SFTP, &timeouts, move |timeouts| {
+        let sess = Ssh::connect(&credential, socket_addr, timeouts)?;
+        sess.set_timeout((timeouts.exec_timeout * 1000) as u32);
+        read_remote(&sess, &config, &known)
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let reward_per_task = Accounting::get_reward_per_task().await;
+    let rules = Rules::load().await;
+    let mut summary = PullSummary::default();
+    // 每个文件入库后立即保存位置，后面的文件失败时不重复入库前面的文件
+    for chunk in chunks.iter() {
+        if chunk.path == pull.run_logs {
+            let accounting = Arc::clone(&ACCOUNTING);
+            let accounting = accounting.lock().await;
+            summary.tasks += ingest_tasks(&accounting, order.server_id, chunk, reward_per_task)?;
+        } else {
+            let store = Arc::clone(&LOG_STORE);
+            let store = store.lock().await;
+            summary.lines += ingest_logs(&store, &rules, order.server_id, chunk, Local::now())?;
+        }
+        summary.files += 1;
+
+        let mut locked = offsets.lock().await;
+        locked.set(
+            &offset_key(order.order_id, &chunk.path),
+            Offset {
+                inode: 0,
+                offset: chunk.end,
+            },
+        );
+        locked.save()?;
+    }
+    info!(
+        "拉取日志:serverid:{},orderid:{},{}",
+        order.server_id, order.order_id, summary
+    );
+    Ok(summary)
+}
+
+/// 定时拉取所有订单
+pub async fn schedule() {
+    loop {
+        let config = Arc::clone(&CONFIG);
+        let pull = config.lock().await.pull.clone();
+        if pull.enabled {
+            let concurrency = Ssh::get_config().await.concurrency.max(1);
+            match Clore::default().my_orders().await {
+                Ok(orders) => {
+                    futures::stream::iter(orders.iter().filter(|order| {
+                        order.get_ssh_host().is_some() && order.get_map_ssh_port().is_some()
+                    }))
+                    .for_each_concurrent(concurrency, |order| async move {
+                        if let Err(e) = pull_order(order).await {
+                            warn!(
+                                "拉取日志失败:serverid:{},orderid:{},{}",
+                                order.server_id, order.order_id, e
+                            );
+                        }
+                    })
+                    .await;
+                }
+                Err(e) => error!("拉取日志获取订单失败:{}", e),
+            }
+        }
+        tokio::time::sleep(std::time::Duration::from_secs(pull.interval.max(60))).await;
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    accounting::Accounting,
+    address::{Address, Deployed},
+    clore::model::my_orders::Order,
+};
+
+/// 单个订单的收益情况，费用单位CLORE，奖励单位$NIM
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrderReport {
+    pub order_id: u32,
+    pub server_id: u32,
+    pub card_type: String,
+    pub card_number: u32,
+    /// 租用价格(CLORE/天)
+    pub price: f64,
+    pub create_time: DateTime<Local>,
+    pub hours: f64,
+    pub cost: f64,
+    pub gpu_hours: f64,
+    pub tasks: i64,
+    pub success: i64,
+    pub reward: f64,
+    pub cost_per_task: Option<f64>,
+    pub cost_per_gpu_hour: Option<f64>,
+    /// 绑定钱包一小时平均算力的均值
+    pub hashrate: Option<f64>,
+    pub addresses: Vec<String>,
+}
+
+/// 按显卡型号汇总，nim_price未配置时无法判断是否回本
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CardTypeReport {
+    pub card_type: String,
+    pub orders: u32,
+    pub cards: u32,
+    pub gpu_hours: f64,
+    pub cost: f64,
+    pub success: i64,
+    pub reward: f64,
+    pub cost_per_task: Option<f64>,
+    pub cost_per_gpu_hour: Option<f64>,
+    pub income: Option<f64>,
+    pub break_even: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Profitability {
+    pub orders: Vec<OrderReport>,
+    pub card_types: Vec<CardTypeReport>,
+}
+
+fn divide(value: f64, by: f64) -> Option<f64> {
+    if by > 0f64 {
+        Some(value / by)
+    } else {
+        None
+    }
+}
+
+impl Profitability {
+    pub fn build(
+        orders: &[Order],
+        wallets: &Address,
+        accounting: &Accounting,
+        nim_price: Option<f64>,
+        now: DateTime<Local>,
+    ) -> Result<Profitability, String> {
+        let mut reports = Vec::new();
+        for order in orders.iter() {
+            let create_time = DateTime::from_timestamp(order.create_time, 0)
+                .ok_or(format!("订单创建时间错误:{}", order.create_time))?
+                .with_timezone(&Local);
+            // 到期的订单不再计费，duration为可用时长(秒)
+            let mut seconds = (now - create_time).num_seconds().max(0);
+            if order.duration > 0 {
+                seconds = seconds.min(order.duration as i64);
+            }
+            let hours = seconds as f64 / 3600f64;
+            let card_number = order.specs.get_card_number();
+            let cost = order.price * hours / 24f64;
+            let gpu_hours = hours * card_number as f64;
+            let earning = accounting.earning_for_server(order.server_id, order.create_time)?;
+
+            let mut addresses = Vec::new();
+            let mut hashrates = Vec::new();
+            for (address, wallet) in wallets.iter() {
+                if let Deployed::DEPLOYING { orderid, .. } | Deployed::DEPLOYED { orderid, .. } =
+                    &wallet.deploy
+                {
+                    if *orderid == order.order_id {
+                        addresses.push(address.clone());
+                        if let Some(avg_1h) = wallet.hashrate.as_ref().and_then(|s| s.avg_1h) {
+                            hashrates.push(avg_1h);
+                        }
+                    }
+                }
+            }
+            addresses.sort();
+
+            reports.push(OrderReport {
+                order_id: order.order_id,
+                server_id: order.server_id,
+                card_type: order.specs.get_card_type().to_string(),
+                card_number,
+                price: order.price,
+                create_time,
+                hours,
+                cost,
+                gpu_hours,
+                tasks: earning.tasks,
+                success: earning.success,
+                reward: earning.reward,
+                cost_per_task: divide(cost, earning.success as f64),
+                cost_per_gpu_hour: divide(cost, gpu_hours),
+                hashrate: divide(hashrates.iter().sum::<f64>(), hashrates.len() as f64),
+                addresses,
+            });
+        }
+
+        let mut groups = BTreeMap::<String, Vec<&OrderReport>>::new();
+        for report in reports.iter() {
+            groups
+                .entry(report.card_type.clone())
+                .or_default()
+                .push(report);
+        }
+        let card_types = groups
+            .into_iter()
+            .map(|(card_type, reports)| {
+                let cost = reports.iter().map(|report| report.cost).sum::<f64>();
+                let gpu_hours = reports.iter().map(|report| report.gpu_hours).sum::<f64>();
+                let success = reports.iter().map(|report| report.success).sum::<i64>();
+                let reward = reports.iter().map(|report| report.reward).sum::<f64>();
+                let income = nim_price.map(|price| price * reward);
+                CardTypeReport {
+                    card_type,
+                    orders: reports.len() as u32,
+                    cards: reports.iter().map(|report| report.card_number).sum(),
+                    gpu_hours,
+                    cost,
+                    success,
+                    reward,
+                    cost_per_task: divide(cost, success as f64),
+                    cost_per_gpu_hour: divide(cost, gpu_hours),
+                    income,
+                    break_even: income.map(|income| income >= cost),
+                }
+            })
+            .collect();
+
+        Ok(Profitability {
+            orders: reports,
+            card_types,
+        })
+    }
+
+    /// 导出订单明细和按显卡型号汇总的csv
+    pub fn to_csv(&self) -> String {
+        let option = |value: Option<f64>| value.map(|v| format!("{:.4}", v)).unwrap_or_default();
+        let mut rows = vec![
+            "order_id,server_id,card_type,card_number,price,create_time,hours,cost,gpu_hours,tasks,success,reward,cost_per_task,cost_per_gpu_hour,hashrate,addresses"
+                .to_string(),
+        ];
+        for report in self.orders.iter() {
+            rows.push(format!(
+                "{},{},{},{},{:.4},{},{:.2},{:.4},{:.2},{},{},{:.4},{},{},{},{}",
+                report.order_id,
+                report.server_id,
+                report.card_type,
+                report.card_number,
+                report.price,
+                report.create_time.format("%Y-%m-%d %H:%M:%S"),
+                report.hours,
+                report.cost,
+                report.gpu_hours,
+                report.tasks,
+                report.success,
+                report.reward,
+                option(report.cost_per_task),
+                option(report.cost_per_gpu_hour),
+                option(report.hashrate),
+                report.addresses.join(" ")
+            ));
+        }
+        // 空行后按显卡型号汇总，break_even为空表示未配置nim_price
+        rows.push(String::new());
+        rows.push(
+            "card_type,orders,cards,gpu_hours,cost,success,reward,cost_per_task,cost_per_gpu_hour,income,break_even"
+                .to_string(),
+        );
+        for report in self.card_types.iter() {
+            rows.push(format!(
+                "{},{},{},{:.2},{:.4},{},{:.4},{},{},{},{}",
+                report.card_type,
+                report.orders,
+                report.cards,
+                report.gpu_hours,
+                report.cost,
+                report.success,
+                report.reward,
+                option(report.cost_per_task),
+                option(report.cost_per_gpu_hour),
+                option(report.income),
+                report
+                    .break_even
+                    .map(|break_even| break_even.to_string())
+                    .unwrap_or_default()
+            ));
+        }
+        rows.join("\n") + "\n"
+    }
+}
@@ -1,70 +1,258 @@
+use std::collections::BTreeMap;
 use std::collections::HashMap;
+use std::fmt::Display;
 use std::io::Read;
 use std::net::SocketAddr;
 use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use futures::StreamExt;
 use hickory_resolver::config::*;
 use hickory_resolver::TokioAsyncResolver;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
 use ssh2::Session;
 use tracing::info;
 use tracing::warn;
 
+use crate::alert::{self, Alert, Severity};
+use crate::config::{self, CONFIG};
 use crate::server::address::Deployed;
 use crate::server::clore::Clore;
 
 use super::clore::model::my_orders::Order;
 
+/// 查找远程挖矿进程的命令
+pub const PROCESS_COMMAND: &str = "ps -aeo command |grep execute.py |grep -v grep";
+
+/// libssh2超时的错误码
+const LIBSSH2_ERROR_TIMEOUT: i32 = -9;
+
+lazy_static! {
+    /// 多个阻塞线程同时检测时，串行读写主机公钥文件
+    static ref HOST_KEYS_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+}
+
+/// ssh链接的阶段
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, strum::Display)]
+pub enum Phase {
+    DNS,
+    TCP,
+    HANDSHAKE,
+    AUTH,
+    EXEC,
+    SFTP,
+}
+
+/// ssh远程操作失败的类型
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SshError {
+    /// 订单没有ssh域名或端口映射
+    NOADDRESS,
+    DNS(String),
+    TCP(String),
+    HANDSHAKE(String),
+    AUTH(String),
+    EXEC(String),
+    SFTP(String),
+    TIMEOUT(Phase),
+    /// 主机公钥和首次链接时记录的不一致
+    HOSTKEY(String),
+    /// 命令执行成功，但没有找到挖矿进程
+    NOPROCESS,
+}
+
+impl SshError {
+    fn new(phase: Phase, message: String) -> SshError {
+        match phase {
+            Phase::DNS => SshError::DNS(message),
+            Phase::TCP => SshError::TCP(message),
+            Phase::HANDSHAKE => SshError::HANDSHAKE(message),
+            Phase::AUTH => SshError::AUTH(message),
+            Phase::EXEC => SshError::EXEC(message),
+            Phase::SFTP => SshError::SFTP(message),
+        }
+    }
+
+    fn from_ssh2(phase: Phase, e: ssh2::Error) -> SshError {
+        if e.code() == ssh2::ErrorCode::Session(LIBSSH2_ERROR_TIMEOUT) {
+            SshError::TIMEOUT(phase)
+        } else {
+            SshError::new(phase, e.to_string())
+        }
+    }
+
+    pub fn from_io(phase: Phase, e: std::io::Error) -> SshError {
+        match e.kind() {
+            std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock => {
+                SshError::TIMEOUT(phase)
+            }
+            _ => SshError::new(phase, e.to_string()),
+        }
+    }
+}
+
+impl Display for SshError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SshError::NOADDRESS => write!(f, "没有ssh地址"),
+            SshError::DNS(e) => write!(f, "域名解析失败:{}", e),
+            SshError::TCP(e) => write!(f, "tcp链接失败:{}", e),
+            SshError::HANDSHAKE(e) => write!(f, "ssh握手失败:{}", e),
+            SshError::AUTH(e) => write!(f, "ssh认证失败:{}", e),
+            SshError::EXEC(e) => write!(f, "远程执行失败:{}", e),
+            SshError::SFTP(e) => write!(f, "上传文件失败:{}", e),
+            SshError::TIMEOUT(phase) => write!(f, "{}超时", phase),
+            SshError::HOSTKEY(e) => write!(f, "主机公钥校验失败:{}", e),
+            SshError::NOPROCESS => write!(f, "远程进程无结果"),
+        }
+    }
+}
+
+/// 订单的主机公钥指纹，首次链接时记录，之后必须一致。文件每行为"订单号 指纹"
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HostKeys {
+    path: PathBuf,
+    keys: BTreeMap<u32, String>,
+}
+
+impl HostKeys {
+    /// 文件不存在时为空
+    pub fn load(path: impl AsRef<Path>) -> HostKeys {
+        let path = path.as_ref().to_path_buf();
+        let text = std::fs::read_to_string(&path).unwrap_or_default();
+        let keys = text
+            .lines()
+            .filter_map(|line| {
+                let (order_id, fingerprint) = line.trim().split_once(' ')?;
+                Some((
+                    order_id.parse::<u32>().ok()?,
+                    fingerprint.trim().to_string(),
+                ))
+            })
+            .collect();
+        HostKeys { path, keys }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let text = self
+            .keys
+            .iter()
+            .map(|(order_id, fingerprint)| format!("{} {}\n", order_id, fingerprint))
+            .collect::<String>();
+        std::fs::write(&self.path, text).map_err(|e| format!("{:?}:{}", self.path, e))
+    }
+
+    pub fn get(&self, order_id: u32) -> Option<&String> {
+        self.keys.get(&order_id)
+    }
+
+    /// 首次见到的订单记录指纹并返回true，已记录且一致返回false，不一致返回错误
+    pub fn pin(&mut self, order_id: u32, fingerprint: &str) -> Result<bool, String> {
+        match self.keys.get(&order_id) {
+            Some(pinned) if pinned == fingerprint => Ok(false),
+            Some(pinned) => Err(format!(
+                "order_id:{},记录:{},当前:{}",
+                order_id, pinned, fingerprint
+            )),
+            None => {
+                self.keys.insert(order_id, fingerprint.to_string());
+                self.save()?;
+                Ok(true)
+            }
+        }
+    }
+}
+
+/// ssh登录信息，有私钥时优先使用私钥，失败后使用密码
+#[derive(Debug, Clone, Default)]
+pub struct Credential {
+    pub password: String,
+    pub private_key: Option<PathBuf>,
+    /// 主机公钥文件和订单号，为空时不校验
+    pub known_hosts: Option<(PathBuf, u32)>,
+}
+
+impl Credential {
+    pub fn for_order(order: &Order, password: &str, config: &config::Ssh) -> Credential {
+        Credential {
+            password: password.to_string(),
+            private_key: config
+                .key_dir
+                .as_ref()
+                .map(|key_dir| Ssh::key_path(key_dir, order.server_id))
+                .filter(|path| path.exists()),
+            known_hosts: if config.known_hosts.is_empty() {
+                None
+            } else {
+                Some((PathBuf::from(&config.known_hosts), order.order_id))
+            },
+        }
+    }
+}
+
+/// 远程命令的输出和退出码
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExecOutput {
+    pub stdout: String,
+    pub stderr: String,
+    pub status: i32,
+}
+
 pub struct Ssh {}
 
 impl Ssh {
+    pub async fn get_config() -> config::Ssh {
+        let config = Arc::clone(&CONFIG);
+        let locked = config.lock().await;
+        locked.ssh.clone()
+    }
+
+    /// 并发检测所有订单的挖矿进程，返回进程中的钱包地址和失败的订单
     pub async fn try_run_command_remote(
         orders: &Vec<Order>,
-    ) -> (HashMap<String, Deployed>, Vec<u32>) {
-        let config = Clore::get_config().await;
+    ) -> (HashMap<String, Deployed>, Vec<(u32, SshError)>) {
+        let ssh_passwd = Clore::get_config().await.ssh_passwd;
+        let timeouts = Ssh::get_config().await;
+        let results = futures::stream::iter(orders.iter().cloned())
+            .map(|order| {
+                let credential = Credential::for_order(&order, &ssh_passwd, &timeouts);
+                let timeouts = timeouts.clone();
+                async move {
+                    let result = Ssh::probe(&order, &credential, &timeouts).await;
+                    (order, result)
+                }
+            })
+            .buffer_unordered(timeouts.concurrency.max(1))
+            .collect::<Vec<_>>()
+            .await;
+
         let mut address = HashMap::<String, Deployed>::new();
         let mut errors = Vec::new();
-        for order in orders.iter() {
-            let domain = order.get_ssh_host();
-            let port = order.get_map_ssh_port();
-
-            if domain.is_none() || port.is_none() {
-                errors.push(order.order_id);
-                warn!("server_id:{}无法进行远程链接", order.server_id);
-                continue;
-            }
-
-            let sshaddr = domain.unwrap();
-            let sshport = port.unwrap();
-            info!(
-                "远程测试中:server_id:{},order_id:{},{}:{}",
-                order.server_id, order.order_id, sshaddr, sshport
-            );
-            let result = Ssh::get_remote_ip(sshaddr.clone(), sshport).await;
-            if result.is_ok() {
-                let result = Ssh::exec_to_remote(
-                    config.ssh_passwd.clone(),
-                    result.unwrap(),
-                    "ps -aeo command |grep execute.py |grep -v grep",
-                );
-                if let Ok(deployed_addr) = result {
+        for (order, result) in results {
+            match result {
+                Ok(deployed_addr) => {
                     for addr in deployed_addr.iter() {
                         address.insert(
                             addr.clone(),
                             Deployed::DEPLOYED {
                                 orderid: order.order_id,
                                 serverid: order.server_id,
-                                sshaddr: Some(sshaddr.clone()),
-                                sshport: Some(sshport),
+                                sshaddr: order.get_ssh_host(),
+                                sshport: order.get_map_ssh_port(),
                             },
                         );
                     }
-                } else {
-                    errors.push(order.order_id);
-                    warn!("ssh远程操作失败:{:?}", result)
                 }
-            } else {
-                errors.push(order.order_id);
-                warn!("ssh远程操作失败:{:?}", result)
+                Err(e) => {
+                    warn!(
+                        "ssh远程操作失败:server_id:{},order_id:{},{}",
+                        order.server_id, order.order_id, e
+                    );
+                    errors.push((order.order_id, e));
+                }
             }
         }
         info!("远端总在跑的地址:");
@@ -119,51 +307,370 @@ impl Ssh {
         (address, errors)
     }
 
-    pub fn exec_to_remote(
-        ssh_passwd: String,
-        socket_addr: SocketAddr,
-        ssh_command: &str,
-    ) -> Result<Vec<String>, String> {
+    /// 检测一个订单，返回远程挖矿进程中的钱包地址
+    pub async fn probe(
+        order: &Order,
+        credential: &Credential,
+        timeouts: &config::Ssh,
+    ) -> Result<Vec<String>, SshError> {
+        info!(
+            "远程测试中:server_id:{},order_id:{}",
+            order.server_id, order.order_id
+        );
+        let output = Ssh::run_with_timeouts(order, credential, PROCESS_COMMAND, timeouts).await?;
+        info!("ssh运行结果:\n{}{}", output.stdout, output.stderr);
+        let address = Ssh::parse_address(&output.stdout);
+        info!("解析远程地址:{:?}", address);
+        if address.is_empty() {
+            Err(SshError::NOPROCESS)
+        } else {
+            Ok(address)
+        }
+    }
+
+    /// 从进程列表中解析钱包地址
+    pub fn parse_address(output: &str) -> Vec<String> {
         let mut address = Vec::new();
-        info!("链接远程:{},运行命令:{}", socket_addr, ssh_command);
-        let tcp = TcpStream::connect(socket_addr).map_err(|e| e.to_string())?;
+        let reg: regex::Regex = regex::Regex::new(r"(nimble[\w]+)").unwrap();
+        for row in output.split("\n") {
+            if let Some(captures) = reg.captures(row) {
+                let (_, [addr]) = captures.extract::<1>();
+                let addr = addr.trim().to_string();
+                if !addr.is_empty() {
+                    address.push(addr);
+                }
+            };
+        }
+        address
+    }
 
-        let mut sess = Session::new().map_err(|e| e.to_string())?;
+    /// 建立ssh链接：tcp、握手、校验主机公钥、认证。阻塞调用，每个阶段单独超时
+    pub fn connect(
+        credential: &Credential,
+        socket_addr: SocketAddr,
+        timeouts: &config::Ssh,
+    ) -> Result<Session, SshError> {
+        let tcp =
+            TcpStream::connect_timeout(&socket_addr, Duration::from_secs(timeouts.connect_timeout))
+                .map_err(|e| SshError::from_io(Phase::TCP, e))?;
+        // libssh2的超时之外，socket读写也设置超时兜底
+        let backstop = Duration::from_secs(
+            timeouts
+                .handshake_timeout
+                .max(timeouts.auth_timeout)
+                .max(timeouts.exec_timeout)
+                .max(1),
+        );
+        let _ = tcp.set_read_timeout(Some(backstop));
+        let _ = tcp.set_write_timeout(Some(backstop));
+
+        let mut sess = Session::new().map_err(|e| SshError::from_ssh2(Phase::HANDSHAKE, e))?;
         sess.set_tcp_stream(tcp);
-        sess.handshake().map_err(|e| e.to_string())?;
-        sess.userauth_password("root", &ssh_passwd)
-            .map_err(|e| e.to_string())?;
+        sess.set_timeout((timeouts.handshake_timeout * 1000) as u32);
+        sess.handshake()
+            .map_err(|e| SshError::from_ssh2(Phase::HANDSHAKE, e))?;
+        if let Some((path, order_id)) = &credential.known_hosts {
+            let fingerprint = sess
+                .host_key_hash(ssh2::HashType::Sha256)
+                .map(Ssh::fingerprint)
+                .ok_or(SshError::HANDSHAKE("没有主机公钥".to_string()))?;
+            let _locked = HOST_KEYS_LOCK.lock();
+            if HostKeys::load(path)
+                .pin(*order_id, &fingerprint)
+                .map_err(SshError::HOSTKEY)?
+            {
+                info!("记录主机公钥:order_id:{},{}", order_id, fingerprint);
+            }
+        }
 
-        let mut channel = sess.channel_session().map_err(|e| e.to_string())?;
-        channel.exec(ssh_command).map_err(|e| e.to_string())?;
-        let mut output = String::new();
+        sess.set_timeout((timeouts.auth_timeout * 1000) as u32);
+        let mut authenticated = false;
+        if let Some(private_key) = &credential.private_key {
+            match sess.userauth_pubkey_file("root", None, private_key, None) {
+                Ok(_) => authenticated = true,
+                Err(e) if e.code() == ssh2::ErrorCode::Session(LIBSSH2_ERROR_TIMEOUT) => {
+                    return Err(SshError::TIMEOUT(Phase::AUTH))
+                }
+                Err(e) => warn!("私钥认证失败,使用密码:{},{}", socket_addr, e),
+            }
+        }
+        if !authenticated {
+            sess.userauth_password("root", &credential.password)
+                .map_err(|e| SshError::from_ssh2(Phase::AUTH, e))?;
+        }
+        Ok(sess)
+    }
 
-        let result = channel
-            .read_to_string(&mut output)
-            .map_err(|e| e.to_string());
+    /// 远程执行命令，返回stdout、stderr以及退出码
+    pub fn run(
+        credential: &Credential,
+        socket_addr: SocketAddr,
+        ssh_command: &str,
+        timeouts: &config::Ssh,
+    ) -> Result<ExecOutput, SshError> {
+        info!("链接远程:{},运行命令:{}", socket_addr, ssh_command);
+        let sess = Ssh::connect(credential, socket_addr, timeouts)?;
+        sess.set_timeout((timeouts.exec_timeout * 1000) as u32);
+        let mut channel = sess
+            .channel_session()
+            .map_err(|e| SshError::from_ssh2(Phase::EXEC, e))?;
+        channel
+            .exec(ssh_command)
+            .map_err(|e| SshError::from_ssh2(Phase::EXEC, e))?;
+        // 交替读取stdout和stderr，避免stderr写满窗口后远程命令阻塞
+        sess.set_blocking(false);
+        let deadline = Instant::now() + Duration::from_secs(timeouts.exec_timeout);
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        let result = loop {
+            // 先判断eof，eof之前到达的数据都已在缓冲区中
+            let eof = channel.eof();
+            let read = Ssh::read_available(&mut channel.stream(0), &mut stdout).and_then(|read| {
+                Ok(Ssh::read_available(&mut channel.stderr(), &mut stderr)? || read)
+            });
+            match read {
+                Err(e) => break Err(SshError::from_io(Phase::EXEC, e)),
+                Ok(true) => {}
+                Ok(false) if eof => break Ok(()),
+                Ok(false) if Instant::now() >= deadline => {
+                    break Err(SshError::TIMEOUT(Phase::EXEC))
+                }
+                Ok(false) => std::thread::sleep(Duration::from_millis(20)),
+            }
+        };
+        sess.set_blocking(true);
+        let stdout = String::from_utf8_lossy(&stdout).to_string();
+        let stderr = String::from_utf8_lossy(&stderr).to_string();
         let _ = channel.wait_close();
-        if result.is_ok() {
-            info!("ssh运行结果:\n{}", output);
-            let reg: regex::Regex = regex::Regex::new(r"(nimble[\w]+)").unwrap();
-            for row in output.split("\n").into_iter() {
-                if let Some(captures) = reg.captures(row) {
-                    let (_, [addr]) = captures.extract::<1>();
-                    let addr = addr.trim().to_string();
-                    if !addr.is_empty() {
-                        address.push(addr);
-                    }
-                };
+        if let Err(e) = result {
+            warn!("远程执行ssh读取失败:{},{}", socket_addr, e);
+            return Err(e);
+        }
+        let status = channel
+            .exit_status()
+            .map_err(|e| SshError::from_ssh2(Phase::EXEC, e))?;
+        Ok(ExecOutput {
+            stdout,
+            stderr,
+            status,
+        })
+    }
+
+    /// 非阻塞读取已经到达的数据，返回是否读到数据
+    fn read_available(stream: &mut impl Read, output: &mut Vec<u8>) -> std::io::Result<bool> {
+        let mut buf = [0u8; 8192];
+        match stream.read(&mut buf) {
+            Ok(size) => {
+                output.extend_from_slice(&buf[..size]);
+                Ok(size > 0)
             }
-            info!("解析远程地址:{:?}", address);
-            if address.is_empty() {
-                Err("远程进程无结果".to_string())
-            } else {
-                Ok(address)
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// 通过sftp上传文件，覆盖远程已有文件并创建上级目录
+    pub fn upload(
+        credential: &Credential,
+        socket_addr: SocketAddr,
+        files: &[config::ProvisionFile],
+        timeouts: &config::Ssh,
+    ) -> Result<(), SshError> {
+        info!("链接远程:{},上传文件:{}", socket_addr, files.len());
+        let sess = Ssh::connect(credential, socket_addr, timeouts)?;
+        sess.set_timeout((timeouts.exec_timeout * 1000) as u32);
+        let sftp = sess
+            .sftp()
+            .map_err(|e| SshError::from_ssh2(Phase::SFTP, e))?;
+        for file in files.iter() {
+            let content = std::fs::read(&file.local)
+                .map_err(|e| SshError::SFTP(format!("{}:{}", file.local, e)))?;
+            let remote = Path::new(&file.remote);
+            let mut dir = PathBuf::new();
+            for component in remote.parent().into_iter().flat_map(|parent| parent.iter()) {
+                dir.push(component);
+                if sftp.stat(&dir).is_err() {
+                    sftp.mkdir(&dir, 0o755)
+                        .map_err(|e| SshError::from_ssh2(Phase::SFTP, e))?;
+                }
             }
-        } else {
-            let e = format!("远程执行ssh读取失败{:?}", result);
-            warn!(e);
-            Err(e)
+            let mut remote_file = sftp
+                .open_mode(
+                    remote,
+                    ssh2::OpenFlags::WRITE | ssh2::OpenFlags::CREATE | ssh2::OpenFlags::TRUNCATE,
+                    file.mode,
+                    ssh2::OpenType::File,
+                )
+                .map_err(|e| SshError::from_ssh2(Phase::SFTP, e))?;
+            std::io::Write::write_all(&mut remote_file, &content)
+                .map_err(|e| SshError::from_io(Phase::SFTP, e))?;
+            drop(remote_file);
+            // 文件已存在时open_mode不会修改权限
+            sftp.setstat(
+                remote,
+                ssh2::FileStat {
+                    size: None,
+                    uid: None,
+                    gid: None,
+                    perm: Some(file.mode as u32),
+                    atime: None,
+                    mtime: None,
+                },
+            )
+            .map_err(|e| SshError::from_ssh2(Phase::SFTP, e))?;
+            info!("已上传:{},{}->{}", socket_addr, file.local, file.remote);
+        }
+        Ok(())
+    }
+
+    /// 在阻塞线程中执行，总时长超过各阶段超时之和时放弃等待，错误记在phase阶段
+    pub async fn blocking<T: Send + 'static>(
+        phase: Phase,
+        timeouts: &config::Ssh,
+        task: impl FnOnce(&config::Ssh) -> Result<T, SshError> + Send + 'static,
+    ) -> Result<T, SshError> {
+        let total = Duration::from_secs(
+            timeouts.connect_timeout
+                + timeouts.handshake_timeout
+                + timeouts.auth_timeout
+                + timeouts.exec_timeout,
+        );
+        let config = timeouts.clone();
+        let task = tokio::task::spawn_blocking(move || task(&config));
+        match tokio::time::timeout(total, task).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(e)) => Err(SshError::new(phase, e.to_string())),
+            Err(_) => Err(SshError::TIMEOUT(phase)),
+        }
+    }
+
+    pub async fn run_blocking(
+        credential: &Credential,
+        socket_addr: SocketAddr,
+        ssh_command: &str,
+        timeouts: &config::Ssh,
+    ) -> Result<ExecOutput, SshError> {
+        let credential = credential.clone();
+        let ssh_command = ssh_command.to_string();
+        Ssh::blocking(Phase::EXEC, timeouts, move |config| {
+            Ssh::run(&credential, socket_addr, &ssh_command, config)
+        })
+        .await
+    }
+
+    /// 解析订单的ssh地址
+    pub async fn resolve(order: &Order, timeouts: &config::Ssh) -> Result<SocketAddr, SshError> {
+        let (Some(sshaddr), Some(sshport)) = (order.get_ssh_host(), order.get_map_ssh_port())
+        else {
+            return Err(SshError::NOADDRESS);
+        };
+        tokio::time::timeout(
+            Duration::from_secs(timeouts.dns_timeout),
+            Ssh::get_remote_ip(sshaddr, sshport),
+        )
+        .await
+        .map_err(|_| SshError::TIMEOUT(Phase::DNS))?
+        .map_err(SshError::DNS)
+    }
+
+    fn notify_host_key<T>(order: &Order, result: &Result<T, SshError>) {
+        if let Err(SshError::HOSTKEY(e)) = result {
+            alert::notify(Alert::new(
+                format!("host_key:{}", order.order_id),
+                Severity::CRITICAL,
+                "主机公钥变化",
+                format!("serverid:{},{}", order.server_id, e),
+            ));
+        }
+    }
+
+    /// 解析订单的ssh地址，并远程执行命令
+    pub async fn run_with_timeouts(
+        order: &Order,
+        credential: &Credential,
+        ssh_command: &str,
+        timeouts: &config::Ssh,
+    ) -> Result<ExecOutput, SshError> {
+        let socket_addr = Ssh::resolve(order, timeouts).await?;
+        let result = Ssh::run_blocking(credential, socket_addr, ssh_command, timeouts).await;
+        Ssh::notify_host_key(order, &result);
+        result
+    }
+
+    /// 解析订单的ssh地址，并上传文件
+    pub async fn upload_to_order(
+        order: &Order,
+        credential: &Credential,
+        files: &[config::ProvisionFile],
+        timeouts: &config::Ssh,
+    ) -> Result<(), SshError> {
+        let socket_addr = Ssh::resolve(order, timeouts).await?;
+        let credential = credential.clone();
+        let files = files.to_vec();
+        let result = Ssh::blocking(Phase::SFTP, timeouts, move |config| {
+            Ssh::upload(&credential, socket_addr, &files, config)
+        })
+        .await;
+        Ssh::notify_host_key(order, &result);
+        result
+    }
+
+    /// 使用配置的密码和超时远程执行命令
+    pub async fn run_on_order(order: &Order, ssh_command: &str) -> Result<ExecOutput, SshError> {
+        let ssh_passwd = Clore::get_config().await.ssh_passwd;
+        let timeouts = Ssh::get_config().await;
+        let credential = Credential::for_order(order, &ssh_passwd, &timeouts);
+        Ssh::run_with_timeouts(order, &credential, ssh_command, &timeouts).await
+    }
+
+    fn fingerprint(hash: &[u8]) -> String {
+        let hex = hash
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>();
+        format!("SHA256:{}", hex)
+    }
+
+    /// 服务器的私钥路径，公钥为同名.pub
+    pub fn key_path(key_dir: &str, server_id: u32) -> PathBuf {
+        Path::new(key_dir).join(format!("clore_{}", server_id))
+    }
+
+    /// 下单前为服务器重新生成ed25519密钥对，返回公钥
+    pub fn generate_keypair(key_dir: &str, server_id: u32) -> Result<String, String> {
+        std::fs::create_dir_all(key_dir).map_err(|e| format!("{}:{}", key_dir, e))?;
+        let path = Ssh::key_path(key_dir, server_id);
+        let public_path = path.with_extension("pub");
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&public_path);
+        let output = std::process::Command::new("ssh-keygen")
+            .args(["-q", "-t", "ed25519", "-N", ""])
+            .arg("-C")
+            .arg(format!("clore-{}", server_id))
+            .arg("-f")
+            .arg(&path)
+            .output()
+            .map_err(|e| format!("ssh-keygen:{}", e))?;
+        if !output.status.success() {
+            return Err(format!(
+                "ssh-keygen:{}",
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+        std::fs::read_to_string(&public_path)
+            .map(|key| key.trim().to_string())
+            .map_err(|e| format!("{:?}:{}", public_path, e))
+    }
+
+    /// 启动命令中写入authorized_keys，公钥由环境变量SSH_PUBLIC_KEY传入，保留首行的#!
+    pub fn authorize_key_command(command: &str) -> String {
+        let authorize = "mkdir -p ~/.ssh && chmod 700 ~/.ssh && echo \"$SSH_PUBLIC_KEY\" >> ~/.ssh/authorized_keys && chmod 600 ~/.ssh/authorized_keys\n";
+        let command = command.trim_start_matches('\n');
+        match command.split_once('\n') {
+            Some((shebang, rest)) if shebang.starts_with("#!") => {
+                format!("{}\n{}{}", shebang, authorize, rest)
+            }
+            _ => format!("{}{}", authorize, command),
         }
     }
 
@@ -0,0 +1,276 @@
+use std::{path::Path, sync::Arc};
+
+use chrono::{DateTime, Local};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sqlite::{ConnectionThreadSafe, State, Value};
+use tokio::sync::Mutex;
+use tracing::{error, info};
+
+use crate::{config::CONFIG, log::rule::LogEvent};
+
+lazy_static! {
+    pub static ref LOG_STORE: Arc<Mutex<LogStore>> = Arc::new(Mutex::new(LogStore::default()));
+    static ref IDENTIFIER: Regex = Regex::new(r"^[0-9A-Za-z_-]{1,100}$").unwrap();
+}
+
+/// 监控端上报的一条日志
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogRecord {
+    pub id: i64,
+    pub server_id: u32,
+    pub address: String,
+    pub kind: String,
+    pub timestamp: i64,
+    pub body: String,
+}
+
+impl std::fmt::Display for LogRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let time = DateTime::from_timestamp(self.timestamp, 0)
+            .map(|time| {
+                time.with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M:%S")
+                    .to_string()
+            })
+            .unwrap_or_default();
+        write!(
+            f,
+            "{} [{}] {} {} {}",
+            time, self.server_id, self.address, self.kind, self.body
+        )
+    }
+}
+
+/// 查询条件，时间为unix时间戳
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LogQuery {
+    pub server_id: Option<u32>,
+    pub address: Option<String>,
+    pub kind: Option<String>,
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    pub limit: Option<usize>,
+}
+
+impl LogQuery {
+    /// 实时推送时按server_id、地址和类型过滤
+    pub fn matches(&self, record: &LogRecord) -> bool {
+        self.server_id
+            .is_none_or(|server_id| server_id == record.server_id)
+            && self
+                .address
+                .as_ref()
+                .is_none_or(|address| *address == record.address)
+            && self.kind.as_ref().is_none_or(|kind| *kind == record.kind)
+    }
+}
+
+/// 地址、文件名等路径参数只允许字母数字和-_
+pub fn validate_identifier(identifier: &str) -> Result<&str, String> {
+    if IDENTIFIER.is_match(identifier) {
+        Ok(identifier)
+    } else {
+        Err(format!("非法的标识:{:?}", identifier))
+    }
+}
+
+pub struct LogStore {
+    connection: ConnectionThreadSafe,
+}
+
+impl Default for LogStore {
+    fn default() -> Self {
+        let path = std::env::current_dir().unwrap().join("server_logs.db");
+        LogStore::open(path).unwrap()
+    }
+}
+
+impl LogStore {
+    pub fn open(path: impl AsRef<Path>) -> Result<LogStore, String> {
+        let connection = sqlite::Connection::open_thread_safe(path).map_err(|e| e.to_string())?;
+        connection
+            .execute(
+                "
+                CREATE TABLE IF NOT EXISTS logs (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    server_id INTEGER NOT NULL,
+                    address TEXT NOT NULL,
+                    kind TEXT NOT NULL,
+                    timestamp INTEGER NOT NULL,
+                    body TEXT NOT NULL
+                );
+                CREATE INDEX IF NOT EXISTS logs_server_time ON logs (server_id, timestamp);
+                CREATE INDEX IF NOT EXISTS logs_address_time ON logs (address, timestamp);
+                ",
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(LogStore { connection })
+    }
+
+    pub async fn get_retention_days() -> u32 {
+        let mutex_conf = Arc::clone(&CONFIG);
+        let config = &mutex_conf.lock().await;
+        config.store.retention_days
+    }
+
+    /// 保存上报的日志，每行一条记录，能解析为事件的使用事件的类型和时间
+    pub fn ingest(
+        &self,
+        server_id: u32,
+        address: &str,
+        body: &str,
+        now: DateTime<Local>,
+    ) -> Result<Vec<LogRecord>, String> {
+        let address = validate_identifier(address)?;
+        // 一次上报在同一个事务中写入，失败时整批回滚
+        self.connection
+            .execute("BEGIN")
+            .map_err(|e| e.to_string())?;
+        let result = body
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                let (kind, timestamp) = match serde_json::from_str::<LogEvent>(line) {
+                    Ok(event) => (event.kind.to_string(), event.time.timestamp()),
+                    Err(_) => ("RAW".to_string(), now.timestamp()),
+                };
+                self.insert(server_id, address, &kind, timestamp, line)
+            })
+            .collect::<Result<Vec<LogRecord>, String>>()
+            .and_then(|records| {
+                self.connection
+                    .execute("COMMIT")
+                    .map(|_| records)
+                    .map_err(|e| e.to_string())
+            });
+        if result.is_err() {
+            let _ = self.connection.execute("ROLLBACK");
+        }
+        result
+    }
+
+    pub fn insert(
+        &self,
+        server_id: u32,
+        address: &str,
+        kind: &str,
+        timestamp: i64,
+        body: &str,
+    ) -> Result<LogRecord, String> {
+        let mut statement = self
+            .connection
+            .prepare(
+                "INSERT INTO logs (server_id, address, kind, timestamp, body) VALUES (?, ?, ?, ?, ?)",
+            )
+            .map_err(|e| e.to_string())?;
+        statement
+            .bind::<&[Value]>(
+                &[
+                    (server_id as i64).into(),
+                    address.into(),
+                    kind.into(),
+                    timestamp.into(),
+                    body.into(),
+                ][..],
+            )
+            .map_err(|e| e.to_string())?;
+        statement.next().map_err(|e| e.to_string())?;
+        let mut statement = self
+            .connection
+            .prepare("SELECT last_insert_rowid()")
+            .map_err(|e| e.to_string())?;
+        statement.next().map_err(|e| e.to_string())?;
+        Ok(LogRecord {
+            id: statement.read::<i64, _>(0).map_err(|e| e.to_string())?,
+            server_id,
+            address: address.to_string(),
+            kind: kind.to_string(),
+            timestamp,
+            body: body.to_string(),
+        })
+    }
+
+    /// 按条件查询，返回时间范围内最新的limit条，按时间升序
+    pub fn query(&self, query: &LogQuery) -> Result<Vec<LogRecord>, String> {
+        let mut conditions = Vec::new();
+        let mut values = Vec::<Value>::new();
+        if let Some(server_id) = query.server_id {
+            conditions.push("server_id = ?");
+            values.push((server_id as i64).into());
+        }
+        if let Some(address) = &query.address {
+            conditions.push("address = ?");
+            values.push(validate_identifier(address)?.into());
+        }
+        if let Some(kind) = &query.kind {
+            conditions.push("kind = ?");
+            values.push(kind.as_str().into());
+        }
+        if let Some(since) = query.since {
+            conditions.push("timestamp >= ?");
+            values.push(since.into());
+        }
+        if let Some(until) = query.until {
+            conditions.push("timestamp < ?");
+            values.push(until.into());
+        }
+        let filter = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+        let limit = query.limit.unwrap_or(100).clamp(1, 1000);
+        let sql = format!(
+            "SELECT id, server_id, address, kind, timestamp, body FROM logs {filter}
+             ORDER BY timestamp DESC, id DESC LIMIT {limit}"
+        );
+        let mut statement = self.connection.prepare(sql).map_err(|e| e.to_string())?;
+        statement
+            .bind::<&[Value]>(&values[..])
+            .map_err(|e| e.to_string())?;
+        let mut records = Vec::new();
+        while let State::Row = statement.next().map_err(|e| e.to_string())? {
+            records.push(LogRecord {
+                id: statement.read::<i64, _>(0).map_err(|e| e.to_string())?,
+                server_id: statement.read::<i64, _>(1).map_err(|e| e.to_string())? as u32,
+                address: statement.read::<String, _>(2).map_err(|e| e.to_string())?,
+                kind: statement.read::<String, _>(3).map_err(|e| e.to_string())?,
+                timestamp: statement.read::<i64, _>(4).map_err(|e| e.to_string())?,
+                body: statement.read::<String, _>(5).map_err(|e| e.to_string())?,
+            });
+        }
+        records.reverse();
+        Ok(records)
+    }
+
+    /// 删除before之前的日志，返回删除的条数
+    pub fn prune(&self, before: i64) -> Result<usize, String> {
+        let mut statement = self
+            .connection
+            .prepare("DELETE FROM logs WHERE timestamp < ?")
+            .map_err(|e| e.to_string())?;
+        statement.bind((1, before)).map_err(|e| e.to_string())?;
+        statement.next().map_err(|e| e.to_string())?;
+        Ok(self.connection.change_count())
+    }
+}
+
+/// 每小时按保留天数清理一次日志
+pub async fn retention() {
+    loop {
+        let days = LogStore::get_retention_days().await;
+        let before = (Local::now() - chrono::Duration::days(days as i64)).timestamp();
+        let store = Arc::clone(&LOG_STORE);
+        let locked = store.lock().await;
+        match locked.prune(before) {
+            Ok(count) if count > 0 => info!("清理{}天前的日志:{}条", days, count),
+            Ok(_) => {}
+            Err(e) => error!("清理日志失败:{}", e),
+        }
+        drop(locked);
+        tokio::time::sleep(std::time::Duration::from_secs(3600)).await;
+    }
+}
@@ -0,0 +1,198 @@
+use std::{collections::VecDeque, sync::Arc};
+
+use actix_web::web::Bytes;
+use chrono::Local;
+use futures::Stream;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::broadcast::{self, error::RecvError, Receiver, Sender},
+    time::{interval, Duration, Interval, MissedTickBehavior},
+};
+
+use super::store::{LogQuery, LogRecord, LOG_STORE};
+
+lazy_static! {
+    /// 新保存的日志，推送给实时订阅的连接
+    pub static ref LOG_STREAM: Sender<LogRecord> = broadcast::channel(1024).0;
+}
+
+/// 实时日志订阅条件，连接时先回放最近replay条
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StreamQuery {
+    pub server_id: Option<u32>,
+    pub address: Option<String>,
+    pub kind: Option<String>,
+    pub replay: Option<usize>,
+}
+
+impl StreamQuery {
+    fn filter(&self) -> LogQuery {
+        LogQuery {
+            server_id: self.server_id,
+            address: self.address.clone(),
+            kind: self.kind.clone(),
+            limit: self.replay,
+            ..Default::default()
+        }
+    }
+}
+
+pub fn publish(records: &[LogRecord]) {
+    for record in records.iter() {
+        // 没有订阅者时发送失败，忽略
+        let _ = LOG_STREAM.send(record.clone());
+    }
+}
+
+/// Server-Sent Events格式，浏览器EventSource和命令行follow都可以读取
+pub fn to_sse(record: &LogRecord) -> String {
+    format!(
+        "id: {}\nevent: log\ndata: {}\n\n",
+        record.id,
+        serde_json::to_string(record).unwrap_or_default()
+    )
+}
+
+pub fn heartbeat() -> String {
+    format!("event: heartbeat\ndata: {}\n\n", Local::now().timestamp())
+}
+
+struct Subscription {
+    filter: LogQuery,
+    replay: VecDeque<LogRecord>,
+    last_id: i64,
+    receiver: Receiver<LogRecord>,
+    heartbeat: Interval,
+}
+
+impl Subscription {
+    async fn next(&mut self) -> Option<String> {
+        if let Some(record) = self.replay.pop_front() {
+            return Some(to_sse(&record));
+        }
+        loop {
+            tokio::select! {
+                result = self.receiver.recv() => match result {
+                    Ok(record) if record.id > self.last_id && self.filter.matches(&record) => {
+                        return Some(to_sse(&record));
+                    }
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(count)) => {
+                        return Some(format!("event: lagged\ndata: {}\n\n", count));
+                    }
+                    Err(RecvError::Closed) => return None,
+                },
+                _ = self.heartbeat.tick() => return Some(heartbeat()),
+            }
+        }
+    }
+}
+
+/// 先订阅再查询回放，回放过的记录不会重复推送
+pub async fn subscribe(
+    query: &StreamQuery,
+) -> Result<impl Stream<Item = Result<Bytes, actix_web::Error>>, String> {
+    let receiver = LOG_STREAM.subscribe();
+    let filter = query.filter();
+    let replay = match query.replay {
+        Some(replay) if replay > 0 => {
+            let store = Arc::clone(&LOG_STORE);
+            let locked = store.lock().await;
+            locked.query(&filter)?
+        }
+        _ => Vec::new(),
+    };
+    let last_id = replay.iter().map(|record| record.id).max().unwrap_or(0);
+    let mut heartbeat = interval(Duration::from_secs(15));
+    heartbeat.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    heartbeat.reset();
+    let subscription = Subscription {
+        filter,
+        replay: replay.into(),
+        last_id,
+        receiver,
+        heartbeat,
+    };
+    Ok(futures::stream::unfold(
+        subscription,
+        |mut subscription| async move {
+            let event = subscription.next().await?;
+            Some((Ok(Bytes::from(event)), subscription))
+        },
+    ))
+}
+
+/// 解析Server-Sent Events，返回(event, data)
+#[derive(Debug, Default)]
+pub struct SseParser {
+    buffer: Vec<u8>,
+}
+
+impl SseParser {
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<(String, String)> {
+        // 按字节缓存，避免多字节字符被拆到两个chunk中
+        self.buffer.extend_from_slice(chunk);
+        let mut events = Vec::new();
+        while let Some(position) = self.buffer.windows(2).position(|w| w == b"\n\n") {
+            let block = self.buffer.drain(..position + 2).collect::<Vec<u8>>();
+            let block = String::from_utf8_lossy(&block);
+            let mut event = "message".to_string();
+            let mut data = Vec::new();
+            for line in block.lines() {
+                if let Some(value) = line.strip_prefix("event:") {
+                    event = value.trim().to_string();
+                } else if let Some(value) = line.strip_prefix("data:") {
+                    data.push(value.trim_start().to_string());
+                }
+            }
+            if !data.is_empty() {
+                events.push((event, data.join("\n")));
+            }
+        }
+        events
+    }
+}
+
+/// 命令行follow，持续打印服务端推送的日志
+pub async fn follow(server: &str, token: &str, query: &StreamQuery) -> Result<(), String> {
+    let mut params = Vec::new();
+    if let Some(server_id) = query.server_id {
+        params.push(format!("server_id={}", server_id));
+    }
+    if let Some(address) = &query.address {
+        params.push(format!("address={}", address));
+    }
+    if let Some(kind) = &query.kind {
+        params.push(format!("kind={}", kind));
+    }
+    if let Some(replay) = query.replay {
+        params.push(format!("replay={}", replay));
+    }
+    let url = format!(
+        "{}/logs/stream?{}",
+        server.trim_end_matches('/'),
+        params.join("&")
+    );
+    let mut response = reqwest::Client::new()
+        .get(url)
+        .bearer_auth(token)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.to_string())?;
+    let mut parser = SseParser::default();
+    while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+        for (event, data) in parser.feed(&chunk) {
+            match event.as_str() {
+                "log" => match serde_json::from_str::<LogRecord>(&data) {
+                    Ok(record) => println!("{}", record),
+                    Err(_) => println!("{}", data),
+                },
+                "lagged" => eprintln!("跳过了{}条日志", data),
+                _ => {}
+            }
+        }
+    }
+    Err("服务端已断开".to_string())
+}
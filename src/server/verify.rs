@@ -0,0 +1,395 @@
+use std::{collections::HashMap, sync::Arc};
+
+use chrono::{DateTime, Local};
+use lazy_static::lazy_static;
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+
+use crate::{
+    alert::{self, Alert, Severity},
+    config::{self, CONFIG},
+};
+
+use super::{
+    address::{count_cancellation, Address, Deployed, WALLETS_STATE},
+    clore::{
+        model::{gpu, market::Specs, my_orders::Order, CardType},
+        Clore,
+    },
+    ssh::{Ssh, SshError},
+};
+
+/// 拉黑原因
+pub const SPEC_MISMATCH: &str = "spec mismatch";
+
+lazy_static! {
+    /// 订单的检查结果，重启后重新检查
+    static ref VERIFICATIONS: Arc<Mutex<HashMap<u32, VerifyState>>> =
+        Arc::new(Mutex::new(HashMap::new()));
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerifyState {
+    RUNNING,
+    PASSED,
+    /// 硬件不一致，订单已取消
+    MISMATCH,
+    /// ssh不可用、命令失败或取消失败的时间，间隔后重试
+    FAILED(DateTime<Local>),
+}
+
+impl VerifyState {
+    pub fn needs_run(
+        state: Option<&VerifyState>,
+        retry_interval: u64,
+        now: DateTime<Local>,
+    ) -> bool {
+        match state {
+            None => true,
+            Some(VerifyState::FAILED(time)) => (now - *time).num_seconds() >= retry_interval as i64,
+            Some(_) => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gpu {
+    pub name: String,
+    /// 显存(MiB)
+    pub memory_total: Option<f64>,
+}
+
+/// 服务器上读取到的硬件，读取不到的项为None
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Hardware {
+    pub gpus: Vec<Gpu>,
+    pub cpu_threads: Option<u32>,
+    /// 内存(GiB)
+    pub ram: Option<f64>,
+    /// 根目录硬盘(GiB)
+    pub disk: Option<f64>,
+}
+
+impl Hardware {
+    pub const COMMAND: &'static str =
+        "nvidia-smi --query-gpu=name,memory.total --format=csv,noheader,nounits; \
+         echo '== cpus'; nproc; \
+         echo '== ram'; grep MemTotal /proc/meminfo; \
+         echo '== disk'; df -Pk / | tail -n 1";
+
+    pub fn parse(text: &str) -> Hardware {
+        let mut hardware = Hardware::default();
+        let mut section = "gpus";
+        for line in text.lines().map(|line| line.trim()) {
+            if let Some(name) = line.strip_prefix("== ") {
+                section = name;
+                continue;
+            }
+            if line.is_empty() {
+                continue;
+            }
+            match section {
+                "gpus" => {
+                    if let Some((name, memory)) = line.rsplit_once(',') {
+                        hardware.gpus.push(Gpu {
+                            name: name.trim().to_string(),
+                            memory_total: memory.trim().parse::<f64>().ok(),
+                        });
+                    }
+                }
+                "cpus" => hardware.cpu_threads = line.parse::<u32>().ok(),
+                "ram" => {
+                    // MemTotal:       65755468 kB
+                    hardware.ram = line
+                        .split_whitespace()
+                        .nth(1)
+                        .and_then(|kb| kb.parse::<f64>().ok())
+                        .map(|kb| kb / 1024f64 / 1024f64);
+                }
+                "disk" => {
+                    // overlay 1056763236 8239876 994828092 1% /
+                    hardware.disk = line
+                        .split_whitespace()
+                        .nth(1)
+                        .and_then(|kb| kb.parse::<f64>().ok())
+                        .map(|kb| kb / 1024f64 / 1024f64);
+                }
+                _ => {}
+            }
+        }
+        hardware
+    }
+}
+
+impl std::fmt::Display for Hardware {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let gpus = self
+            .gpus
+            .iter()
+            .map(|gpu| match gpu.memory_total {
+                Some(memory) => format!("{} {:.0}MiB", gpu.name, memory),
+                None => gpu.name.clone(),
+            })
+            .collect::<Vec<String>>();
+        let value = |value: Option<f64>| value.map_or("-".to_string(), |v| format!("{:.1}", v));
+        write!(
+            f,
+            "显卡:[{}],cpu线程:{},内存:{}GiB,硬盘:{}GiB",
+            gpus.join(","),
+            self.cpu_threads.map_or("-".to_string(), |v| v.to_string()),
+            value(self.ram),
+            value(self.disk)
+        )
+    }
+}
+
+/// 和标注不一致的项，expected为标注值
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mismatch {
+    GPUMODEL {
+        expected: CardType,
+        actual: CardType,
+    },
+    GPUCOUNT {
+        expected: u32,
+        actual: u32,
+    },
+    /// GB
+    VRAM {
+        expected: f64,
+        actual: f64,
+    },
+    CPU {
+        expected: u32,
+        actual: u32,
+    },
+    /// GB
+    RAM {
+        expected: f64,
+        actual: f64,
+    },
+    /// GB
+    DISK {
+        expected: f64,
+        actual: f64,
+    },
+}
+
+impl std::fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Mismatch::GPUMODEL { expected, actual } => {
+                write!(f, "显卡型号:标注{},实际{}", expected, actual)
+            }
+            Mismatch::GPUCOUNT { expected, actual } => {
+                write!(f, "显卡数量:标注{},实际{}", expected, actual)
+            }
+            Mismatch::VRAM { expected, actual } => {
+                write!(f, "显存:标注{:.1}GB,实际{:.1}GB", expected, actual)
+            }
+            Mismatch::CPU { expected, actual } => {
+                write!(f, "cpu线程:标注{},实际{}", expected, actual)
+            }
+            Mismatch::RAM { expected, actual } => {
+                write!(f, "内存:标注{:.1}GB,实际{:.1}GB", expected, actual)
+            }
+            Mismatch::DISK { expected, actual } => {
+                write!(f, "硬盘:标注{:.0}GB,实际{:.0}GB", expected, actual)
+            }
+        }
+    }
+}
+
+/// 比较标注和实际硬件，比标注多不算不一致，读取不到的项不检查
+/// 读取不到显卡或无法识别标注的型号时无法检查，返回错误稍后重试
+pub fn check(
+    specs: &Specs,
+    hardware: &Hardware,
+    config: &config::Verify,
+) -> Result<Vec<Mismatch>, String> {
+    if hardware.gpus.is_empty() {
+        return Err("读取不到显卡".to_string());
+    }
+    let mut mismatches = Vec::new();
+    let short = |expected: f64, actual: f64, tolerance: f64| actual < expected * (1f64 - tolerance);
+
+    let expected = specs.get_card_number();
+    let actual = hardware.gpus.len() as u32;
+    if expected != actual {
+        mismatches.push(Mismatch::GPUCOUNT { expected, actual });
+    }
+    let expected = gpu::parse(specs.get_gpu_name());
+    if matches!(expected, CardType::UNKNOWN(_)) {
+        return Err(format!("无法识别标注的显卡型号:{}", specs.get_gpu_name()));
+    }
+    if let Some(actual) = hardware
+        .gpus
+        .iter()
+        .map(|gpu| gpu::parse(&gpu.name))
+        .find(|card_type| *card_type != expected)
+    {
+        mismatches.push(Mismatch::GPUMODEL { expected, actual });
+    }
+    let expected = specs.gpuram as f64;
+    if let Some(actual) = hardware
+        .gpus
+        .iter()
+        .filter_map(|gpu| gpu.memory_total)
+        .map(|memory| memory / 1024f64)
+        .min_by(|a, b| a.total_cmp(b))
+    {
+        if short(expected, actual, config.tolerance) {
+            mismatches.push(Mismatch::VRAM { expected, actual });
+        }
+    }
+    if let (Some(expected), Some(actual)) = (specs.get_cpu_threads(), hardware.cpu_threads) {
+        if short(expected as f64, actual as f64, config.tolerance) {
+            mismatches.push(Mismatch::CPU { expected, actual });
+        }
+    }
+    if let Some(actual) = hardware.ram {
+        if short(specs.ram, actual, config.tolerance) {
+            mismatches.push(Mismatch::RAM {
+                expected: specs.ram,
+                actual,
+            });
+        }
+    }
+    if let (Some(expected), Some(actual)) = (specs.get_disk_size(), hardware.disk) {
+        if short(expected, actual, config.disk_tolerance) {
+            mismatches.push(Mismatch::DISK { expected, actual });
+        }
+    }
+    Ok(mismatches)
+}
+
+/// 读取订单服务器上的硬件
+pub async fn inspect(order: &Order) -> Result<Hardware, SshError> {
+    let output = Ssh::run_on_order(order, Hardware::COMMAND).await?;
+    if output.status != 0 && output.stdout.is_empty() {
+        return Err(SshError::EXEC(format!(
+            "退出码:{},{}",
+            output.status,
+            output.stderr.trim()
+        )));
+    }
+    Ok(Hardware::parse(&output.stdout))
+}
+
+/// 需要检查的订单：有钱包在部署中，ssh端口已映射
+pub fn pending(
+    wallets: &Address,
+    orders: &[Order],
+    states: &HashMap<u32, VerifyState>,
+    retry_interval: u64,
+    now: DateTime<Local>,
+) -> Vec<Order> {
+    let deploying = wallets
+        .values()
+        .filter_map(|wallet| match wallet.deploy {
+            Deployed::DEPLOYING { orderid, .. } if orderid != 0 => Some(orderid),
+            _ => None,
+        })
+        .collect::<Vec<u32>>();
+    orders
+        .iter()
+        .filter(|order| deploying.contains(&order.order_id))
+        .filter(|order| order.get_ssh_host().is_some() && order.get_map_ssh_port().is_some())
+        .filter(|order| VerifyState::needs_run(states.get(&order.order_id), retry_interval, now))
+        .cloned()
+        .collect()
+}
+
+/// 检查已通过，未开启检查时也视为通过
+pub async fn passed(order_id: u32) -> bool {
+    let config = Arc::clone(&CONFIG);
+    if !config.lock().await.verify.enabled {
+        return true;
+    }
+    let verifications = Arc::clone(&VERIFICATIONS);
+    let passed = verifications.lock().await.get(&order_id) == Some(&VerifyState::PASSED);
+    passed
+}
+
+/// 取消订单并低分评价，取消成功后拉黑服务器，重置订单上的钱包
+async fn reject(
+    order: &Order,
+    mismatches: &[Mismatch],
+    config: &config::Verify,
+) -> Result<(), String> {
+    let reasons = mismatches
+        .iter()
+        .map(|mismatch| mismatch.to_string())
+        .collect::<Vec<String>>()
+        .join(";");
+    warn!(
+        "硬件和标注不一致:serverid:{},orderid:{},{}",
+        order.server_id, order.order_id, reasons
+    );
+    Clore::default()
+        .cancel_order_with_rating(order.order_id, config.rating)
+        .await?;
+    Clore::append_block_server_id_with_reason(order.server_id, SPEC_MISMATCH);
+    count_cancellation("spec_mismatch").await;
+    let wallets = Arc::clone(&WALLETS_STATE);
+    let addresses = wallets.lock().await.release_order(order.order_id).await?;
+    alert::notify(Alert::new(
+        format!("verify:{}", order.order_id),
+        Severity::WARNING,
+        "硬件和标注不一致，订单已取消",
+        format!(
+            "serverid:{},orderid:{},{},已拉黑,重置钱包:{:?}",
+            order.server_id, order.order_id, reasons, addresses
+        ),
+    ));
+    Ok(())
+}
+
+async fn verify_order(order: &Order, config: &config::Verify) -> Result<VerifyState, String> {
+    let hardware = inspect(order).await.map_err(|e| e.to_string())?;
+    info!(
+        "硬件:serverid:{},orderid:{},{}",
+        order.server_id, order.order_id, hardware
+    );
+    let mismatches = check(&order.specs, &hardware, config)?;
+    if mismatches.is_empty() {
+        return Ok(VerifyState::PASSED);
+    }
+    reject(order, &mismatches, config).await?;
+    Ok(VerifyState::MISMATCH)
+}
+
+/// 对新订单在后台检查硬件，同一订单只检查一次
+pub async fn schedule(wallets: &Address, orders: &[Order]) {
+    let config = Arc::clone(&CONFIG);
+    let verify = config.lock().await.verify.clone();
+    if !verify.enabled {
+        return;
+    }
+    let verifications = Arc::clone(&VERIFICATIONS);
+    let mut locked = verifications.lock().await;
+    for order in pending(
+        wallets,
+        orders,
+        &locked,
+        verify.retry_interval,
+        Local::now(),
+    ) {
+        locked.insert(order.order_id, VerifyState::RUNNING);
+        let verify = verify.clone();
+        tokio::spawn(async move {
+            let state = match verify_order(&order, &verify).await {
+                Ok(state) => state,
+                Err(e) => {
+                    error!(
+                        "检查硬件失败:serverid:{},orderid:{},{}",
+                        order.server_id, order.order_id, e
+                    );
+                    VerifyState::FAILED(Local::now())
+                }
+            };
+            let verifications = Arc::clone(&VERIFICATIONS);
+            verifications.lock().await.insert(order.order_id, state);
+        });
+    }
+}
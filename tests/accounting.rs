@@ -0,0 +1,75 @@
+pub mod common;
+
+#[cfg(test)]
+mod test {
+    use chrono::{Duration, Local, TimeZone};
+    use monitor::{log::RunLogs, server::accounting::Accounting};
+
+    fn open(name: &str) -> Accounting {
+        let path = crate::common::temp_dir(name).join("accounting.db");
+        Accounting::open(path).unwrap()
+    }
+
+    #[test]
+    fn record_task_test() {
+        crate::common::setup();
+        let accounting = open("accounting_task");
+        let text = std::fs::read_to_string("./example/my_logs.json")
+            .unwrap()
+            .replacen(r#""Status": "Success""#, r#""Status": "Failed""#, 1);
+        let run_logs = text.parse::<RunLogs>().unwrap();
+        for task in run_logs.iter() {
+            assert!(accounting.record_task(25299, task, 2f64).unwrap());
+        }
+        // 重复上报不会重复记账
+        assert!(!accounting.record_task(25299, &run_logs[0], 2f64).unwrap());
+
+        let earnings = accounting.earnings_by_address().unwrap();
+        assert_eq!(1, earnings.len());
+        assert_eq!(
+            "nimble1ha23ka8jzm63eupcrf0thlhhldu4j6z66shqjl",
+            earnings[0].key
+        );
+        assert_eq!(3, earnings[0].tasks);
+        assert_eq!(2, earnings[0].success);
+        assert_eq!(4f64, earnings[0].reward);
+
+        let earnings = accounting.earnings_by_server().unwrap();
+        assert_eq!("25299", earnings[0].key);
+
+        let earnings = accounting.earnings_by_day().unwrap();
+        assert_eq!(
+            vec!["2024-05-31", "2024-06-01"],
+            earnings
+                .iter()
+                .map(|earning| earning.key.as_str())
+                .collect::<Vec<&str>>()
+        );
+        assert_eq!(2, earnings[0].tasks);
+        assert_eq!(2f64, earnings[1].reward);
+    }
+
+    #[test]
+    fn record_balance_test() {
+        crate::common::setup();
+        let accounting = open("accounting_balance");
+        let address = "nimble1ha23ka8jzm63eupcrf0thlhhldu4j6z66shqjl";
+        let time = Local.with_ymd_and_hms(2024, 6, 1, 8, 0, 0).unwrap();
+        assert!(accounting.record_balance(address, 100f64, time).unwrap());
+        assert!(!accounting
+            .record_balance(address, 100f64, time + Duration::minutes(1))
+            .unwrap());
+        assert!(accounting
+            .record_balance(address, 106f64, time + Duration::hours(2))
+            .unwrap());
+        assert!(accounting
+            .record_balance(address, 110f64, time + Duration::days(1))
+            .unwrap());
+
+        let changes = accounting.balance_by_day().unwrap();
+        assert_eq!(2, changes.len());
+        assert_eq!("2024-06-01", changes[0].day);
+        assert_eq!(6f64, changes[0].change);
+        assert_eq!(0f64, changes[1].change);
+    }
+}
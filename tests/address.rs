@@ -1,9 +1,12 @@
 pub mod common;
 #[cfg(test)]
 mod wallet {
-    use std::any::Any;
+    use std::{any::Any, collections::HashMap};
 
-    use monitor::server::address::{Address, Wallet};
+    use monitor::server::{
+        address::{Address, AddressType, Deployed, Wallet},
+        clore::Clore,
+    };
     use tracing::info;
 
     #[tokio::test]
@@ -14,4 +17,80 @@ mod wallet {
         info!("{:?}", address);
         assert_eq!(std::any::TypeId::of::<Vec<Wallet>>(), address.type_id())
     }
+
+    #[test]
+    fn server_addresses_test() {
+        crate::common::setup();
+        let wallet = |address: &str, serverid: u32| {
+            let mut wallet = Wallet::new(address.to_string(), AddressType::SUB);
+            wallet.deploy = Deployed::DEPLOYED {
+                orderid: 1,
+                serverid,
+                sshaddr: None,
+                sshport: None,
+            };
+            (address.to_string(), wallet)
+        };
+        let wallets = Address(HashMap::from([
+            wallet("nimble1b", 1001),
+            wallet("nimble1a", 1001),
+            wallet("nimble1c", 1003),
+            (
+                "nimble1d".to_string(),
+                Wallet::new("nimble1d".to_string(), AddressType::SUB),
+            ),
+        ]));
+        assert_eq!(
+            vec!["nimble1a".to_string(), "nimble1b".to_string()],
+            wallets.server_addresses(1001)
+        );
+        assert!(wallets.server_addresses(1002).is_empty());
+    }
+
+    #[tokio::test]
+    async fn rent_server_test() {
+        crate::common::setup();
+        let mut deployed = Wallet::new("nimble1a".to_string(), AddressType::SUB);
+        deployed.deploy = Deployed::DEPLOYING {
+            orderid: 0,
+            serverid: 1001,
+            sshaddr: None,
+            sshport: None,
+        };
+        let mut wallets = Address(HashMap::from([
+            ("nimble1a".to_string(), deployed),
+            (
+                "nimble1b".to_string(),
+                Wallet::new("nimble1b".to_string(), AddressType::SUB),
+            ),
+            (
+                "nimble1m".to_string(),
+                Wallet::new("nimble1m".to_string(), AddressType::MASTER),
+            ),
+        ]));
+        let clore = Clore::default();
+        let rent = |addresses: &[&str]| {
+            addresses
+                .iter()
+                .map(|address| address.to_string())
+                .collect::<Vec<String>>()
+        };
+        // 下单前检查钱包，不请求市场
+        for addresses in [
+            rent(&[]),
+            rent(&["nimble1a", "nimble1b"]),
+            rent(&["nimble1b", "nimble1b"]),
+            rent(&["nimble1m"]),
+            rent(&["nimble1x"]),
+        ] {
+            assert!(wallets
+                .rent_server(&clore, 1002, &addresses, true)
+                .await
+                .is_err());
+        }
+        assert!(matches!(
+            wallets.get("nimble1b").unwrap().deploy,
+            Deployed::NOTASSIGNED
+        ));
+    }
 }
@@ -0,0 +1,168 @@
+pub mod common;
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use actix_web::App;
+    use monitor::{
+        auth::verify_admin,
+        config::CONFIG,
+        server::{
+            address::{is_pool_paused, Address, AddressType, Deployed},
+            admin, api, logs,
+        },
+    };
+
+    #[test]
+    fn verify_admin_test() {
+        crate::common::setup();
+        assert!(verify_admin(Some("admin"), Some("Bearer admin")).is_ok());
+        assert!(verify_admin(Some("admin"), Some("Bearer other")).is_err());
+        assert!(verify_admin(Some("admin"), Some("admin")).is_err());
+        assert!(verify_admin(Some("admin"), None).is_err());
+        // 未配置token时拒绝所有请求
+        assert!(verify_admin(None, Some("Bearer ")).is_err());
+        assert!(verify_admin(Some(""), Some("Bearer ")).is_err());
+    }
+
+    #[tokio::test]
+    async fn sub_address_test() {
+        crate::common::setup();
+        let mut address = Address::default();
+        address.add_sub_address("nimble1admintest").await.unwrap();
+        assert!(address.add_sub_address("nimble1admintest").await.is_err());
+        assert!(address.add_sub_address("../nimble").await.is_err());
+        assert_eq!(
+            AddressType::SUB,
+            address.get("nimble1admintest").unwrap().addr_type
+        );
+        let config = Arc::clone(&CONFIG);
+        assert!(config
+            .lock()
+            .await
+            .address
+            .sub_address
+            .contains(&"nimble1admintest".to_string()));
+
+        // 正在使用的钱包不能移除，重置后可以移除
+        address
+            .assgin_server(
+                "nimble1admintest",
+                Deployed::DEPLOYED {
+                    orderid: 1,
+                    serverid: 1001,
+                    sshaddr: None,
+                    sshport: None,
+                },
+            )
+            .await
+            .unwrap();
+        assert!(address
+            .remove_sub_address("nimble1admintest")
+            .await
+            .is_err());
+        let deploy = address.reset_wallet("nimble1admintest").await.unwrap();
+        assert!(matches!(deploy, Deployed::DEPLOYED { orderid: 1, .. }));
+        let wallet = address.get("nimble1admintest").unwrap();
+        assert_eq!(Deployed::NOTASSIGNED, wallet.deploy);
+        assert_eq!(None, wallet.start_time);
+
+        address
+            .remove_sub_address("nimble1admintest")
+            .await
+            .unwrap();
+        assert!(!address.contains_key("nimble1admintest"));
+        assert!(!config
+            .lock()
+            .await
+            .address
+            .sub_address
+            .contains(&"nimble1admintest".to_string()));
+        assert!(address.reset_wallet("nimble1admintest").await.is_err());
+    }
+
+    #[actix_web::test]
+    async fn pool_pause_test() {
+        crate::common::setup();
+        let config = Arc::clone(&CONFIG);
+        config.lock().await.auth.admin_token = Some("admin".to_string());
+        let app = actix_web::test::init_service(
+            App::new()
+                .service(admin::pause_pool)
+                .service(admin::resume_pool),
+        )
+        .await;
+
+        let req = actix_web::test::TestRequest::post()
+            .uri("/admin/pool/pause")
+            .to_request();
+        let resp = actix_web::test::call_service(&app, req).await;
+        assert_eq!(401, resp.status().as_u16());
+        assert!(!is_pool_paused());
+
+        let req = actix_web::test::TestRequest::post()
+            .uri("/admin/pool/pause")
+            .insert_header(("Authorization", "Bearer admin"))
+            .to_request();
+        let resp = actix_web::test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+        assert!(is_pool_paused());
+
+        let req = actix_web::test::TestRequest::post()
+            .uri("/admin/pool/resume")
+            .insert_header(("Authorization", "Bearer admin"))
+            .to_request();
+        let resp = actix_web::test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+        assert!(!is_pool_paused());
+    }
+
+    #[actix_web::test]
+    async fn read_auth_test() {
+        crate::common::setup();
+        let config = Arc::clone(&CONFIG);
+        config.lock().await.auth.admin_token = Some("admin".to_string());
+        let app = actix_web::test::init_service(
+            App::new()
+                .service(api::wallets)
+                .service(api::market)
+                .service(api::blocklist)
+                .service(api::metrics)
+                .service(logs),
+        )
+        .await;
+
+        // 查询接口同样需要管理token
+        for uri in [
+            "/api/wallets",
+            "/api/market",
+            "/api/blocklist",
+            "/metrics",
+            "/logs",
+        ] {
+            let req = actix_web::test::TestRequest::get().uri(uri).to_request();
+            let resp = actix_web::test::call_service(&app, req).await;
+            assert_eq!(401, resp.status().as_u16(), "{}", uri);
+        }
+
+        let req = actix_web::test::TestRequest::get()
+            .uri("/api/wallets")
+            .insert_header(("Authorization", "Bearer admin"))
+            .to_request();
+        let resp = actix_web::test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+        let req = actix_web::test::TestRequest::get()
+            .uri("/api/blocklist")
+            .insert_header(("Authorization", "Bearer admin"))
+            .to_request();
+        let resp = actix_web::test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+        let req = actix_web::test::TestRequest::get()
+            .uri("/metrics")
+            .insert_header(("Authorization", "Bearer admin"))
+            .to_request();
+        let resp = actix_web::test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+    }
+}
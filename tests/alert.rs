@@ -0,0 +1,158 @@
+pub mod common;
+
+#[cfg(test)]
+mod test {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+
+    use chrono::{Local, TimeZone};
+    use monitor::{
+        alert::{Alert, Alerter, Severity, WebhookKind},
+        config::{self, Webhook},
+    };
+    use serde_json::Value;
+
+    type Requests = Arc<Mutex<Vec<(String, Value)>>>;
+
+    /// 本地接收webhook的http服务，记录请求路径和body
+    fn receiver() -> (String, Requests) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let received = Arc::clone(&requests);
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let path = request_line
+                    .split_whitespace()
+                    .nth(1)
+                    .unwrap_or_default()
+                    .to_string();
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim().to_lowercase();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some(value) = line.strip_prefix("content-length:") {
+                        length = value.trim().parse::<usize>().unwrap();
+                    }
+                }
+                let mut body = vec![0u8; length];
+                reader.read_exact(&mut body).unwrap();
+                received
+                    .lock()
+                    .unwrap()
+                    .push((path, serde_json::from_slice(&body).unwrap()));
+                let mut stream = stream;
+                let _ = stream.write_all(
+                    b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                );
+            }
+        });
+        (url, requests)
+    }
+
+    fn alert(key: &str, severity: Severity, hour: u32, minute: u32) -> Alert {
+        Alert {
+            key: key.to_string(),
+            severity,
+            title: "订单已取消".to_string(),
+            message: "订单:1".to_string(),
+            time: Local.with_ymd_and_hms(2024, 6, 1, hour, minute, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn should_send_test() {
+        crate::common::setup();
+        let mut alerter = Alerter::new(config::Alert {
+            dedup_seconds: 600,
+            quiet_hours: vec!["23:00-07:00".to_string()],
+            ..Default::default()
+        });
+        // 低于最低级别
+        assert!(!alerter.should_send(&alert("a", Severity::INFO, 12, 0)));
+        assert!(alerter.should_send(&alert("a", Severity::WARNING, 12, 0)));
+        // 没有发送成功时不去重
+        assert!(alerter.should_send(&alert("a", Severity::WARNING, 12, 1)));
+        alerter.record_sent(&alert("a", Severity::WARNING, 12, 0));
+        // 去重
+        assert!(!alerter.should_send(&alert("a", Severity::WARNING, 12, 5)));
+        assert!(alerter.should_send(&alert("b", Severity::WARNING, 12, 5)));
+        assert!(alerter.should_send(&alert("a", Severity::WARNING, 12, 10)));
+        // 跨零点的静默时段只发送CRITICAL
+        assert!(!alerter.should_send(&alert("c", Severity::WARNING, 23, 30)));
+        assert!(!alerter.should_send(&alert("c", Severity::WARNING, 6, 59)));
+        assert!(alerter.should_send(&alert("c", Severity::CRITICAL, 23, 30)));
+        assert!(alerter.should_send(&alert("d", Severity::WARNING, 7, 0)));
+    }
+
+    #[tokio::test]
+    async fn send_test() {
+        crate::common::setup();
+        let (url, requests) = receiver();
+        let webhook = |kind: WebhookKind, path: &str, min_severity: Severity| Webhook {
+            kind,
+            url: format!("{}{}", url, path),
+            chat_id: Some("42".to_string()),
+            min_severity,
+        };
+        let mut alerter = Alerter::new(config::Alert {
+            webhooks: vec![
+                webhook(WebhookKind::JSON, "/json", Severity::WARNING),
+                webhook(WebhookKind::DINGTALK, "/dingtalk", Severity::WARNING),
+                webhook(WebhookKind::TELEGRAM, "/telegram", Severity::CRITICAL),
+                // 端口不存在，发送失败不影响其他webhook
+                Webhook {
+                    url: "http://127.0.0.1:1/closed".to_string(),
+                    ..webhook(WebhookKind::JSON, "", Severity::WARNING)
+                },
+            ],
+            ..Default::default()
+        });
+
+        let warning = alert("cancel:1", Severity::WARNING, 12, 0);
+        assert_eq!(2, alerter.send(&warning).await);
+        assert_eq!(0, alerter.send(&warning).await);
+        let critical = alert("miner_exited:1", Severity::CRITICAL, 12, 0);
+        assert_eq!(3, alerter.send(&critical).await);
+        // 只有失败的webhook时不记录，下次继续发送
+        let retry = alert("cancel:2", Severity::WARNING, 12, 0);
+        let mut failed = Alerter::new(config::Alert {
+            webhooks: vec![Webhook {
+                url: "http://127.0.0.1:1/closed".to_string(),
+                ..webhook(WebhookKind::JSON, "", Severity::WARNING)
+            }],
+            ..Default::default()
+        });
+        assert_eq!(0, failed.send(&retry).await);
+        assert!(failed.should_send(&retry));
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(5, requests.len());
+        let (path, body) = &requests[0];
+        assert_eq!("/json", path);
+        assert_eq!("cancel:1", body["key"]);
+        assert_eq!("WARNING", body["severity"]);
+        let (path, body) = &requests[1];
+        assert_eq!("/dingtalk", path);
+        assert_eq!("text", body["msgtype"]);
+        assert!(body["text"]["content"]
+            .as_str()
+            .unwrap()
+            .starts_with("[WARNING] 订单已取消\n订单:1"));
+        let (path, body) = &requests[4];
+        assert_eq!("/telegram", path);
+        assert_eq!("42", body["chat_id"]);
+        assert!(body["text"]
+            .as_str()
+            .unwrap()
+            .starts_with("[CRITICAL] 订单已取消"));
+    }
+}
@@ -0,0 +1,95 @@
+pub mod common;
+
+#[cfg(test)]
+mod test {
+
+    use monitor::config::Log;
+    use monitor::log::analyzer::{format_summaries, Analyzer};
+    use monitor::log::rule::Rules;
+    use monitor::log::Logs;
+
+    const ADDRESS: &str = "nimble1ha23ka8jzm63eupcrf0thlhhldu4j6z66shqjl";
+
+    #[test]
+    fn analyze_dir_test() {
+        crate::common::setup();
+        let dir = crate::common::temp_dir("analyzer");
+        std::fs::create_dir_all(dir.join("logs")).unwrap();
+        let lines = [
+            "Generating train split:  45%|████▌     | 4500/10000 [00:03<00:04, 1234.56 examples/s]\r\
+             Generating train split: 100%|██████████| 10000/10000 [00:06<00:00, 1234.56 examples/s]",
+            " 10%|█         | 100/1000 [00:10<01:30, 10.00it/s]",
+            " 20%|██        | 200/1000 [00:20<01:20, 12.00it/s]\r 30%|███       | 300/1000 [00:30<01:10, 14.00it/s]",
+            " 40%|████      | 400/1000 [00:40<01:00, 16.00it/s]",
+            " 41%|████      | 410/1000 [00:40<00:10, 52.30it/s]",
+            "Traceback (most recent call last):",
+            "RuntimeError: CUDA out of memory",
+            "Failed to init particle",
+            "completed the task",
+            "other output",
+        ];
+        std::fs::write(
+            dir.join("logs").join(format!("{}.txt", ADDRESS)),
+            lines.join("\n"),
+        )
+        .unwrap();
+        std::fs::write(dir.join("logs").join("other.log"), "ignored").unwrap();
+        std::fs::copy("./example/my_logs.json", dir.join("my_logs.json")).unwrap();
+
+        let mut analyzer = Analyzer::new(Rules::default());
+        analyzer
+            .feed_dir(&dir, &Logs::get_include(&Log::default()))
+            .unwrap();
+        let summaries = analyzer.summaries();
+        assert_eq!(1, summaries.len());
+        let summary = &summaries[0];
+        assert_eq!(ADDRESS, summary.address);
+        assert_eq!(12, summary.lines);
+
+        let hashrate = &summary.hashrate;
+        assert_eq!(4, hashrate.samples);
+        assert_eq!(1, hashrate.verifying);
+        assert_eq!(Some(13f64), hashrate.mean);
+        assert_eq!(Some(10f64), hashrate.min);
+        assert_eq!(Some(16f64), hashrate.max);
+        assert_eq!(0.25, hashrate.below);
+
+        let phase = &summary.downloads["download:Generating"];
+        assert_eq!("100%", phase.percent);
+        assert_eq!(Some(10000), phase.total);
+        assert_eq!(2, phase.lines);
+
+        assert_eq!(Some(&2), summary.failures.get("exception"));
+        assert_eq!(Some(&1), summary.failures.get("init_particle_failed"));
+
+        let tasks = &summary.tasks;
+        assert_eq!(3, tasks.success);
+        assert_eq!(Some(1f64), tasks.success_rate);
+        assert_eq!(1, tasks.completions);
+        // 第一个任务开始训练到日志文件修改时间
+        assert!(summary.uptime.unwrap() > 3f64 * 3600f64);
+
+        let text = format_summaries(&summaries);
+        assert!(text.contains(ADDRESS));
+        assert!(text.contains("download:Generating 100% 10000/10000"));
+        assert!(text.contains("exception: 2次"));
+        let json = serde_json::to_value(&summaries).unwrap();
+        assert_eq!(3, json[0]["tasks"]["success"]);
+    }
+
+    #[test]
+    fn single_time_point_test() {
+        crate::common::setup();
+        let dir = crate::common::temp_dir("analyzer_single");
+        let path = dir.join(format!("{}.txt", ADDRESS));
+        std::fs::write(&path, " 10%|█         | 100/1000 [00:10<01:30, 10.00it/s]").unwrap();
+
+        // 只有日志文件修改时间一个时间点，不计算运行时长
+        let mut analyzer = Analyzer::new(Rules::default());
+        analyzer.feed_log_file(&path).unwrap();
+        let summaries = analyzer.summaries();
+        assert!(summaries[0].first_seen.is_some());
+        assert_eq!(summaries[0].first_seen, summaries[0].last_seen);
+        assert_eq!(None, summaries[0].uptime);
+    }
+}
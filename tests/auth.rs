@@ -0,0 +1,145 @@
+pub mod common;
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use monitor::{
+        auth::{issue_token, Signer, Verifier},
+        config::Auth,
+    };
+
+    fn auth() -> Auth {
+        Auth {
+            secret: Some("secret".to_string()),
+            revoked: vec![1003],
+            max_skew: 300,
+            admin_token: None,
+        }
+    }
+
+    fn headers(
+        signer: &Signer,
+        path: &str,
+        body: &[u8],
+        timestamp: i64,
+        nonce: &str,
+    ) -> HashMap<String, String> {
+        signer
+            .sign("POST", path, body, timestamp, nonce)
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value))
+            .collect()
+    }
+
+    #[test]
+    fn verify_test() {
+        crate::common::setup();
+        let now = 1717200000;
+        let path = "/report_task/1001/nimble1a";
+        let body = br#"{"WalletAddr":"nimble1a"}"#;
+        let signer = Signer::new(1001, 1, issue_token("secret", 1001, 1));
+        let mut verifier = Verifier::new(&auth());
+
+        let signed = headers(&signer, path, body, now, "n1");
+        let header = |name: &str| signed.get(name).cloned();
+        assert_eq!(
+            Ok(1001),
+            verifier.verify(header, "POST", path, body, now + 10)
+        );
+        // 重放
+        assert!(verifier
+            .verify(header, "POST", path, body, now + 20)
+            .is_err());
+        // 篡改body或路径
+        let signed = headers(&signer, path, body, now, "n2");
+        let header = |name: &str| signed.get(name).cloned();
+        assert!(verifier.verify(header, "POST", path, b"{}", now).is_err());
+        assert!(verifier
+            .verify(header, "POST", "/report_task/1002/nimble1a", body, now)
+            .is_err());
+        // 过期
+        assert!(verifier
+            .verify(header, "POST", path, body, now + 301)
+            .is_err());
+        // 没有签名
+        assert!(verifier
+            .verify(|_: &str| None, "POST", path, body, now)
+            .is_err());
+
+        // 使用其他服务器的token
+        let forged = Signer::new(1002, 1, issue_token("secret", 1001, 1));
+        let signed = headers(&forged, path, body, now, "n3");
+        assert!(verifier
+            .verify(
+                |name: &str| signed.get(name).cloned(),
+                "POST",
+                path,
+                body,
+                now
+            )
+            .is_err());
+
+        // 已吊销
+        let revoked = Signer::new(1003, 1, issue_token("secret", 1003, 1));
+        let signed = headers(&revoked, path, body, now, "n4");
+        assert!(verifier
+            .verify(
+                |name: &str| signed.get(name).cloned(),
+                "POST",
+                path,
+                body,
+                now
+            )
+            .is_err());
+        // 修改签发时间
+        let forged = Signer::new(1001, 2, issue_token("secret", 1001, 1));
+        let signed = headers(&forged, path, body, now, "n5");
+        assert!(verifier
+            .verify(
+                |name: &str| signed.get(name).cloned(),
+                "POST",
+                path,
+                body,
+                now
+            )
+            .is_err());
+        // 订单释放后吊销，重新下单签发的token可以继续使用
+        verifier.revoke(1001, 5);
+        let signed = headers(&signer, path, body, now, "n7");
+        assert!(verifier
+            .verify(
+                |name: &str| signed.get(name).cloned(),
+                "POST",
+                path,
+                body,
+                now
+            )
+            .is_err());
+        let renewed = Signer::new(1001, 6, issue_token("secret", 1001, 6));
+        let signed = headers(&renewed, path, body, now, "n8");
+        assert_eq!(
+            Ok(1001),
+            verifier.verify(
+                |name: &str| signed.get(name).cloned(),
+                "POST",
+                path,
+                body,
+                now
+            )
+        );
+
+        // 未配置密钥时全部拒绝
+        let mut verifier = Verifier::new(&Auth::default());
+        let signed = headers(&signer, path, body, now, "n6");
+        assert!(verifier
+            .verify(
+                |name: &str| signed.get(name).cloned(),
+                "POST",
+                path,
+                body,
+                now
+            )
+            .is_err());
+    }
+}
@@ -10,7 +10,10 @@ mod test {
 
     use monitor::server::{
         clore::{
-            model::{market::Marketplace, Card},
+            model::{
+                market::{Exclusion, Marketplace},
+                Card,
+            },
             Clore,
         },
         ssh::Ssh,
@@ -83,6 +86,47 @@ mod test {
         assert_eq!(std::any::TypeId::of::<Vec<Card>>(), cards.type_id())
     }
 
+    #[test]
+    fn marketplace_candidates_test() {
+        crate::common::setup();
+        let row = std::fs::read_to_string("./example/market.json").unwrap();
+        let model = serde_json::from_str::<Marketplace>(&row).unwrap();
+
+        let candidates = model.candidates(&[]);
+        assert_eq!(4, candidates.len());
+        assert!(candidates[0].is_available());
+        assert_eq!(
+            vec![
+                Exclusion::LOWRATING,
+                Exclusion::RENTED,
+                Exclusion::OVERPRICED
+            ],
+            candidates[1].exclusions
+        );
+        assert_eq!(
+            vec![
+                Exclusion::SLOWNETWORK,
+                Exclusion::FEWCPUS,
+                Exclusion::CARDTYPE
+            ],
+            candidates[2].exclusions
+        );
+        assert_eq!(
+            vec![
+                Exclusion::GPUMODEL,
+                Exclusion::COINNOTALLOWED,
+                Exclusion::SHORTMRL,
+                Exclusion::CARDTYPE,
+                Exclusion::OVERPRICED
+            ],
+            candidates[3].exclusions
+        );
+
+        let candidates = model.candidates(&[1001]);
+        assert_eq!(vec![Exclusion::BLOCKED], candidates[0].exclusions);
+        assert!(candidates.iter().all(|candidate| !candidate.is_available()));
+    }
+
     #[tokio::test]
     async fn create_order_test() {
         crate::common::setup();
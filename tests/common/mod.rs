@@ -1,3 +1,6 @@
+use std::path::PathBuf;
+
+use monitor::server::clore::model::my_orders::{MyOrders, Order};
 use tracing::info;
 
 pub fn setup() {
@@ -8,3 +11,17 @@ pub fn setup() {
         info!("日志初始化完成")
     }
 }
+
+/// 按进程号区分的临时目录，每次重新创建
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// example/my_orders.json中的订单
+pub fn orders() -> Vec<Order> {
+    let row = std::fs::read_to_string("./example/my_orders.json").unwrap();
+    serde_json::from_str::<MyOrders>(&row).unwrap().to_vec()
+}
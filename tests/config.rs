@@ -2,7 +2,7 @@ pub mod common;
 
 #[cfg(test)]
 mod test {
-    use monitor::config::Config;
+    use monitor::config::{Config, Server};
     use std::any::{self, Any};
 
     use crate::common;
@@ -28,4 +28,24 @@ mod test {
 
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn bind_addr_test() {
+        common::setup();
+        let server = Server {
+            ip: None,
+            port: None,
+        };
+        assert_eq!(Ok(("0.0.0.0".parse().unwrap(), 8888)), server.bind_addr());
+        let server = Server {
+            ip: Some("127.0.0.1".parse().unwrap()),
+            port: Some(9000),
+        };
+        assert_eq!(Ok(("127.0.0.1".parse().unwrap(), 9000)), server.bind_addr());
+        let server = Server {
+            ip: None,
+            port: Some(70000),
+        };
+        assert!(server.bind_addr().is_err());
+    }
 }
@@ -0,0 +1,129 @@
+pub mod common;
+
+#[cfg(test)]
+mod test {
+    use monitor::server::{
+        address::{AddressType, Deployed, Wallet},
+        clore::model::{my_orders::Order, CardType},
+        fleet::{self, FleetFilter, HostResult},
+        ssh::{ExecOutput, Phase, SshError},
+    };
+
+    fn output(stdout: &str, status: i32) -> Result<ExecOutput, SshError> {
+        Ok(ExecOutput {
+            stdout: stdout.to_string(),
+            stderr: String::new(),
+            status,
+        })
+    }
+
+    fn order_ids(orders: &[&Order]) -> Vec<u32> {
+        orders.iter().map(|order| order.order_id).collect()
+    }
+
+    #[test]
+    fn select_test() {
+        crate::common::setup();
+        let orders = crate::common::orders();
+        let mut wallet = Wallet::new("nimble1a".to_string(), AddressType::SUB);
+        wallet.deploy = Deployed::DEPLOYED {
+            orderid: 0,
+            serverid: 1003,
+            sshaddr: None,
+            sshport: None,
+        };
+        let wallets = vec![wallet];
+
+        let filter = FleetFilter::default();
+        assert!(filter.is_empty());
+        assert_eq!(
+            vec![2001, 2002],
+            order_ids(&filter.select(&orders, &wallets))
+        );
+
+        let filter = FleetFilter {
+            server_ids: vec![1001],
+            ..Default::default()
+        };
+        assert_eq!(vec![2001], order_ids(&filter.select(&orders, &wallets)));
+
+        let filter = FleetFilter {
+            wallets: vec!["nimble1a".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(vec![2002], order_ids(&filter.select(&orders, &wallets)));
+
+        // 条件之间为且
+        let filter = FleetFilter {
+            card_types: vec![CardType::NVIDIA4090],
+            order_ids: vec![2001, 9999],
+            ..Default::default()
+        };
+        assert_eq!(vec![2001], order_ids(&filter.select(&orders, &wallets)));
+        let filter = FleetFilter {
+            card_types: vec![CardType::NVIDIA3090],
+            ..Default::default()
+        };
+        assert!(filter.select(&orders, &wallets).is_empty());
+    }
+
+    #[test]
+    fn group_test() {
+        crate::common::setup();
+        let results = vec![
+            HostResult {
+                order_id: 3,
+                server_id: 30,
+                result: output("ok\n", 0),
+            },
+            HostResult {
+                order_id: 1,
+                server_id: 10,
+                result: output("ok\n", 0),
+            },
+            HostResult {
+                order_id: 2,
+                server_id: 20,
+                result: Err(SshError::TIMEOUT(Phase::TCP)),
+            },
+            HostResult {
+                order_id: 4,
+                server_id: 40,
+                result: Ok(ExecOutput {
+                    stdout: String::new(),
+                    stderr: "not found".to_string(),
+                    status: 127,
+                }),
+            },
+        ];
+        assert_eq!(2, results.iter().filter(|host| host.is_success()).count());
+        let groups = fleet::group(&results);
+        assert_eq!(3, groups.len());
+        assert_eq!(vec![(10, 1), (30, 3)], groups[0].hosts);
+        assert_eq!(vec![(20, 2)], groups[1].hosts);
+        assert_eq!(
+            "== 2台 exit:0 serverid/orderid:10/1,30/3\nok\n\
+             == 1台 error:TCP超时 serverid/orderid:20/2\n\
+             == 1台 exit:127 serverid/orderid:40/4\n-- stderr\nnot found\n",
+            fleet::format_groups(&groups)
+        );
+    }
+
+    #[tokio::test]
+    async fn exec_test() {
+        crate::common::setup();
+        let mut orders = crate::common::orders();
+        // 没有ssh端口的订单不需要链接
+        orders.iter_mut().for_each(|order| order.tcp_ports.clear());
+        let orders = orders.iter().collect::<Vec<&Order>>();
+        let mut results = fleet::exec(&orders, "true", Some(2)).await;
+        results.sort_by_key(|host| host.order_id);
+        assert_eq!(2, results.len());
+        assert!(results
+            .iter()
+            .all(|host| host.result == Err(SshError::NOADDRESS)));
+        let groups = fleet::group(&results);
+        assert_eq!(1, groups.len());
+        assert_eq!(vec![(1001, 2001), (1003, 2002)], groups[0].hosts);
+    }
+}
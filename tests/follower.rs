@@ -0,0 +1,92 @@
+pub mod common;
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    use monitor::log::follower::{FileState, Follower, Offsets};
+
+    fn append(path: &PathBuf, text: &str) {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap();
+        file.write_all(text.as_bytes()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn follow_test() {
+        crate::common::setup();
+        let dir = crate::common::temp_dir("follower");
+        let path = dir.join("nimble1a.txt");
+        append(&path, "first\nsecond\n");
+
+        let mut follower = Follower::open(&path, None).await.unwrap();
+        let (state, lines) = follower.read_lines().await.unwrap();
+        assert_eq!(FileState::NORMAL, state);
+        assert_eq!(vec!["first", "second"], lines);
+
+        // 读到文件末尾后继续跟踪新内容，不完整的行等待换行
+        append(&path, "third\r 45%|#### | 10it/s\rpart");
+        let (_, lines) = follower.read_lines().await.unwrap();
+        assert_eq!(vec!["third", "45%|#### | 10it/s"], lines);
+        append(&path, "ial\n");
+        let (_, lines) = follower.read_lines().await.unwrap();
+        assert_eq!(vec!["partial"], lines);
+
+        // pm2 flush 截断文件
+        std::fs::write(&path, "").unwrap();
+        let (state, lines) = follower.read_lines().await.unwrap();
+        assert_eq!(FileState::TRUNCATED, state);
+        assert!(lines.is_empty());
+        append(&path, "after truncate\n");
+        let (_, lines) = follower.read_lines().await.unwrap();
+        assert_eq!(vec!["after truncate"], lines);
+
+        // 轮转:旧文件改名，重新创建新文件，旧文件剩余内容先读完
+        append(&path, "before rotate\n");
+        std::fs::rename(&path, dir.join("nimble1a.txt.1")).unwrap();
+        append(&path, "rotated\n");
+        let (state, lines) = follower.read_lines().await.unwrap();
+        assert_eq!(FileState::ROTATED, state);
+        assert_eq!(vec!["before rotate", "rotated"], lines);
+
+        std::fs::remove_file(&path).unwrap();
+        let (state, _) = follower.read_lines().await.unwrap();
+        assert_eq!(FileState::REMOVED, state);
+    }
+
+    #[tokio::test]
+    async fn resume_test() {
+        crate::common::setup();
+        let dir = crate::common::temp_dir("follower_resume");
+        let path = dir.join("nimble1b.txt");
+        append(&path, "first\nsecond\n");
+
+        let mut follower = Follower::open(&path, None).await.unwrap();
+        follower.read_lines().await.unwrap();
+        let mut offsets = Offsets::load(dir.join(".log_offsets.json"));
+        offsets.set(&path, follower.offset());
+        offsets.save().unwrap();
+        drop(follower);
+
+        // 监控重启后从保存的位置继续
+        append(&path, "third\n");
+        let offsets = Offsets::load(dir.join(".log_offsets.json"));
+        let resume = offsets.get(&path);
+        assert_eq!(Some(13), resume.map(|offset| offset.offset));
+        let mut follower = Follower::open(&path, resume).await.unwrap();
+        let (_, lines) = follower.read_lines().await.unwrap();
+        assert_eq!(vec!["third"], lines);
+
+        // 文件已经不是同一个文件时从头读取
+        std::fs::remove_file(&path).unwrap();
+        append(&dir.join("other.txt"), "placeholder\n");
+        append(&path, "new\n");
+        let mut follower = Follower::open(&path, resume).await.unwrap();
+        let (_, lines) = follower.read_lines().await.unwrap();
+        assert_eq!(vec!["new"], lines);
+    }
+}
@@ -0,0 +1,123 @@
+pub mod common;
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use monitor::{
+        monitor::nvidia::GeForce,
+        server::clore::model::{gpu, CardType, Generation},
+    };
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    struct Fixture {
+        gpu: String,
+        card_type: String,
+        card_number: u32,
+    }
+
+    #[test]
+    fn specs_test() {
+        crate::common::setup();
+        let row = std::fs::read_to_string("./example/gpus.json").unwrap();
+        let fixtures = serde_json::from_str::<Vec<Fixture>>(&row).unwrap();
+        let mut specs = crate::common::orders()[0].specs.clone();
+        for fixture in fixtures.iter() {
+            specs.gpu = fixture.gpu.clone();
+            let expected = match fixture.card_type.as_str() {
+                "UNKNOWN" => CardType::UNKNOWN(fixture.gpu.clone()),
+                card_type => CardType::from_str(card_type).unwrap(),
+            };
+            assert_eq!(expected, specs.get_card_type(), "{}", fixture.gpu);
+            assert_eq!(
+                fixture.card_number,
+                specs.get_card_number(),
+                "{}",
+                fixture.gpu
+            );
+            // 去掉数量后和nvidia-smi的名称结果相同
+            if fixture.card_type != "UNKNOWN" {
+                assert_eq!(
+                    expected,
+                    gpu::parse(specs.get_gpu_name()),
+                    "{}",
+                    fixture.gpu
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn parse_test() {
+        crate::common::setup();
+        assert_eq!(
+            CardType::NVIDIA4070TI,
+            gpu::parse("NVIDIA GeForce RTX 4070Ti")
+        );
+        assert_eq!(
+            CardType::NVIDIA4090D,
+            gpu::parse("nvidia geforce rtx 4090d")
+        );
+        assert_eq!(
+            CardType::NVIDIA4060L,
+            gpu::parse("NVIDIA GeForce RTX 4060 Mobile")
+        );
+        assert_eq!(
+            CardType::UNKNOWN("NVIDIA A10G".to_string()),
+            gpu::parse("NVIDIA A10G")
+        );
+        assert_eq!(CardType::UNKNOWN("".to_string()), gpu::parse(""));
+    }
+
+    #[test]
+    fn metadata_test() {
+        crate::common::setup();
+        assert_eq!(Some(24), CardType::NVIDIA4090.vram());
+        assert_eq!(Some(16), CardType::NVIDIA4090L.vram());
+        assert_eq!(Some(80), CardType::NVIDIAA10080G.vram());
+        assert_eq!(Some(40), CardType::NVIDIAA100.vram());
+        assert_eq!(Some(192), CardType::AMDMI300X.vram());
+        assert_eq!(None, CardType::UNKNOWN("x".to_string()).vram());
+
+        assert_eq!(Some(Generation::ADA), CardType::NVIDIA4090D.generation());
+        assert_eq!(Some(Generation::AMPERE), CardType::NVIDIAA6000.generation());
+        assert_eq!(Some(Generation::HOPPER), CardType::NVIDIAH100.generation());
+        assert_eq!(Some(Generation::TURING), CardType::NVIDIA1660.generation());
+        assert_eq!(Some(Generation::RDNA3), CardType::AMD7900XTX.generation());
+        assert_eq!(None, CardType::UNKNOWN("x".to_string()).generation());
+
+        // 只有原来的型号有租用价格
+        assert_eq!(64f64, CardType::NVIDIA4090.get_max_price(2f64));
+        assert_eq!(0f64, CardType::NVIDIA4090D.get_max_price(2f64));
+    }
+
+    #[test]
+    fn nvidia_smi_test() {
+        crate::common::setup();
+        let lines = [
+            ("GPU 0: NVIDIA GeForce RTX 4070 (UUID: GPU-5e4c623f-998d-912c-3743-3465506f63ad)", CardType::NVIDIA4070),
+            ("GPU 1: NVIDIA GeForce RTX 4070 Ti (UUID: GPU-5e4c623f-998d-912c-3743-3465506f63ad)", CardType::NVIDIA4070TI),
+            ("GPU 2: NVIDIA GeForce RTX 4070 Ti SUPER (UUID: GPU-5e4c623f-998d-912c-3743-3465506f63ad)", CardType::NVIDIA4070TIS),
+            ("GPU 4: NVIDIA A100-SXM4-80GB (UUID: GPU-13d44c72-a798-c126-54cb-98e543beadd3)", CardType::NVIDIAA10080G),
+        ];
+        for (line, expected) in lines.iter() {
+            match GeForce::parse(line) {
+                GeForce::CARD {
+                    id,
+                    uuid,
+                    card_type,
+                } => {
+                    assert!(id <= 4);
+                    assert!(uuid.starts_with("GPU-") && !uuid.ends_with(')'), "{}", uuid);
+                    assert_eq!(*expected, card_type, "{}", line);
+                }
+                GeForce::ERROR(e) => panic!("{}", e),
+            }
+        }
+        assert!(matches!(
+            GeForce::parse("No devices were found"),
+            GeForce::ERROR(_)
+        ));
+    }
+}
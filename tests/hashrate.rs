@@ -0,0 +1,83 @@
+pub mod common;
+
+#[cfg(test)]
+mod test {
+    use chrono::{Duration, Local};
+    use monitor::{
+        log::rule::Rules,
+        monitor::hashrate::{HashrateSeries, HashrateStats, Hashrates},
+        server::address::{Address, AddressType, Deployed, Wallet},
+    };
+
+    #[test]
+    fn series_stats_test() {
+        crate::common::setup();
+        let now = Local::now();
+        let mut series = HashrateSeries::default();
+        // 两小时前的样本会被淘汰
+        series.push(now - Duration::minutes(120), 1f64, true);
+        series.push(now - Duration::minutes(40), 15f64, false);
+        series.push(now - Duration::minutes(10), 9f64, true);
+        series.push(now - Duration::seconds(30), 13f64, false);
+
+        let stats = series.stats(now);
+        assert_eq!(3, stats.samples);
+        assert_eq!(Some(13f64), stats.avg_1m);
+        assert_eq!(Some(11f64), stats.avg_15m);
+        assert_eq!(Some(37f64 / 3f64), stats.avg_1h);
+        assert_eq!(Some(9f64), stats.min);
+        assert_eq!(Some(15f64), stats.max);
+        assert!((stats.span - 2400f64).abs() < 1e-6);
+        // 9it/s 持续了9分30秒
+        assert!((stats.under_threshold - 570f64 / 2400f64).abs() < 1e-6);
+        assert!(!stats.need_restart());
+    }
+
+    #[test]
+    fn record_events_test() {
+        crate::common::setup();
+        let address = "nimble1ha23ka8jzm63eupcrf0thlhhldu4j6z66shqjl";
+        let rules = Rules::default();
+        let mut hashrates = Hashrates::default();
+        for line in [
+            " 45%|████▌     | 450/1000 [00:30<00:37, 8.82it/s]",
+            " 46%|████▌     | 460/1000 [00:30<00:37, 52.12it/s]",
+            "Failed to init particle",
+        ] {
+            let event = rules.parse(address, line).unwrap();
+            hashrates.record(&event);
+        }
+        let stats = hashrates.stats(Local::now());
+        assert_eq!(1, stats[address].samples);
+        assert_eq!(Some(8.82), stats[address].max);
+    }
+
+    #[test]
+    fn rank_servers_test() {
+        crate::common::setup();
+        let mut address = Address::default();
+        for (index, (serverid, avg_1h)) in [
+            (1, Some(12f64)),
+            (1, Some(14f64)),
+            (2, Some(15f64)),
+            (3, None),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let mut wallet = Wallet::new(format!("nimble{}", index), AddressType::SUB);
+            wallet.deploy = Deployed::DEPLOYED {
+                orderid: serverid * 10,
+                serverid,
+                sshaddr: None,
+                sshport: None,
+            };
+            wallet.hashrate = avg_1h.map(|avg_1h| HashrateStats {
+                avg_1h: Some(avg_1h),
+                ..Default::default()
+            });
+            address.insert(wallet.address.clone(), wallet);
+        }
+        assert_eq!(vec![(2, 15f64), (1, 13f64)], address.rank_servers());
+    }
+}
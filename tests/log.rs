@@ -0,0 +1,148 @@
+pub mod common;
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use monitor::log::{
+        rule::{Rule, RuleKind, Rules, Threshold},
+        RunLogs, SeenTasks,
+    };
+
+    const ADDRESS: &str = "nimble1ha23ka8jzm63eupcrf0thlhhldu4j6z66shqjl";
+
+    #[test]
+    fn default_rules_test() {
+        crate::common::setup();
+        let rules = Rules::default();
+
+        let event = rules
+            .parse(
+                ADDRESS,
+                "Generating train split:  45%|████▌     | 4500/10000 [00:03<00:04, 1234.56 examples/s]",
+            )
+            .unwrap();
+        assert_eq!(RuleKind::PROGRESS, event.kind);
+        assert_eq!("Generating", event.fields["operate"]);
+        assert_eq!("4500", event.fields["progress"]);
+        assert_eq!("download:Generating", event.key());
+
+        let event = rules
+            .parse(
+                ADDRESS,
+                " 45%|████▌     | 450/1000 [00:30<00:37, 14.82it/s]",
+            )
+            .unwrap();
+        assert_eq!(RuleKind::HASHRATE, event.kind);
+        assert_eq!(Some(14.82), event.value);
+        assert_eq!(Threshold::NORMAL, event.threshold);
+        assert!(!event.need_restart());
+
+        let event = rules
+            .parse(ADDRESS, " 46%|████▌     | 460/1000 [00:31<00:37, 8.10it/s]")
+            .unwrap();
+        assert_eq!(Threshold::BELOW, event.threshold);
+        assert!(event.need_restart());
+
+        let event = rules
+            .parse(ADDRESS, " 47%|████▋     | 470/1000 [00:31<00:20, 52.3it/s]")
+            .unwrap();
+        assert_eq!(Threshold::ABOVE, event.threshold);
+
+        let event = rules
+            .parse(ADDRESS, "{'eval_loss': 0.41, 'epoch': 1.0}")
+            .unwrap();
+        assert_eq!(RuleKind::IGNORE, event.kind);
+
+        let event = rules
+            .parse(ADDRESS, "Failed to init particle, retrying")
+            .unwrap();
+        assert_eq!(RuleKind::RESTART, event.kind);
+        assert!(event.need_restart());
+
+        assert!(rules.parse(ADDRESS, "loading checkpoint shards").is_none());
+    }
+
+    #[test]
+    fn config_rules_test() {
+        crate::common::setup();
+        let config = r#"
+            [[rules]]
+            name = "speed"
+            regex = 'speed=(?P<speed>[\d\.]+)'
+            kind = "HASHRATE"
+            value = "speed"
+            min = 5
+        "#;
+        #[derive(serde::Deserialize)]
+        struct Log {
+            rules: Vec<Rule>,
+        }
+        let log = toml::from_str::<Log>(config).unwrap();
+        let rules = Rules::new(log.rules).unwrap();
+        let event = rules.parse(ADDRESS, "step 10 speed=3.5").unwrap();
+        assert_eq!("speed", event.rule);
+        assert_eq!("3.5", event.fields["speed"]);
+        assert_eq!(Threshold::BELOW, event.threshold);
+
+        let invalid = Rule {
+            regex: "(".to_string(),
+            ..Rules::default_rules()[0].clone()
+        };
+        assert!(Rules::new(vec![invalid]).is_err());
+    }
+
+    #[test]
+    fn run_logs_test() {
+        crate::common::setup();
+        let text = std::fs::read_to_string("./example/my_logs.json").unwrap();
+        let run_logs = text.parse::<RunLogs>().unwrap();
+        assert_eq!(3, run_logs.len());
+
+        let mut seen = HashSet::new();
+        assert_eq!(3, run_logs.take_new(&mut seen).len());
+        assert!(run_logs.take_new(&mut seen).is_empty());
+
+        // 追加一条失败的任务
+        let appended = text.trim_end().trim_end_matches(']').to_string()
+            + r#",{"WalletAddr":"nimble1ray0ewn9yfnwfe04d62lf4svvqug820dd53c78","CompletedTime":"2024-06-01 03:30:00","TrainRuntime":120.5,"Status":"Failed"}]"#;
+        let run_logs = appended.parse::<RunLogs>().unwrap();
+        let tasks = run_logs.take_new(&mut seen);
+        assert_eq!(1, tasks.len());
+        let event = tasks[0].to_event();
+        assert_eq!(RuleKind::COMPLETION, event.kind);
+        assert_eq!(
+            "nimble1ray0ewn9yfnwfe04d62lf4svvqug820dd53c78",
+            event.address
+        );
+        assert_eq!("Failed", event.fields["status"]);
+        assert_eq!(Some(120.5), event.value);
+        assert_eq!(
+            "2024-06-01 03:30:00",
+            event.time.format("%Y-%m-%d %H:%M:%S").to_string()
+        );
+
+        assert!("".parse::<RunLogs>().unwrap().is_empty());
+        assert!("[{".parse::<RunLogs>().is_err());
+    }
+
+    #[test]
+    fn seen_tasks_test() {
+        crate::common::setup();
+        let path = crate::common::temp_dir("seen_tasks").join("seen.json");
+        let text = std::fs::read_to_string("./example/my_logs.json").unwrap();
+        let run_logs = text.parse::<RunLogs>().unwrap();
+
+        let mut seen = SeenTasks::load(path.clone());
+        assert_eq!(3, run_logs.take_new(&mut seen).len());
+        seen.insert("removed".to_string());
+        seen.retain(&run_logs);
+        seen.save().unwrap();
+
+        // 重启后已发送的任务不再上报
+        let mut seen = SeenTasks::load(path.clone());
+        assert_eq!(3, seen.len());
+        assert!(run_logs.take_new(&mut seen).is_empty());
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,110 @@
+pub mod common;
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use monitor::{
+        metrics::{checked, timed, Metrics, METRICS},
+        monitor::nvidia::GpuTelemetry,
+    };
+
+    #[test]
+    fn render_test() {
+        crate::common::setup();
+        let mut metrics = Metrics::default();
+        assert_eq!("", metrics.render());
+
+        metrics.set("clore_wallets", &[("state", "DEPLOYED")], 3f64);
+        metrics.set("clore_wallets", &[("state", "NOTASSIGNED")], 1f64);
+        metrics.inc("clore_cancellations_total", &[("reason", "manual")], 1f64);
+        metrics.inc("clore_cancellations_total", &[("reason", "manual")], 1f64);
+        metrics.observe(
+            "clore_api_request_duration_seconds",
+            &[("endpoint", "v1/my_orders")],
+            0.5,
+        );
+        metrics.observe(
+            "clore_api_request_duration_seconds",
+            &[("endpoint", "v1/my_orders")],
+            0.25,
+        );
+        metrics.set(
+            "monitor_hashrate",
+            &[("address", "a\"b"), ("gpu", "0")],
+            1.5,
+        );
+        // 未注册的指标忽略
+        metrics.set("unknown", &[], 1f64);
+
+        let text = metrics.render();
+        assert!(text.contains("# TYPE clore_wallets gauge\n"));
+        assert!(text.contains("clore_wallets{state=\"DEPLOYED\"} 3\n"));
+        assert!(text.contains("clore_wallets{state=\"NOTASSIGNED\"} 1\n"));
+        assert!(text.contains("# TYPE clore_cancellations_total counter\n"));
+        assert!(text.contains("clore_cancellations_total{reason=\"manual\"} 2\n"));
+        assert!(text.contains("# TYPE clore_api_request_duration_seconds summary\n"));
+        assert!(text
+            .contains("clore_api_request_duration_seconds_sum{endpoint=\"v1/my_orders\"} 0.75\n"));
+        assert!(text
+            .contains("clore_api_request_duration_seconds_count{endpoint=\"v1/my_orders\"} 2\n"));
+        assert!(text.contains("monitor_hashrate{address=\"a\\\"b\",gpu=\"0\"} 1.5\n"));
+        assert!(!text.contains("unknown"));
+        assert!(!text.contains("clore_blocklist_size"));
+
+        metrics.clear("clore_wallets");
+        assert_eq!(None, metrics.get("clore_wallets", &[("state", "DEPLOYED")]));
+    }
+
+    #[tokio::test]
+    async fn timed_test() {
+        crate::common::setup();
+        let result = timed("test/ok", async { Ok::<u32, String>(1) }).await;
+        assert_eq!(Ok(1), result);
+        let result = timed("test/err", async { Err::<u32, String>("err".to_string()) }).await;
+        assert!(result.is_err());
+        // 请求成功但返回失败
+        let result = checked("test/err", Err::<u32, String>("code:1".to_string())).await;
+        assert!(result.is_err());
+        assert_eq!(Ok(1), checked("test/ok", Ok::<u32, String>(1)).await);
+
+        let metrics = Arc::clone(&METRICS);
+        let locked = metrics.lock().await;
+        assert_eq!(
+            None,
+            locked.get("clore_api_errors_total", &[("endpoint", "test/ok")])
+        );
+        assert_eq!(
+            Some(2f64),
+            locked.get("clore_api_errors_total", &[("endpoint", "test/err")])
+        );
+        assert!(locked
+            .render()
+            .contains("clore_api_request_duration_seconds_count{endpoint=\"test/ok\"} 1\n"));
+    }
+
+    #[test]
+    fn telemetry_test() {
+        crate::common::setup();
+        let text = "0, GPU-5e4c623f, 65, 100, 320.55, 20000, 24564, 75\n\
+                    1, GPU-13d44c72, 40, 0, [N/A], 1, 24564, [N/A]\n\
+                    bad line\n";
+        let telemetry = GpuTelemetry::parse(text);
+        assert_eq!(2, telemetry.len());
+        assert_eq!(
+            GpuTelemetry {
+                index: 0,
+                uuid: "GPU-5e4c623f".to_string(),
+                temperature: Some(65f64),
+                utilization: Some(100f64),
+                power: Some(320.55),
+                memory_used: Some(20000f64),
+                memory_total: Some(24564f64),
+                fan: Some(75f64),
+            },
+            telemetry[0]
+        );
+        assert_eq!(None, telemetry[1].power);
+        assert_eq!(None, telemetry[1].fan);
+    }
+}
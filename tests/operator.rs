@@ -0,0 +1,69 @@
+pub mod common;
+
+#[cfg(test)]
+mod test {
+    use monitor::server::{
+        address::{AddressType, Deployed, Wallet},
+        clore::model::market::Marketplace,
+        operator::{find_order, format_candidates},
+    };
+
+    #[test]
+    fn find_order_test() {
+        crate::common::setup();
+        let orders = crate::common::orders();
+        let mut deployed = Wallet::new("nimble1a".to_string(), AddressType::SUB);
+        deployed.deploy = Deployed::DEPLOYED {
+            orderid: 2001,
+            serverid: 1001,
+            sshaddr: None,
+            sshport: None,
+        };
+        // 刚下单时订单号为0，按服务器查找
+        let mut deploying = Wallet::new("nimble1b".to_string(), AddressType::SUB);
+        deploying.deploy = Deployed::DEPLOYING {
+            orderid: 0,
+            serverid: 1003,
+            sshaddr: None,
+            sshport: None,
+        };
+        let unused = Wallet::new("nimble1c".to_string(), AddressType::SUB);
+        let wallets = vec![deployed, deploying, unused];
+
+        let order = find_order("2001", &orders, &wallets).unwrap();
+        assert_eq!(1001, order.server_id);
+        assert_eq!(
+            Some("n1.c1.clorecloud.net".to_string()),
+            order.get_ssh_host()
+        );
+        assert_eq!(Some(10001), order.get_map_ssh_port());
+        assert_eq!(
+            2001,
+            find_order("nimble1a", &orders, &wallets).unwrap().order_id
+        );
+        assert_eq!(
+            2002,
+            find_order("nimble1b", &orders, &wallets).unwrap().order_id
+        );
+        assert!(find_order("9999", &orders, &wallets).is_err());
+        assert!(find_order("nimble1c", &orders, &wallets).is_err());
+        assert!(find_order("nimble1d", &orders, &wallets).is_err());
+    }
+
+    #[test]
+    fn format_candidates_test() {
+        crate::common::setup();
+        let row = std::fs::read_to_string("./example/market.json").unwrap();
+        let candidates = serde_json::from_str::<Marketplace>(&row)
+            .unwrap()
+            .candidates(&[1003]);
+        let text = format_candidates(&candidates);
+        let lines = text.lines().collect::<Vec<&str>>();
+        assert_eq!(5, lines.len());
+        assert!(lines[0].contains("serverid"));
+        // 按单卡价格排序，不能租用的显示原因
+        let server = |line: &str| line.split_whitespace().next().unwrap().to_string();
+        assert_eq!("1004", server(lines[1]));
+        assert!(lines.iter().any(|line| line.contains("BLOCKED")));
+    }
+}
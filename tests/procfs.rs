@@ -0,0 +1,69 @@
+pub mod common;
+
+#[cfg(test)]
+mod test {
+    use std::path::{Path, PathBuf};
+
+    use monitor::monitor::procfs::ProcFs;
+
+    fn fake_process(root: &Path, pid: u32, cmdline: &[&str], environ: &[&str], stat: &str) {
+        let dir = root.join(pid.to_string());
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("cmdline"), format!("{}\0", cmdline.join("\0"))).unwrap();
+        std::fs::write(dir.join("environ"), format!("{}\0", environ.join("\0"))).unwrap();
+        std::fs::write(dir.join("stat"), stat).unwrap();
+    }
+
+    fn fake_procfs(name: &str) -> PathBuf {
+        let root = crate::common::temp_dir(name);
+        std::fs::create_dir_all(root.join("self")).unwrap();
+        std::fs::write(root.join("uptime"), "1000.00 3000.00\n").unwrap();
+        root
+    }
+
+    #[test]
+    fn miner_processes_test() {
+        crate::common::setup();
+        let root = fake_procfs("procfs_miner");
+        fake_process(
+            &root,
+            4321,
+            &["python", "execute.py", "nimble1ha23ka8jzm63eupcrf0thlhhldu4j6z66shqjl"],
+            &["PATH=/usr/bin", "CUDA_VISIBLE_DEVICES=1"],
+            "4321 (python exe) S 1 4321 4321 0 -1 4194560 0 0 0 0 5000 1000 0 0 20 0 12 0 50000 0 0",
+        );
+        fake_process(
+            &root,
+            99,
+            &[
+                "bash",
+                "-c",
+                "grep execute.py nimble1ha23ka8jzm63eupcrf0thlhhldu4j6z66shqjl",
+            ],
+            &[],
+            "99 (bash) S 1 99 99 0 -1 4194560 0 0 0 0 1 1 0 0 20 0 1 0 100 0 0",
+        );
+
+        let processes = ProcFs::new(&root).miner_processes().unwrap();
+        assert_eq!(1, processes.len());
+        let process = &processes[0];
+        assert_eq!(4321, process.pid);
+        assert_eq!(
+            "nimble1ha23ka8jzm63eupcrf0thlhhldu4j6z66shqjl",
+            process.address
+        );
+        assert_eq!(Some("1".to_string()), process.cuda_visible_devices);
+        assert!((process.uptime - 500f64).abs() < 1e-6);
+        assert!((process.cpu_usage - 12f64).abs() < 1e-6);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn no_miner_processes_test() {
+        crate::common::setup();
+        let root = fake_procfs("procfs_empty");
+        let processes = ProcFs::new(&root).miner_processes().unwrap();
+        assert!(processes.is_empty());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
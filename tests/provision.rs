@@ -0,0 +1,128 @@
+pub mod common;
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use chrono::{Duration, Local};
+    use monitor::{
+        config,
+        server::{
+            address::{Address, AddressType, Deployed, Wallet},
+            provision::{pending, render_command, Provisioning, StepState, CONNECT},
+        },
+    };
+
+    fn wallet(address: &str, orderid: u32, serverid: u32) -> Wallet {
+        let mut wallet = Wallet::new(address.to_string(), AddressType::SUB);
+        wallet.deploy = Deployed::DEPLOYING {
+            orderid,
+            serverid,
+            sshaddr: None,
+            sshport: None,
+        };
+        wallet
+    }
+
+    #[test]
+    fn needs_run_test() {
+        crate::common::setup();
+        let now = Local::now();
+        let mut provision = Provisioning::new(2001);
+        assert!(provision.needs_run(2001, 300, now));
+        assert!(provision.needs_run(2002, 300, now));
+
+        // 部署中
+        provision.update(CONNECT, StepState::SUCCESS, "", now);
+        provision.update("env", StepState::RUNNING, "", now);
+        assert!(!provision.needs_run(2001, 300, now));
+        assert_eq!(Some(now), provision.running_since());
+
+        // ssh还未启动，立即重试
+        provision.update(CONNECT, StepState::FAILED, "TCP超时", now);
+        assert!(provision.needs_run(2001, 300, now));
+
+        // 其它步骤失败，间隔后重试
+        provision.update(CONNECT, StepState::SUCCESS, "", now);
+        provision.update("env", StepState::FAILED, "退出码:1", now);
+        assert_eq!(2, provision.steps.len());
+        assert!(!provision.needs_run(2001, 300, now + Duration::seconds(299)));
+        assert!(provision.needs_run(2001, 300, now + Duration::seconds(300)));
+        assert_eq!(None, provision.running_since());
+
+        provision.finished = Some(now);
+        assert!(!provision.needs_run(2001, 300, now + Duration::seconds(300)));
+        assert_eq!(
+            "orderid:2001,connect:SUCCESS,env:FAILED",
+            provision.to_string()
+        );
+
+        // 步骤超时需要小于25分钟的部署超时
+        assert!(config::Provision::default()
+            .steps
+            .iter()
+            .all(|step| step.timeout < 25 * 60));
+    }
+
+    #[test]
+    fn pending_test() {
+        crate::common::setup();
+        let orders = crate::common::orders();
+        let now = Local::now();
+
+        let mut finished = wallet("nimble1c", 2001, 1001);
+        let mut provision = Provisioning::new(2001);
+        provision.finished = Some(now);
+        finished.provision = Some(provision);
+        let wallets = Address(HashMap::from([
+            ("nimble1b".to_string(), wallet("nimble1b", 2001, 1001)),
+            ("nimble1a".to_string(), wallet("nimble1a", 2001, 1001)),
+            // 订单2002没有ssh端口
+            ("nimble1d".to_string(), wallet("nimble1d", 2002, 1003)),
+            // 还没有订单号
+            ("nimble1e".to_string(), wallet("nimble1e", 0, 1004)),
+            (
+                "nimble1f".to_string(),
+                Wallet::new("nimble1f".to_string(), AddressType::SUB),
+            ),
+        ]));
+        let result = pending(&wallets, &orders, 300, now);
+        assert_eq!(1, result.len());
+        assert_eq!(2001, result[0].0.order_id);
+        assert_eq!(
+            vec!["nimble1a".to_string(), "nimble1b".to_string()],
+            result[0].1
+        );
+
+        // 所有钱包都部署完成
+        let wallets = Address(HashMap::from([("nimble1c".to_string(), finished)]));
+        assert!(pending(&wallets, &orders, 300, now).is_empty());
+    }
+
+    #[test]
+    fn render_command_test() {
+        crate::common::setup();
+        let provision = config::Provision::default();
+        assert!(!provision.enabled);
+        let miner = provision
+            .steps
+            .iter()
+            .find(|step| step.per_address)
+            .unwrap();
+        let command = render_command(&miner.command, "nimble1a", 1);
+        assert!(command.contains("pm2 describe nimble1 "), "{}", command);
+        assert!(
+            command.contains("execute.sh start 1 nimble1a"),
+            "{}",
+            command
+        );
+        assert_eq!(
+            vec!["env.sh", "erust.sh", "execute.sh"],
+            provision
+                .files
+                .iter()
+                .map(|file| file.local.as_str())
+                .collect::<Vec<&str>>()
+        );
+    }
+}
@@ -0,0 +1,102 @@
+pub mod common;
+
+#[cfg(test)]
+mod test {
+    use chrono::Local;
+    use monitor::{
+        log::{
+            rule::{LogEvent, Rules},
+            RunLogs,
+        },
+        server::{
+            accounting::Accounting,
+            pull::{complete_lines, ingest_logs, ingest_tasks, start_offset, Chunk},
+            store::{LogQuery, LogRecord, LogStore},
+        },
+    };
+
+    const RUN_LOGS: &str = "/root/clore/nimble-miner-public/my_logs.json";
+
+    #[test]
+    fn offset_test() {
+        crate::common::setup();
+        assert_eq!(0, start_offset(None, 100));
+        assert_eq!(40, start_offset(Some(40), 100));
+        assert_eq!(100, start_offset(Some(100), 100));
+        // 文件被截断
+        assert_eq!(0, start_offset(Some(120), 100));
+
+        assert_eq!(0, complete_lines(b""));
+        assert_eq!(0, complete_lines(b"partial"));
+        assert_eq!(6, complete_lines(b"line1\npart"));
+        assert_eq!(11, complete_lines(b"line1\n 50%\r 6"));
+    }
+
+    #[test]
+    fn ingest_test() {
+        crate::common::setup();
+        let store = LogStore::open(crate::common::temp_dir("pull_store").join("logs.db")).unwrap();
+        let accounting =
+            Accounting::open(crate::common::temp_dir("pull_accounting").join("accounting.db"))
+                .unwrap();
+        let rules = Rules::default();
+        let address = "nimble1ha23ka8jzm63eupcrf0thlhhldu4j6z66shqjl";
+        let now = Local::now();
+        let chunk = Chunk {
+            path: format!("/root/clore/logs/{}.txt", address),
+            end: 120,
+            data: " 40%|████      | 400/1000 [00:30<00:37, 14.10it/s]\r 45%|████▌     | 450/1000 [00:30<00:37, 14.82it/s]\n{'loss': 0.1}\nstarted\nstarted\n"
+                .as_bytes()
+                .to_vec(),
+        };
+        // 和监控相同的规则解析，忽略的行不保存，重复的行都保存
+        assert_eq!(4, ingest_logs(&store, &rules, 1001, &chunk, now).unwrap());
+        let records = store
+            .query(&LogQuery {
+                server_id: Some(1001),
+                address: Some(address.to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(4, records.len());
+        let hashrate = records
+            .iter()
+            .filter(|record| record.kind == "HASHRATE")
+            .collect::<Vec<&LogRecord>>();
+        assert_eq!(2, hashrate.len());
+        let event = serde_json::from_str::<LogEvent>(&hashrate[0].body).unwrap();
+        assert_eq!(now.timestamp(), event.time.timestamp());
+        assert_eq!(
+            2,
+            records
+                .iter()
+                .filter(|record| record.kind == "RAW" && record.body == "started")
+                .count()
+        );
+
+        let run_logs = std::fs::read("./example/my_logs.json").unwrap();
+        let tasks = String::from_utf8_lossy(&run_logs)
+            .parse::<RunLogs>()
+            .unwrap()
+            .len();
+        let chunk = Chunk {
+            path: RUN_LOGS.to_string(),
+            end: run_logs.len() as u64,
+            data: run_logs,
+        };
+        assert_eq!(
+            tasks,
+            ingest_tasks(&accounting, 1001, &chunk, 1f64).unwrap()
+        );
+        // 任务记录重复拉取时不重复记账
+        assert_eq!(0, ingest_tasks(&accounting, 1001, &chunk, 1f64).unwrap());
+
+        // 文件名不是合法地址
+        let chunk = Chunk {
+            path: "/root/clore/logs/a b.txt".to_string(),
+            end: 4,
+            data: b"bad\n".to_vec(),
+        };
+        assert!(ingest_logs(&store, &rules, 1001, &chunk, now).is_err());
+    }
+}
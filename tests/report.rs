@@ -0,0 +1,124 @@
+pub mod common;
+
+#[cfg(test)]
+mod test {
+    use chrono::{Duration, Local, TimeZone};
+    use monitor::{
+        log::RunLogs,
+        monitor::hashrate::HashrateStats,
+        server::{
+            accounting::Accounting,
+            address::{Address, AddressType, Deployed, Wallet},
+            clore::model::my_orders::Order,
+            report::Profitability,
+        },
+    };
+
+    fn order(order_id: u32, server_id: u32, gpu: &str, price: f64, create_time: i64) -> Order {
+        let row = format!(
+            r#"{{"id":{},"si":{},"mrl":259200,"ct":{},"price":{},"pub_cluster":["n1.c1.clorecloud.net"],"tcp_ports":["22:10022"],"http_port":"8888","specs":{{"mb":"B450","cpu":"Intel Core i5","cpus":"8/16","ram":31.2,"disk":"ssd 256GB","disk_speed":1500,"gpu":"{}","gpuram":24,"net":{{"up":100,"down":100,"cc":"US"}}}}}}"#,
+            order_id, server_id, create_time, price, gpu
+        );
+        serde_json::from_str::<Order>(&row).unwrap()
+    }
+
+    #[test]
+    fn profitability_test() {
+        crate::common::setup();
+        let path = crate::common::temp_dir("report").join("accounting.db");
+        let accounting = Accounting::open(&path).unwrap();
+        let run_logs = std::fs::read_to_string("./example/my_logs.json")
+            .unwrap()
+            .parse::<RunLogs>()
+            .unwrap();
+        for task in run_logs.iter() {
+            accounting.record_task(1001, task, 2f64).unwrap();
+        }
+
+        let create_time = Local.with_ymd_and_hms(2024, 5, 31, 12, 0, 0).unwrap();
+        let now = create_time + Duration::hours(24);
+        let orders = vec![
+            order(
+                9001,
+                1001,
+                "2x NVIDIA GeForce RTX 4090",
+                48f64,
+                create_time.timestamp(),
+            ),
+            order(
+                9002,
+                1002,
+                "1x NVIDIA GeForce RTX 3080",
+                12f64,
+                create_time.timestamp(),
+            ),
+        ];
+
+        let mut wallets = Address::default();
+        for (address, avg_1h) in [("nimble1a", 15f64), ("nimble1b", 13f64)] {
+            let mut wallet = Wallet::new(address.to_string(), AddressType::SUB);
+            wallet.deploy = Deployed::DEPLOYED {
+                orderid: 9001,
+                serverid: 1001,
+                sshaddr: None,
+                sshport: None,
+            };
+            wallet.hashrate = Some(HashrateStats {
+                avg_1h: Some(avg_1h),
+                ..Default::default()
+            });
+            wallets.insert(address.to_string(), wallet);
+        }
+
+        let report =
+            Profitability::build(&orders, &wallets, &accounting, Some(10f64), now).unwrap();
+        let first = &report.orders[0];
+        assert_eq!(48f64, first.cost);
+        assert_eq!(48f64, first.gpu_hours);
+        assert_eq!(3, first.success);
+        assert_eq!(Some(16f64), first.cost_per_task);
+        assert_eq!(Some(1f64), first.cost_per_gpu_hour);
+        assert_eq!(Some(14f64), first.hashrate);
+        assert_eq!(vec!["nimble1a", "nimble1b"], first.addresses);
+
+        let second = &report.orders[1];
+        assert_eq!(0, second.tasks);
+        assert_eq!(None, second.cost_per_task);
+
+        assert_eq!(2, report.card_types.len());
+        let nvidia4090 = report
+            .card_types
+            .iter()
+            .find(|card_type| card_type.card_type == "NVIDIA4090")
+            .unwrap();
+        assert_eq!(Some(60f64), nvidia4090.income);
+        assert_eq!(Some(true), nvidia4090.break_even);
+        let nvidia3080 = report
+            .card_types
+            .iter()
+            .find(|card_type| card_type.card_type == "NVIDIA3080")
+            .unwrap();
+        assert_eq!(Some(false), nvidia3080.break_even);
+
+        let csv = report.to_csv();
+        let rows = csv.lines().collect::<Vec<&str>>();
+        assert_eq!(7, rows.len());
+        assert!(rows[0].starts_with("order_id,server_id,card_type"));
+        assert!(rows[1].starts_with("9001,1001,NVIDIA4090,2,48.0000,2024-05-31 12:00:00"));
+        assert_eq!("", rows[3]);
+        assert!(rows[4].starts_with("card_type,orders,cards"));
+        assert!(rows[5..]
+            .iter()
+            .any(|row| row.starts_with("NVIDIA4090,1,2,") && row.ends_with(",60.0000,true")));
+        assert!(rows[5..]
+            .iter()
+            .any(|row| row.starts_with("NVIDIA3080,") && row.ends_with(",false")));
+
+        // 超过可用时长(72小时)后不再计费
+        let later = create_time + Duration::hours(100);
+        let report =
+            Profitability::build(&orders, &wallets, &accounting, Some(10f64), later).unwrap();
+        assert_eq!(72f64, report.orders[0].hours);
+        assert_eq!(144f64, report.orders[0].cost);
+    }
+}
@@ -0,0 +1,169 @@
+pub mod common;
+
+#[cfg(test)]
+mod test {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::{mpsc, Arc, Mutex};
+
+    use monitor::auth::Signer;
+    use monitor::log::spool::Spool;
+    use tokio::time::{Duration, Instant};
+
+    /// 本地接收上报的http服务，记录收到的body
+    fn receiver() -> (String, Arc<Mutex<Vec<String>>>) {
+        receiver_with_gate(None)
+    }
+
+    /// 收到body后等待gate再响应，用于模拟上报过程中有新记录入队
+    fn receiver_with_gate(gate: Option<mpsc::Receiver<()>>) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let api = format!("http://{}/printlnlog", listener.local_addr().unwrap());
+        let bodies = Arc::new(Mutex::new(Vec::new()));
+        let received = Arc::clone(&bodies);
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim().to_lowercase();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some(value) = line.strip_prefix("content-length:") {
+                        length = value.trim().parse::<usize>().unwrap();
+                    }
+                }
+                let mut body = vec![0u8; length];
+                reader.read_exact(&mut body).unwrap();
+                received
+                    .lock()
+                    .unwrap()
+                    .push(String::from_utf8(body).unwrap());
+                if let Some(gate) = &gate {
+                    let _ = gate.recv();
+                }
+                let mut stream = stream;
+                let _ = stream.write_all(
+                    b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                );
+            }
+        });
+        (api, bodies)
+    }
+
+    #[test]
+    fn spool_test() {
+        crate::common::setup();
+        let mut spool = Spool::new(crate::common::temp_dir("spool"), 3, 2);
+        for i in 0..4 {
+            spool.push("nimble1a", &format!("{{\"n\":{}}}", i)).unwrap();
+        }
+        // 超过上限丢弃最早的记录
+        assert_eq!(3, spool.depth("nimble1a"));
+        assert_eq!(
+            vec![r#"{"n":1}"#, r#"{"n":2}"#],
+            spool.peek("nimble1a").unwrap()
+        );
+        let sent = spool.peek("nimble1a").unwrap();
+        spool.ack("nimble1a", &sent).unwrap();
+        assert_eq!(vec![r#"{"n":3}"#], spool.peek("nimble1a").unwrap());
+        spool.push("nimble1b", "line\nbreak").unwrap();
+        assert_eq!(vec!["line break"], spool.peek("nimble1b").unwrap());
+        assert_eq!(
+            vec![("nimble1a".to_string(), 1), ("nimble1b".to_string(), 1)],
+            spool.depths().into_iter().collect::<Vec<(String, usize)>>()
+        );
+
+        let now = Instant::now();
+        assert!(spool.is_due("nimble1a", now));
+        assert_eq!(Duration::from_secs(5), spool.failed("nimble1a", now));
+        assert_eq!(Duration::from_secs(10), spool.failed("nimble1a", now));
+        assert!(!spool.is_due("nimble1a", now));
+        assert!(spool.is_due("nimble1a", now + Duration::from_secs(10)));
+        for _ in 0..10 {
+            spool.failed("nimble1a", now);
+        }
+        assert_eq!(Duration::from_secs(300), spool.failed("nimble1a", now));
+        spool.succeeded("nimble1a");
+        assert!(spool.is_due("nimble1a", now));
+    }
+
+    #[tokio::test]
+    async fn flush_test() {
+        crate::common::setup();
+        let client = reqwest::ClientBuilder::new()
+            .timeout(std::time::Duration::from_secs(5))
+            .build()
+            .unwrap();
+        let signer = Signer::new(1, 1, "token");
+        let spool =
+            tokio::sync::Mutex::new(Spool::new(crate::common::temp_dir("spool_flush"), 100, 2));
+        for i in 0..5 {
+            spool
+                .lock()
+                .await
+                .push("nimble1a", &format!("{}", i))
+                .unwrap();
+        }
+
+        // 服务器不可用时保留队列并退避
+        Spool::flush(&spool, &client, "http://127.0.0.1:1/printlnlog", &signer).await;
+        assert_eq!(5, spool.lock().await.depth("nimble1a"));
+        assert!(!spool.lock().await.is_due("nimble1a", Instant::now()));
+        spool.lock().await.succeeded("nimble1a");
+
+        let (api, bodies) = receiver();
+        Spool::flush(&spool, &client, &api, &signer).await;
+        assert_eq!(0, spool.lock().await.depth("nimble1a"));
+        assert_eq!(vec!["0\n1", "2\n3", "4"], *bodies.lock().unwrap());
+    }
+
+    #[tokio::test]
+    async fn flush_overflow_test() {
+        crate::common::setup();
+        let client = reqwest::ClientBuilder::new()
+            .timeout(std::time::Duration::from_secs(5))
+            .build()
+            .unwrap();
+        let signer = Signer::new(1, 1, "token");
+        let spool = Arc::new(tokio::sync::Mutex::new(Spool::new(
+            crate::common::temp_dir("spool_overflow"),
+            3,
+            2,
+        )));
+        for i in 0..3 {
+            spool
+                .lock()
+                .await
+                .push("nimble1a", &format!("{}", i))
+                .unwrap();
+        }
+
+        let (gate, wait) = mpsc::channel();
+        let (api, bodies) = receiver_with_gate(Some(wait));
+        let flushing = Arc::clone(&spool);
+        let task = tokio::spawn(async move {
+            Spool::flush(&flushing, &client, &api, &signer).await;
+        });
+        while bodies.lock().unwrap().is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        // 上报0,1的过程中入队超过上限，0,1被丢弃，确认时不能再移除未上报的2,3
+        for i in 3..5 {
+            spool
+                .lock()
+                .await
+                .push("nimble1a", &format!("{}", i))
+                .unwrap();
+        }
+        for _ in 0..3 {
+            gate.send(()).unwrap();
+        }
+        task.await.unwrap();
+        assert_eq!(0, spool.lock().await.depth("nimble1a"));
+        assert_eq!(vec!["0\n1", "2\n3", "4"], *bodies.lock().unwrap());
+    }
+}
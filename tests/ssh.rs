@@ -0,0 +1,195 @@
+pub mod common;
+
+#[cfg(test)]
+mod test {
+    use std::{
+        net::{SocketAddr, TcpListener},
+        path::PathBuf,
+        sync::Arc,
+        time::{Duration, Instant},
+    };
+
+    use monitor::{
+        config::{self, CONFIG},
+        server::{
+            clore::model::my_orders::Order,
+            ssh::{Credential, HostKeys, Phase, Ssh, SshError},
+        },
+    };
+
+    fn order(order_id: u32) -> Order {
+        crate::common::orders()
+            .into_iter()
+            .find(|order| order.order_id == order_id)
+            .unwrap()
+    }
+
+    fn timeouts() -> config::Ssh {
+        config::Ssh {
+            concurrency: 4,
+            dns_timeout: 1,
+            connect_timeout: 1,
+            handshake_timeout: 1,
+            auth_timeout: 1,
+            exec_timeout: 1,
+            key_dir: None,
+            known_hosts: String::new(),
+        }
+    }
+
+    /// 只接受链接，不发送ssh版本信息
+    fn silent_listener() -> (TcpListener, SocketAddr) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        (listener, addr)
+    }
+
+    #[test]
+    fn parse_address_test() {
+        crate::common::setup();
+        let output = "python execute.py nimble1abc\npython execute.py nimble1def --gpu 1\nbash\n";
+        assert_eq!(
+            vec!["nimble1abc".to_string(), "nimble1def".to_string()],
+            Ssh::parse_address(output)
+        );
+        assert!(Ssh::parse_address("").is_empty());
+    }
+
+    #[tokio::test]
+    async fn failure_class_test() {
+        crate::common::setup();
+        // 没有ssh端口映射
+        let result =
+            Ssh::run_with_timeouts(&order(2002), &Credential::default(), "true", &timeouts()).await;
+        assert_eq!(Err(SshError::NOADDRESS), result);
+
+        // 端口未监听
+        let closed = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let result = Ssh::run_blocking(&Credential::default(), closed, "true", &timeouts()).await;
+        assert!(matches!(result, Err(SshError::TCP(_))), "{:?}", result);
+
+        // 握手阶段没有响应
+        let (_listener, addr) = silent_listener();
+        let result = Ssh::run_blocking(&Credential::default(), addr, "true", &timeouts()).await;
+        assert_eq!(Err(SshError::TIMEOUT(Phase::HANDSHAKE)), result);
+
+        // 总时长超时和线程异常记在传入的阶段
+        let slow = config::Ssh {
+            connect_timeout: 0,
+            handshake_timeout: 0,
+            auth_timeout: 0,
+            ..timeouts()
+        };
+        let result = Ssh::blocking(Phase::SFTP, &slow, |_| {
+            std::thread::sleep(Duration::from_secs(2));
+            Ok(())
+        })
+        .await;
+        assert_eq!(Err(SshError::TIMEOUT(Phase::SFTP)), result);
+        let result = Ssh::blocking::<()>(Phase::SFTP, &slow, |_| panic!("sftp")).await;
+        assert!(matches!(result, Err(SshError::SFTP(_))), "{:?}", result);
+    }
+
+    #[tokio::test]
+    async fn concurrent_probe_test() {
+        crate::common::setup();
+        let mut listeners = Vec::new();
+        let mut orders = Vec::new();
+        for order_id in 0..4 {
+            let (listener, addr) = silent_listener();
+            listeners.push(listener);
+            let mut order = order(2001);
+            order.order_id = order_id;
+            order.pub_cluster = vec![addr.ip().to_string()];
+            order.tcp_ports = vec![format!("22:{}", addr.port())];
+            orders.push(order);
+        }
+        let mut without_ssh = order(2002);
+        without_ssh.order_id = 4;
+        orders.push(without_ssh);
+
+        let config = Arc::clone(&CONFIG);
+        config.lock().await.ssh = timeouts();
+        let start = Instant::now();
+        let (address, mut errors) = Ssh::try_run_command_remote(&orders).await;
+        // 4个卡住的服务器并发检测，总耗时接近一个握手超时
+        assert!(
+            start.elapsed() < Duration::from_secs(3),
+            "{:?}",
+            start.elapsed()
+        );
+        assert!(address.is_empty());
+        errors.sort_by_key(|(order_id, _)| *order_id);
+        assert_eq!(
+            vec![
+                (0, SshError::TIMEOUT(Phase::HANDSHAKE)),
+                (1, SshError::TIMEOUT(Phase::HANDSHAKE)),
+                (2, SshError::TIMEOUT(Phase::HANDSHAKE)),
+                (3, SshError::TIMEOUT(Phase::HANDSHAKE)),
+                (4, SshError::NOADDRESS),
+            ],
+            errors
+        );
+    }
+
+    #[test]
+    fn host_keys_test() {
+        crate::common::setup();
+        let path = crate::common::temp_dir("host_keys").join("ssh_known_hosts.txt");
+        let mut host_keys = HostKeys::load(&path);
+        assert_eq!(Ok(true), host_keys.pin(2001, "SHA256:aa"));
+        assert_eq!(Ok(false), host_keys.pin(2001, "SHA256:aa"));
+        assert_eq!(Ok(true), host_keys.pin(2002, "SHA256:bb"));
+
+        // 重新加载后仍然固定，公钥变化返回错误
+        let mut host_keys = HostKeys::load(&path);
+        assert_eq!(Some(&"SHA256:aa".to_string()), host_keys.get(2001));
+        assert!(host_keys.pin(2001, "SHA256:cc").is_err());
+        assert_eq!(Some(&"SHA256:aa".to_string()), host_keys.get(2001));
+    }
+
+    #[test]
+    fn keypair_test() {
+        crate::common::setup();
+        let key_dir = crate::common::temp_dir("ssh_keys");
+        let key_dir = key_dir.to_str().unwrap();
+        let mut config = timeouts();
+        config.key_dir = Some(key_dir.to_string());
+        config.known_hosts = "ssh_known_hosts.txt".to_string();
+
+        // 没有生成密钥时只使用密码
+        let credential = Credential::for_order(&order(2001), "passwd", &config);
+        assert_eq!(None, credential.private_key);
+        assert_eq!(
+            Some((PathBuf::from("ssh_known_hosts.txt"), 2001)),
+            credential.known_hosts
+        );
+
+        let public_key = Ssh::generate_keypair(key_dir, 1001).unwrap();
+        assert!(public_key.starts_with("ssh-ed25519 "), "{}", public_key);
+        assert!(public_key.ends_with("clore-1001"), "{}", public_key);
+        let credential = Credential::for_order(&order(2001), "passwd", &config);
+        assert_eq!(Some(Ssh::key_path(key_dir, 1001)), credential.private_key);
+
+        // 重新下单时生成新的密钥
+        let regenerated = Ssh::generate_keypair(key_dir, 1001).unwrap();
+        assert_ne!(public_key, regenerated);
+    }
+
+    #[test]
+    fn authorize_key_command_test() {
+        crate::common::setup();
+        let command = Ssh::authorize_key_command("\n#!/bin/bash\ncd $HOME\n");
+        let lines = command.lines().collect::<Vec<&str>>();
+        assert_eq!("#!/bin/bash", lines[0]);
+        assert!(lines[1].contains("\"$SSH_PUBLIC_KEY\" >> ~/.ssh/authorized_keys"));
+        assert_eq!("cd $HOME", lines[2]);
+
+        let command = Ssh::authorize_key_command("cd $HOME");
+        assert!(command.starts_with("mkdir -p ~/.ssh"));
+        assert!(command.ends_with("\ncd $HOME"));
+    }
+}
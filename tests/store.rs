@@ -0,0 +1,91 @@
+pub mod common;
+
+#[cfg(test)]
+mod test {
+    use chrono::{Local, TimeZone};
+    use monitor::log::rule::Rules;
+    use monitor::server::store::{validate_identifier, LogQuery, LogStore};
+
+    fn open(name: &str) -> LogStore {
+        let path = crate::common::temp_dir(name).join("logs.db");
+        LogStore::open(path).unwrap()
+    }
+
+    #[test]
+    fn identifier_test() {
+        crate::common::setup();
+        assert!(validate_identifier("nimble1ha23ka8jzm63eupcrf0thlhhldu4j6z66shqjl").is_ok());
+        assert!(validate_identifier("my_logs").is_ok());
+        assert!(validate_identifier("../../etc/passwd").is_err());
+        assert!(validate_identifier("a/b").is_err());
+        assert!(validate_identifier("").is_err());
+    }
+
+    #[test]
+    fn ingest_query_test() {
+        crate::common::setup();
+        let store = open("store");
+        let rules = Rules::default();
+        let event = rules
+            .parse(
+                "nimble1a",
+                " 45%|████▌     | 450/1000 [00:30<00:37, 14.82it/s]",
+            )
+            .unwrap();
+        let body = format!(
+            "{}\nplain text line\n\n",
+            serde_json::to_string(&event).unwrap()
+        );
+        let now = Local.with_ymd_and_hms(2024, 6, 1, 8, 0, 0).unwrap();
+        assert_eq!(2, store.ingest(1001, "nimble1a", &body, now).unwrap().len());
+        let records = store.ingest(1002, "nimble1b", "other", now).unwrap();
+        assert_eq!(3, records[0].id);
+        assert!(store.ingest(1001, "../nimble1a", "x", now).is_err());
+
+        let records = store.query(&LogQuery::default()).unwrap();
+        assert_eq!(3, records.len());
+
+        let records = store
+            .query(&LogQuery {
+                server_id: Some(1001),
+                kind: Some("RAW".to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(1, records.len());
+        assert_eq!("plain text line", records[0].body);
+        assert_eq!(now.timestamp(), records[0].timestamp);
+
+        let records = store
+            .query(&LogQuery {
+                address: Some("nimble1a".to_string()),
+                kind: Some("HASHRATE".to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(1, records.len());
+        assert_eq!(event.time.timestamp(), records[0].timestamp);
+
+        let records = store
+            .query(&LogQuery {
+                since: Some(now.timestamp() + 1),
+                until: Some(now.timestamp() + 2),
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(records.is_empty());
+
+        let records = store
+            .query(&LogQuery {
+                limit: Some(1),
+                server_id: Some(1002),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!("other", records[0].body);
+
+        // 清理之前的日志，事件时间为当前时间所以保留
+        assert_eq!(2, store.prune(now.timestamp() + 1).unwrap());
+        assert_eq!(1, store.query(&LogQuery::default()).unwrap().len());
+    }
+}
@@ -0,0 +1,54 @@
+pub mod common;
+
+#[cfg(test)]
+mod test {
+    use futures::StreamExt;
+    use monitor::server::store::LogRecord;
+    use monitor::server::stream::{self, SseParser, StreamQuery};
+
+    fn record(id: i64, server_id: u32) -> LogRecord {
+        LogRecord {
+            id,
+            server_id,
+            address: "nimble1a".to_string(),
+            kind: "HASHRATE".to_string(),
+            timestamp: 1717200000,
+            body: "{}".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn subscribe_test() {
+        crate::common::setup();
+        let query = StreamQuery {
+            server_id: Some(1001),
+            ..Default::default()
+        };
+        let events = stream::subscribe(&query).await.unwrap();
+        let mut events = Box::pin(events);
+        stream::publish(&[record(1, 1002), record(2, 1001)]);
+        let event = events.next().await.unwrap().unwrap();
+        let mut parser = SseParser::default();
+        let parsed = parser.feed(&event);
+        assert_eq!(1, parsed.len());
+        assert_eq!("log", parsed[0].0);
+        let received = serde_json::from_str::<LogRecord>(&parsed[0].1).unwrap();
+        assert_eq!(record(2, 1001), received);
+    }
+
+    #[test]
+    fn parser_test() {
+        crate::common::setup();
+        let mut parser = SseParser::default();
+        let text = stream::to_sse(&record(3, 1001)) + &stream::heartbeat();
+        let (first, second) = text.as_bytes().split_at(10);
+        assert!(parser.feed(first).is_empty());
+        let events = parser.feed(second);
+        assert_eq!(2, events.len());
+        assert_eq!("log", events[0].0);
+        assert_eq!("heartbeat", events[1].0);
+        assert!(record(3, 1001)
+            .to_string()
+            .contains("[1001] nimble1a HASHRATE"));
+    }
+}
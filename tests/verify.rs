@@ -0,0 +1,202 @@
+pub mod common;
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use chrono::{Duration, Local};
+    use monitor::{
+        config,
+        server::{
+            address::{Address, AddressType, Deployed, Wallet},
+            clore::{model::CardType, Clore},
+            verify::{check, pending, Gpu, Hardware, Mismatch, VerifyState, SPEC_MISMATCH},
+        },
+    };
+
+    const OUTPUT: &str = "NVIDIA GeForce RTX 4090, 24564
+NVIDIA GeForce RTX 4090, 24564
+== cpus
+32
+== ram
+MemTotal:       65755468 kB
+== disk
+overlay 1056763236 8239876 994828092 1% /
+";
+
+    #[test]
+    fn parse_test() {
+        crate::common::setup();
+        let hardware = Hardware::parse(OUTPUT);
+        assert_eq!(2, hardware.gpus.len());
+        assert_eq!(
+            Gpu {
+                name: "NVIDIA GeForce RTX 4090".to_string(),
+                memory_total: Some(24564f64),
+            },
+            hardware.gpus[0]
+        );
+        assert_eq!(Some(32), hardware.cpu_threads);
+        assert_eq!("62.7", format!("{:.1}", hardware.ram.unwrap()));
+        assert_eq!("1007.8", format!("{:.1}", hardware.disk.unwrap()));
+
+        // nvidia-smi不可用
+        let hardware =
+            Hardware::parse("bash: nvidia-smi: command not found\n== cpus\n32\n== ram\n== disk\n");
+        assert!(hardware.gpus.is_empty());
+        assert_eq!(Some(32), hardware.cpu_threads);
+        assert_eq!(None, hardware.ram);
+        assert_eq!(None, hardware.disk);
+    }
+
+    #[test]
+    fn check_test() {
+        crate::common::setup();
+        let config = config::Verify::default();
+        let specs = crate::common::orders()[0].specs.clone();
+        assert_eq!(Some(32), specs.get_cpu_threads());
+        assert_eq!(Some(1024f64), specs.get_disk_size());
+        assert_eq!("NVIDIA GeForce RTX 4090", specs.get_gpu_name());
+
+        let hardware = Hardware::parse(OUTPUT);
+        assert!(check(&specs, &hardware, &config).unwrap().is_empty());
+
+        // 比标注多不算不一致
+        let mut more = hardware.clone();
+        more.cpu_threads = Some(64);
+        more.ram = Some(125.0);
+        assert!(check(&specs, &more, &config).unwrap().is_empty());
+
+        let mut fake = hardware.clone();
+        fake.gpus = vec![Gpu {
+            name: "NVIDIA GeForce RTX 3090".to_string(),
+            memory_total: Some(12288f64),
+        }];
+        fake.cpu_threads = Some(8);
+        fake.ram = Some(31.3);
+        fake.disk = Some(200f64);
+        let mismatches = check(&specs, &fake, &config).unwrap();
+        assert_eq!(
+            vec![
+                Mismatch::GPUCOUNT {
+                    expected: 2,
+                    actual: 1
+                },
+                Mismatch::GPUMODEL {
+                    expected: CardType::NVIDIA4090,
+                    actual: CardType::NVIDIA3090
+                },
+                Mismatch::VRAM {
+                    expected: 24f64,
+                    actual: 12f64
+                },
+                Mismatch::CPU {
+                    expected: 32,
+                    actual: 8
+                },
+                Mismatch::RAM {
+                    expected: 62.7,
+                    actual: 31.3
+                },
+                Mismatch::DISK {
+                    expected: 1024f64,
+                    actual: 200f64
+                },
+            ],
+            mismatches
+        );
+        assert_eq!(
+            "显卡型号:标注NVIDIA4090,实际NVIDIA3090",
+            mismatches[1].to_string()
+        );
+
+        // 读取不到的项不检查
+        let partial = Hardware {
+            gpus: hardware.gpus.clone(),
+            ..Default::default()
+        };
+        assert!(check(&specs, &partial, &config).unwrap().is_empty());
+
+        // 读取不到显卡时无法检查，稍后重试
+        assert!(check(&specs, &Hardware::default(), &config).is_err());
+
+        // 标注和实际型号都无法识别时无法检查
+        let mut unknown_specs = specs.clone();
+        unknown_specs.gpu = "2x NVIDIA A10G".to_string();
+        let mut unknown = hardware.clone();
+        for gpu in unknown.gpus.iter_mut() {
+            gpu.name = "NVIDIA A10G".to_string();
+        }
+        assert!(check(&unknown_specs, &unknown, &config).is_err());
+        // 只有标注无法识别时同样无法检查
+        assert!(check(&unknown_specs, &hardware, &config).is_err());
+        // 只有实际型号无法识别时为型号不一致
+        assert!(matches!(
+            check(&specs, &unknown, &config).unwrap()[..],
+            [Mismatch::GPUMODEL { .. }]
+        ));
+    }
+
+    #[test]
+    fn pending_test() {
+        crate::common::setup();
+        let now = Local::now();
+        let orders = crate::common::orders();
+        let mut wallet = Wallet::new("nimble1a".to_string(), AddressType::SUB);
+        wallet.deploy = Deployed::DEPLOYING {
+            orderid: 2001,
+            serverid: 1001,
+            sshaddr: None,
+            sshport: None,
+        };
+        let mut other = Wallet::new("nimble1b".to_string(), AddressType::SUB);
+        // 订单2002没有ssh端口
+        other.deploy = Deployed::DEPLOYING {
+            orderid: 2002,
+            serverid: 1003,
+            sshaddr: None,
+            sshport: None,
+        };
+        let wallets = Address(HashMap::from([
+            ("nimble1a".to_string(), wallet),
+            ("nimble1b".to_string(), other),
+        ]));
+
+        let mut states = HashMap::new();
+        let result = pending(&wallets, &orders, &states, 60, now);
+        assert_eq!(
+            vec![2001],
+            result
+                .iter()
+                .map(|order| order.order_id)
+                .collect::<Vec<u32>>()
+        );
+
+        states.insert(2001, VerifyState::RUNNING);
+        assert!(pending(&wallets, &orders, &states, 60, now).is_empty());
+        states.insert(2001, VerifyState::MISMATCH);
+        assert!(pending(&wallets, &orders, &states, 60, now).is_empty());
+
+        // 失败后间隔重试
+        states.insert(2001, VerifyState::FAILED(now));
+        assert!(pending(&wallets, &orders, &states, 60, now + Duration::seconds(59)).is_empty());
+        assert_eq!(
+            1,
+            pending(&wallets, &orders, &states, 60, now + Duration::seconds(60)).len()
+        );
+    }
+
+    #[test]
+    fn block_reason_test() {
+        crate::common::setup();
+        assert!(Clore::append_block_server_id_with_reason(
+            88888,
+            SPEC_MISMATCH
+        ));
+        let text = std::fs::read_to_string("./block_server_ids.txt").unwrap();
+        assert!(text.contains("88888 spec mismatch"), "{}", text);
+        assert!(Clore::import_block_server_ids().contains(&88888));
+        assert!(Clore::remove_block_server_id(88888).unwrap());
+        assert!(!Clore::import_block_server_ids().contains(&88888));
+    }
+}
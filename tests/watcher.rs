@@ -0,0 +1,67 @@
+pub mod common;
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use monitor::log::{
+        watcher::{WatchEvent, Watcher},
+        Logs,
+    };
+    use regex::Regex;
+
+    async fn next(watcher: &mut Watcher) -> WatchEvent {
+        tokio::time::timeout(Duration::from_secs(5), watcher.next())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn watch_test() {
+        crate::common::setup();
+        let dir = crate::common::temp_dir("watcher");
+        let mut watcher = Watcher::new(std::slice::from_ref(&dir)).unwrap();
+
+        let path = dir.join("nimble1a.txt");
+        std::fs::write(&path, "line\n").unwrap();
+        assert_eq!(WatchEvent::CHANGED(path.clone()), next(&mut watcher).await);
+
+        std::fs::remove_file(&path).unwrap();
+        loop {
+            match next(&mut watcher).await {
+                WatchEvent::CHANGED(_) => continue,
+                event => {
+                    assert_eq!(WatchEvent::REMOVED(path), event);
+                    break;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn include_test() {
+        crate::common::setup();
+        let dir = crate::common::temp_dir("watcher_include");
+        std::fs::write(dir.join("nimble1a.txt"), "").unwrap();
+        std::fs::write(dir.join("nimble1b.txt.1"), "").unwrap();
+        std::fs::write(dir.join("notes.txt"), "").unwrap();
+        std::fs::create_dir_all(dir.join("nimble1c.txt")).unwrap();
+
+        let include = Regex::new(&monitor::config::Log::default().include).unwrap();
+        let mut logs = Logs::new();
+        logs.iter_log_files(&dir, &include);
+        let files = logs
+            .iter()
+            .map(|log| log.filename.clone())
+            .filter(|filename| filename.starts_with(&dir))
+            .collect::<Vec<PathBuf>>();
+        assert_eq!(vec![dir.join("nimble1a.txt")], files);
+
+        // 目录不存在时创建，不会panic
+        let missing = dir.join("missing");
+        logs.iter_log_files(&missing, &include);
+        assert!(missing.exists());
+    }
+}